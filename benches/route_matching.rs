@@ -0,0 +1,76 @@
+//! Route lookup cost for a 10k route table, indexed vs. a linear scan.
+//!
+//! Run with `cargo bench --bench route_matching`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use hyper::Request;
+
+use cloud_gateway::gateway;
+use gateway::config::{PredicateConfig, RouteConfig};
+use gateway::config_loader::build_routes;
+use gateway::route_table::RouteTable;
+
+const ROUTES: usize = 10_000;
+
+fn route_config(i: usize) -> RouteConfig {
+    let mut predicates = vec![
+        PredicateConfig::Path { path: format!("/service-{i}/items/{{id}}"), pattern: true },
+        PredicateConfig::Method { method: "GET".to_string() },
+    ];
+    if i % 10 == 0 {
+        let host = format!("tenant-{i}.example.org");
+        predicates.push(PredicateConfig::Host { patterns: vec![host] });
+    }
+    RouteConfig {
+        id: format!("route-{i}"),
+        order: 0,
+        destination: Some(format!("http://127.0.0.1:{}", 8000 + i % 1000)),
+        response: None,
+        predicates,
+        filters: Vec::new(),
+        skip_default_filters: Vec::new(),
+    }
+}
+
+fn request(path: &str, host: &str) -> Request<()> {
+    Request::builder().uri(path).header("host", host).body(()).unwrap()
+}
+
+fn route_matching(c: &mut Criterion) {
+    let routes = (0..ROUTES).map(route_config).collect();
+    let config = gateway::config::Config {
+        cors: None,
+        default_filters: Vec::new(),
+        access_log: None,
+        tracing: None,
+        admin: None,
+        routes,
+    };
+    let routes = build_routes(config).unwrap();
+    let table = RouteTable::new(routes.clone());
+
+    let cases = [
+        ("first", request("/service-1/items/42", "api.example.org")),
+        ("last", request(&format!("/service-{}/items/42", ROUTES - 1), "api.example.org")),
+        ("host", request("/service-5000/items/42", "tenant-5000.example.org")),
+        ("miss", request("/unknown/items/42", "api.example.org")),
+    ];
+
+    let mut group = c.benchmark_group("route_matching_10k");
+    for (name, req) in &cases {
+        group.bench_function(format!("index/{name}"), |b| {
+            b.iter(|| black_box(table.find(black_box(req)).map(|route| route.order)))
+        });
+        group.bench_function(format!("linear/{name}"), |b| {
+            b.iter(|| {
+                black_box(
+                    routes.iter().find(|route| route.matches(black_box(req))).map(|r| r.order),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, route_matching);
+criterion_main!(benches);
@@ -1,10 +1,17 @@
+pub mod access_log;
 pub mod bodies;
 pub mod config;
 pub mod config_loader;
+pub mod config_watcher;
 pub mod errors;
 pub mod filters;
+pub mod http_client;
+pub mod metrics;
 pub mod predicates;
 pub mod route;
+pub mod route_table;
+pub mod template;
+pub mod trace;
 
 use hyper::Request;
 use predicates::Predicate;
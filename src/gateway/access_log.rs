@@ -0,0 +1,488 @@
+use std::fs::OpenOptions;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use http::header::{REFERER, USER_AGENT};
+use http::{Method, Request, Response, Uri, Version};
+use serde_json::Value;
+use tokio::fs::File;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+
+use crate::gateway::bodies::{BoxBody, CountingBody};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::filters::{Principal, RequestIdValue};
+use crate::gateway::route::RouteId;
+use crate::gateway::template::Template;
+
+/// Variables of a record; JSON lines have one key for each.
+const FIELDS: [&str; 16] = [
+    "timestamp",
+    "client_ip",
+    "method",
+    "uri",
+    "protocol",
+    "route_id",
+    "upstream",
+    "status",
+    "bytes_in",
+    "bytes_out",
+    "upstream_latency_ms",
+    "latency_ms",
+    "request_id",
+    "referer",
+    "user_agent",
+    "principal",
+];
+
+/// Writes are batched up to this size before each flush.
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+
+/// Writes one line per request once its response body has been sent. Lines
+/// are handed to a background task, so the request path never waits on I/O;
+/// when the task falls behind, lines are dropped instead.
+#[derive(Clone, Debug)]
+pub struct AccessLog {
+    format: Arc<LogFormat>,
+    lines: mpsc::Sender<String>,
+}
+
+#[derive(Debug)]
+pub enum LogFormat {
+    Json,
+    Common,
+    Combined,
+    Template(Template),
+}
+
+#[derive(Debug)]
+pub enum LogOutput {
+    Stdout,
+    File { path: PathBuf, max_bytes: u64, max_files: usize },
+}
+
+impl LogFormat {
+    pub fn template(template: &str) -> Result<Self, GatewayError> {
+        let parsed = Template::parse(template)?;
+        parsed.render(|name| FIELDS.contains(&name).then(String::new)).map_err(|name| {
+            GatewayError::InvalidConfig(format!(
+                "access log template '{}' uses unknown variable '{}'",
+                template, name
+            ))
+        })?;
+        Ok(LogFormat::Template(parsed))
+    }
+}
+
+impl AccessLog {
+    /// Opens the output and starts the task writing to it. Must be called
+    /// from within the runtime.
+    pub fn new(format: LogFormat, output: LogOutput, buffer: usize) -> Result<Self, GatewayError> {
+        let sink = Sink::open(output)?;
+        let (lines, receiver) = mpsc::channel(buffer.max(1));
+        tokio::spawn(write_lines(receiver, sink));
+        Ok(Self { format: Arc::new(format), lines })
+    }
+
+    /// Logs the exchange once the response body is done with, counting the
+    /// bytes sent to the client on the way.
+    pub fn attach(&self, record: AccessRecord, response: Response<BoxBody>) -> Response<BoxBody> {
+        let log = self.clone();
+        let status = response.status().as_u16();
+        let context = response.extensions().get::<RequestContext>().cloned().unwrap_or_default();
+        let upstream = response.extensions().get::<UpstreamExchange>().cloned();
+        response.map(|body| -> BoxBody {
+            Box::pin(CountingBody::new(body, move |bytes_out| {
+                let line = log.format.line(&CompletedRecord {
+                    record,
+                    context,
+                    upstream,
+                    status,
+                    bytes_out,
+                });
+                // Full or closed; the request must not wait for the log
+                let _ = log.lines.try_send(line);
+            }))
+        })
+    }
+}
+
+/// What is known about a request before it is routed.
+#[derive(Debug)]
+pub struct AccessRecord {
+    timestamp: DateTime<Utc>,
+    started: Instant,
+    client_ip: IpAddr,
+    method: Method,
+    uri: Uri,
+    version: Version,
+    referer: Option<String>,
+    user_agent: Option<String>,
+}
+
+impl AccessRecord {
+    pub fn new<B>(req: &Request<B>, remote_addr: SocketAddr) -> Self {
+        let header = |name| {
+            req.headers().get(name).and_then(|value| value.to_str().ok()).map(str::to_string)
+        };
+        Self {
+            timestamp: Utc::now(),
+            started: Instant::now(),
+            client_ip: remote_addr.ip(),
+            method: req.method().clone(),
+            uri: req.uri().clone(),
+            version: req.version(),
+            referer: header(REFERER),
+            user_agent: header(USER_AGENT),
+        }
+    }
+}
+
+/// What routing and filters learned about a request, carried over to its
+/// response for the access log.
+#[derive(Clone, Debug, Default)]
+pub struct RequestContext {
+    pub route_id: Option<String>,
+    pub request_id: Option<String>,
+    pub principal: Option<String>,
+}
+
+impl RequestContext {
+    pub fn of<B>(req: &Request<B>) -> Self {
+        let extensions = req.extensions();
+        Self {
+            route_id: extensions.get::<RouteId>().map(|id| id.0.clone()),
+            request_id: extensions.get::<RequestIdValue>().map(|id| id.0.clone()),
+            principal: extensions.get::<Principal>().map(|principal| principal.0.clone()),
+        }
+    }
+}
+
+/// The upstream side of a forwarded request, stored in the response's
+/// extensions.
+#[derive(Clone, Debug)]
+pub struct UpstreamExchange {
+    pub address: String,
+    started: Instant,
+    /// Until the upstream's response headers arrived, or the attempt failed.
+    pub latency: Duration,
+    /// Request body bytes sent, counted while the body is streamed.
+    pub bytes_sent: Arc<AtomicU64>,
+}
+
+impl UpstreamExchange {
+    pub fn new(address: String) -> Self {
+        let bytes_sent = Arc::default();
+        Self { address, started: Instant::now(), latency: Duration::ZERO, bytes_sent }
+    }
+
+    pub fn attach(mut self, mut response: Response<BoxBody>) -> Response<BoxBody> {
+        self.latency = self.started.elapsed();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+struct CompletedRecord {
+    record: AccessRecord,
+    context: RequestContext,
+    upstream: Option<UpstreamExchange>,
+    status: u16,
+    bytes_out: u64,
+}
+
+impl CompletedRecord {
+    fn bytes_in(&self) -> u64 {
+        self.upstream.as_ref().map_or(0, |upstream| upstream.bytes_sent.load(Ordering::Relaxed))
+    }
+
+    fn value(&self, name: &str) -> Value {
+        let record = &self.record;
+        let text = |value: &Option<String>| value.as_deref().map_or(Value::Null, Value::from);
+        let millis = |duration: Duration| (duration.as_secs_f64() * 1e6).round() / 1e3;
+        match name {
+            "timestamp" => record.timestamp.to_rfc3339_opts(SecondsFormat::Millis, true).into(),
+            "client_ip" => record.client_ip.to_string().into(),
+            "method" => record.method.as_str().into(),
+            "uri" => record.uri.to_string().into(),
+            "protocol" => format!("{:?}", record.version).into(),
+            "route_id" => text(&self.context.route_id),
+            "upstream" => self.upstream.as_ref().map_or(Value::Null, |u| u.address.clone().into()),
+            "status" => self.status.into(),
+            "bytes_in" => self.bytes_in().into(),
+            "bytes_out" => self.bytes_out.into(),
+            "upstream_latency_ms" => {
+                self.upstream.as_ref().map_or(Value::Null, |u| millis(u.latency).into())
+            }
+            "latency_ms" => millis(record.started.elapsed()).into(),
+            "request_id" => text(&self.context.request_id),
+            "referer" => text(&record.referer),
+            "user_agent" => text(&record.user_agent),
+            "principal" => text(&self.context.principal),
+            _ => Value::Null,
+        }
+    }
+}
+
+impl LogFormat {
+    fn line(&self, completed: &CompletedRecord) -> String {
+        match self {
+            LogFormat::Json => {
+                let fields = FIELDS.iter().map(|name| (name.to_string(), completed.value(name)));
+                Value::Object(fields.collect()).to_string()
+            }
+            LogFormat::Common => common_line(completed),
+            LogFormat::Combined => {
+                let quoted = |value: &Option<String>| value.as_deref().unwrap_or("-").to_string();
+                format!(
+                    "{} \"{}\" \"{}\"",
+                    common_line(completed),
+                    escape(&quoted(&completed.record.referer)),
+                    escape(&quoted(&completed.record.user_agent))
+                )
+            }
+            LogFormat::Template(template) => template
+                .render(|name| {
+                    Some(match completed.value(name) {
+                        Value::Null => "-".to_string(),
+                        Value::String(text) => text,
+                        value => value.to_string(),
+                    })
+                })
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// `host ident authuser [date] "request" status bytes`, the principal
+/// standing in for the authenticated user.
+fn common_line(completed: &CompletedRecord) -> String {
+    let record = &completed.record;
+    let time = record.timestamp.with_timezone(&Local).format("%d/%b/%Y:%H:%M:%S %z");
+    let bytes = match completed.bytes_out {
+        0 => "-".to_string(),
+        bytes => bytes.to_string(),
+    };
+    format!(
+        "{} - {} [{}] \"{} {} {:?}\" {} {}",
+        record.client_ip,
+        completed.context.principal.as_deref().map_or("-".to_string(), escape),
+        time,
+        record.method,
+        escape(&record.uri.to_string()),
+        record.version,
+        completed.status,
+        bytes
+    )
+}
+
+/// Keeps client-supplied text from breaking quoted fields or lines.
+fn escape(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '"' | '\\' => vec!['\\', c],
+            c if c.is_control() => c.escape_default().collect(),
+            c => vec![c],
+        })
+        .collect()
+}
+
+enum Sink {
+    Stdout(tokio::io::Stdout),
+    File { file: File, size: u64, path: PathBuf, max_bytes: u64, max_files: usize },
+}
+
+impl Sink {
+    /// Opens the file right away so a bad path fails the config load.
+    fn open(output: LogOutput) -> Result<Self, GatewayError> {
+        match output {
+            LogOutput::Stdout => Ok(Sink::Stdout(tokio::io::stdout())),
+            LogOutput::File { path, max_bytes, max_files } => {
+                let (file, size) = open_file(&path).map_err(|err| {
+                    GatewayError::InvalidConfig(format!(
+                        "cannot open access log '{}': {}",
+                        path.display(),
+                        err
+                    ))
+                })?;
+                Ok(Sink::File { file, size, path, max_bytes, max_files })
+            }
+        }
+    }
+
+    async fn write(&mut self, batch: &[u8]) -> std::io::Result<()> {
+        match self {
+            Sink::Stdout(stdout) => write_all(stdout, batch).await,
+            Sink::File { file, size, path, max_bytes, max_files } => {
+                if *size > 0 && *size + batch.len() as u64 > *max_bytes {
+                    file.flush().await?;
+                    rotate(path, *max_files).await?;
+                    (*file, *size) = open_file(path)?;
+                }
+                write_all(file, batch).await?;
+                *size += batch.len() as u64;
+                Ok(())
+            }
+        }
+    }
+}
+
+fn open_file(path: &PathBuf) -> std::io::Result<(File, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok((File::from_std(file), size))
+}
+
+async fn write_all(out: &mut (impl AsyncWrite + Unpin), batch: &[u8]) -> std::io::Result<()> {
+    out.write_all(batch).await?;
+    out.flush().await
+}
+
+/// Shifts `path.N-1` to `path.N` down to `path` to `path.1`, dropping the
+/// oldest file.
+async fn rotate(path: &PathBuf, max_files: usize) -> std::io::Result<()> {
+    let numbered = |n: usize| {
+        let mut name = path.clone().into_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    };
+    if max_files == 0 {
+        return tokio::fs::remove_file(path).await;
+    }
+    let _ = tokio::fs::remove_file(numbered(max_files)).await;
+    for n in (1..max_files).rev() {
+        let _ = tokio::fs::rename(numbered(n), numbered(n + 1)).await;
+    }
+    tokio::fs::rename(path, numbered(1)).await
+}
+
+/// Writes lines as they come, batching whatever queued up meanwhile. Ends
+/// once every `AccessLog` handle is gone, e.g. after a config reload.
+async fn write_lines(mut lines: mpsc::Receiver<String>, mut sink: Sink) {
+    while let Some(line) = lines.recv().await {
+        let mut batch = line;
+        batch.push('\n');
+        while batch.len() < MAX_BATCH_BYTES {
+            let Ok(line) = lines.try_recv() else {
+                break;
+            };
+            batch.push_str(&line);
+            batch.push('\n');
+        }
+        if let Err(err) = sink.write(batch.as_bytes()).await {
+            eprintln!("Access log write failed: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::gateway::bodies::single_chunk_response_body;
+
+    fn completed(status: u16, bytes_out: u64) -> CompletedRecord {
+        let req = Request::get("/search?q=1").header(USER_AGENT, "curl \"evil\"").body(()).unwrap();
+        let upstream = UpstreamExchange::new("10.0.0.9:8080".to_string());
+        upstream.bytes_sent.store(12, Ordering::Relaxed);
+        CompletedRecord {
+            record: AccessRecord::new(&req, "10.1.2.3:40000".parse().unwrap()),
+            context: RequestContext {
+                route_id: Some("api".to_string()),
+                request_id: Some("req-1".to_string()),
+                principal: Some("alice".to_string()),
+            },
+            upstream: Some(upstream),
+            status,
+            bytes_out,
+        }
+    }
+
+    #[test]
+    fn json_lines_have_every_field() {
+        let line: Value = serde_json::from_str(&LogFormat::Json.line(&completed(200, 34))).unwrap();
+        let fields = line.as_object().unwrap();
+        assert_eq!(fields.len(), FIELDS.len());
+        assert_eq!(line["client_ip"], "10.1.2.3");
+        assert_eq!(line["uri"], "/search?q=1");
+        assert_eq!(line["protocol"], "HTTP/1.1");
+        assert_eq!(line["route_id"], "api");
+        assert_eq!(line["upstream"], "10.0.0.9:8080");
+        assert_eq!(line["status"], 200);
+        assert_eq!(line["bytes_in"], 12);
+        assert_eq!(line["bytes_out"], 34);
+        assert_eq!(line["request_id"], "req-1");
+        assert_eq!(line["referer"], Value::Null);
+        assert_eq!(line["user_agent"], "curl \"evil\"");
+        assert!(line["latency_ms"].is_number());
+    }
+
+    #[test]
+    fn common_and_combined_lines_escape_client_text() {
+        let common = LogFormat::Common.line(&completed(200, 34));
+        assert!(common.starts_with("10.1.2.3 - alice ["), "{}", common);
+        assert!(common.ends_with("] \"GET /search?q=1 HTTP/1.1\" 200 34"), "{}", common);
+        let empty = LogFormat::Common.line(&completed(304, 0));
+        assert!(empty.ends_with(" 304 -"), "{}", empty);
+
+        let combined = LogFormat::Combined.line(&completed(200, 34));
+        assert!(combined.ends_with(" 200 34 \"-\" \"curl \\\"evil\\\"\""), "{}", combined);
+        assert_eq!(escape("a\u{1b}[31m\\"), "a\\u{1b}[31m\\\\");
+    }
+
+    #[test]
+    fn templates_render_known_fields() {
+        let format = LogFormat::template("{method} {status} {route_id} {referer}").unwrap();
+        assert_eq!(format.line(&completed(503, 0)), "GET 503 api -");
+        assert!(LogFormat::template("{method} {cookie}").is_err());
+    }
+
+    async fn wait_for_tail(path: &Path, tail: &str) {
+        for _ in 0..200 {
+            let contents = std::fs::read_to_string(path).unwrap_or_default();
+            if contents.ends_with(tail) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} never ended with {:?}", path.display(), tail);
+    }
+
+    #[tokio::test]
+    async fn writes_and_rotates_files() {
+        let dir = std::env::temp_dir().join(format!("access-log-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("access.log");
+        let format = LogFormat::template("{status}").unwrap();
+        let output = LogOutput::File { path: path.clone(), max_bytes: 8, max_files: 2 };
+        let log = AccessLog::new(format, output, 16).unwrap();
+
+        let req = Request::get("/").body(()).unwrap();
+        for status in 201..=207 {
+            let record = AccessRecord::new(&req, "127.0.0.1:1".parse().unwrap());
+            let response = Response::builder()
+                .status(status)
+                .body(single_chunk_response_body("body"))
+                .unwrap();
+            // The line is written once the body is dropped
+            drop(log.attach(record, response));
+            wait_for_tail(&path, &format!("{}\n", status)).await;
+        }
+
+        let read = |suffix: &str| {
+            let mut name = path.clone().into_os_string();
+            name.push(suffix);
+            std::fs::read_to_string(name).unwrap_or_default()
+        };
+        assert_eq!(read(""), "207\n");
+        assert_eq!(read(".1"), "205\n206\n");
+        assert_eq!(read(".2"), "203\n204\n");
+        assert_eq!(read(".3"), "");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
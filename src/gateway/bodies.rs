@@ -1,7 +1,9 @@
 use bytes::Bytes;
+pub use counting_body::CountingBody;
 pub use pinned_body::BoxBody;
 pub use single_chunk_body::SingleChunkBody;
 
+pub mod counting_body;
 pub mod pinned_body;
 pub mod single_chunk_body;
 
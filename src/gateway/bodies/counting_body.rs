@@ -0,0 +1,55 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, Bytes};
+use hyper::body::{Body, Frame, SizeHint};
+
+use crate::gateway::bodies::BoxBody;
+
+/// Passes a body through while counting its data bytes, and reports the count
+/// once the body is dropped, whether it was read to the end or not.
+pub struct CountingBody {
+    inner: BoxBody,
+    bytes: u64,
+    on_drop: Option<Box<dyn FnOnce(u64) + Send>>,
+}
+
+impl CountingBody {
+    pub fn new(inner: BoxBody, on_drop: impl FnOnce(u64) + Send + 'static) -> Self {
+        Self { inner, bytes: 0, on_drop: Some(Box::new(on_drop)) }
+    }
+}
+
+impl Body for CountingBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let poll = self.inner.as_mut().poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll {
+            if let Some(data) = frame.data_ref() {
+                self.bytes += data.remaining() as u64;
+            }
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+impl Drop for CountingBody {
+    fn drop(&mut self) {
+        if let Some(on_drop) = self.on_drop.take() {
+            on_drop(self.bytes);
+        }
+    }
+}
@@ -1,35 +1,807 @@
+use std::net::SocketAddr;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Config {
+    /// CORS policy for every route without a `Cors` filter of its own.
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+    /// Filters every route gets, merged with the route's own by `order`.
+    #[serde(default)]
+    pub default_filters: Vec<FilterEntry>,
+    /// One line per request when set.
+    #[serde(default)]
+    pub access_log: Option<AccessLogConfig>,
+    /// Distributed tracing; requests carry trace headers through unchanged
+    /// when unset.
+    #[serde(default)]
+    pub tracing: Option<TracingConfig>,
+    /// Access to the actuator endpoints, which are closed when unset.
+    #[serde(default)]
+    pub admin: Option<AdminConfig>,
     pub routes: Vec<RouteConfig>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AdminConfig {
+    /// Bearer token every actuator request must carry.
+    pub token: String,
+    /// Writes route changes made through the admin API back to the config
+    /// file. The file is rewritten from the config in use, so comments and
+    /// formatting are lost.
+    #[serde(default)]
+    pub persist: bool,
+    /// Address of the admin listener, 127.0.0.1:9090 when unset. Only read
+    /// at startup.
+    #[serde(default)]
+    pub listen: Option<SocketAddr>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RouteConfig {
     pub id: String,
-    pub destination: String,
+    /// Lower values are matched first; routes with equal order keep file order.
+    #[serde(default)]
+    pub order: i32,
+    /// Upstream URI; exactly one of `destination` and `response` is set.
+    #[serde(default)]
+    pub destination: Option<String>,
+    /// Fixed answer served by the gateway itself, without an upstream.
+    #[serde(default)]
+    pub response: Option<StaticResponseConfig>,
     pub predicates: Vec<PredicateConfig>,
-    pub filters: Vec<FilterConfig>,
+    #[serde(default)]
+    pub filters: Vec<FilterEntry>,
+    /// Default filters this route goes without, by `id` or by type.
+    #[serde(default)]
+    pub skip_default_filters: Vec<String>,
+}
+
+/// A filter with its place in the chain.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FilterEntry {
+    /// Lets routes skip this filter when it is a default filter.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Lower values run first. Without one, a filter's position in its list
+    /// counting from 1 is used, and default filters go first on ties.
+    #[serde(default)]
+    pub order: Option<i32>,
+    #[serde(flatten)]
+    pub filter: FilterConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StaticResponseConfig {
+    #[serde(default = "default_status")]
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub body: String,
+}
+
+fn default_status() -> u16 {
+    200
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Every `PredicateConfig` type, for the admin API.
+pub const PREDICATE_TYPES: [&str; 12] = [
+    "Path",
+    "Header",
+    "QueryParam",
+    "Method",
+    "Cookie",
+    "Host",
+    "RemoteAddr",
+    "XForwardedRemoteAddr",
+    "Weight",
+    "AllOf",
+    "AnyOf",
+    "Not",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum PredicateConfig {
-    Path { path: String },
-    Header { header: String, value: String },
-    QueryParam { param: String, value: String },
+    Path {
+        path: String,
+        /// Gives `*`, `**`, `{name}` and `{*name}` segments their Spring
+        /// pattern meaning; otherwise the path must match exactly.
+        #[serde(default)]
+        pattern: bool,
+    },
+    Header {
+        header: String,
+        #[serde(default)]
+        value: Option<String>,
+        #[serde(default)]
+        mode: Option<MatchMode>,
+        #[serde(default)]
+        repeated: RepeatedValues,
+        #[serde(default)]
+        ignore_case: bool,
+    },
+    QueryParam {
+        param: String,
+        #[serde(default)]
+        value: Option<String>,
+        #[serde(default)]
+        mode: Option<MatchMode>,
+        #[serde(default)]
+        repeated: RepeatedValues,
+        #[serde(default)]
+        ignore_case: bool,
+    },
     Method { method: String },
-    Cookie { name: String, value: String },
+    Cookie {
+        name: String,
+        #[serde(default)]
+        value: Option<String>,
+        #[serde(default)]
+        mode: Option<MatchMode>,
+        #[serde(default)]
+        repeated: RepeatedValues,
+        #[serde(default)]
+        ignore_case: bool,
+    },
     Host { patterns: Vec<String> },
     RemoteAddr { addrs: Vec<String> },
     XForwardedRemoteAddr { addrs: Vec<String> },
+    Weight {
+        group: String,
+        weight: u32,
+        #[serde(default)]
+        sticky: Option<StickyConfig>,
+    },
+    AllOf { predicates: Vec<PredicateConfig> },
+    AnyOf { predicates: Vec<PredicateConfig> },
+    Not { predicate: Box<PredicateConfig> },
+}
+
+/// How a predicate compares a request value with the configured `value`.
+///
+/// When omitted, a `value` is treated as a regex and no `value` means the
+/// header, parameter or cookie only has to be present.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub enum MatchMode {
+    Regex,
+    Exact,
+    Prefix,
+    Present,
+    Absent,
+}
+
+/// Which values must match when a header or parameter is repeated.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+pub enum RepeatedValues {
+    #[default]
+    Any,
+    All,
+}
+
+/// Request attribute that pins a client to one route of a weight group.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum StickyConfig {
+    Cookie { name: String },
+    Header { name: String },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Every `FilterConfig` type, for the admin API.
+pub const FILTER_TYPES: [&str; 24] = [
+    "AddRequestHeader",
+    "AddRequestHeadersIfNotPresent",
+    "AddRequestParameter",
+    "StripPrefix",
+    "PrefixPath",
+    "SetPath",
+    "RewritePath",
+    "RemoveRequestHeader",
+    "SetRequestHeader",
+    "MapRequestHeader",
+    "RemoveRequestParameter",
+    "PreserveHostHeader",
+    "SetRequestHostHeader",
+    "RedirectTo",
+    "RequestRateLimiter",
+    "ConcurrencyLimit",
+    "JwtAuth",
+    "ApiKeyAuth",
+    "BasicAuth",
+    "TokenIntrospection",
+    "HmacSignature",
+    "Cors",
+    "SecureHeaders",
+    "RequestId",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum FilterConfig {
     AddRequestHeader { name: String, value: String },
     AddRequestHeadersIfNotPresent { headers: Vec<(String, String)> },
     AddRequestParameter { name: String, value: String },
+    StripPrefix { parts: usize },
+    PrefixPath { prefix: String },
+    SetPath { template: String },
+    RewritePath { regexp: String, replacement: String },
+    RemoveRequestHeader { name: String },
+    SetRequestHeader { name: String, value: String },
+    MapRequestHeader { from: String, to: String },
+    RemoveRequestParameter { name: String },
+    PreserveHostHeader,
+    SetRequestHostHeader { host: String },
+    RedirectTo {
+        status: u16,
+        url: String,
+        #[serde(default)]
+        include_query: bool,
+    },
+    RequestRateLimiter {
+        /// Tokens added to each bucket per second.
+        replenish_rate: u32,
+        /// Most tokens a bucket can hold, i.e. the largest allowed burst.
+        burst_capacity: u32,
+        /// Tokens one request costs.
+        #[serde(default = "default_requested_tokens")]
+        requested_tokens: u32,
+        #[serde(default)]
+        key_resolver: KeyResolverConfig,
+        /// Answer 403 when the key cannot be resolved instead of letting the
+        /// request through unlimited.
+        #[serde(default = "default_deny_empty_key")]
+        deny_empty_key: bool,
+        #[serde(default)]
+        store: RateLimiterStoreConfig,
+    },
+    ConcurrencyLimit {
+        /// Most requests in flight to the upstream; the starting point when
+        /// `adaptive` is set.
+        max_concurrent: u32,
+        /// Requests allowed to wait for a free slot before getting 503.
+        #[serde(default)]
+        max_queue: usize,
+        #[serde(default = "default_queue_timeout_ms")]
+        queue_timeout_ms: u64,
+        /// Routes naming the same group share one limit, e.g. every route to
+        /// one upstream. Defaults to the route id.
+        #[serde(default)]
+        group: Option<String>,
+        #[serde(default)]
+        adaptive: Option<AdaptiveLimitConfig>,
+    },
+    JwtAuth {
+        keys: JwtKeysConfig,
+        /// Accepted signature algorithms such as `RS256`; all by default.
+        #[serde(default)]
+        algorithms: Vec<String>,
+        #[serde(default)]
+        issuer: Option<String>,
+        /// The token must name at least one of these in `aud`.
+        #[serde(default)]
+        audiences: Vec<String>,
+        /// Leeway for `exp` and `nbf`.
+        #[serde(default = "default_clock_skew_secs")]
+        clock_skew_secs: u64,
+        /// Claim and upstream header pairs.
+        #[serde(default)]
+        claim_headers: Vec<(String, String)>,
+        /// Claim and value pairs the token must carry, else 403.
+        #[serde(default)]
+        required_claims: Vec<(String, String)>,
+    },
+    ApiKeyAuth {
+        #[serde(default = "default_api_key_header")]
+        header: String,
+        /// Also accept the key from this query parameter.
+        #[serde(default)]
+        query_param: Option<String>,
+        /// Consumer and hex SHA-256 digest of its key.
+        #[serde(default)]
+        keys: Vec<(String, String)>,
+        /// More keys, one `consumer:digest` per line.
+        #[serde(default)]
+        keys_file: Option<String>,
+        /// Consumers allowed on this route; everyone with a valid key if empty.
+        #[serde(default)]
+        consumers: Vec<String>,
+        /// Upstream header carrying the consumer name.
+        #[serde(default)]
+        consumer_header: Option<String>,
+    },
+    BasicAuth {
+        /// `user:hash` lines with bcrypt (`$2y$`) or argon2 (`$argon2id$`) hashes.
+        htpasswd_file: String,
+        #[serde(default = "default_realm")]
+        realm: String,
+        /// Users allowed on this route; everyone in the file if empty.
+        #[serde(default)]
+        users: Vec<String>,
+    },
+    TokenIntrospection {
+        /// RFC 7662 introspection endpoint of the authorization server.
+        endpoint: String,
+        client_id: String,
+        client_secret: String,
+        /// Scopes the token must all carry, otherwise 403.
+        #[serde(default)]
+        required_scopes: Vec<String>,
+        /// Longest time an active answer is reused; never past the token's `exp`.
+        #[serde(default = "default_introspection_cache_secs")]
+        cache_secs: u64,
+        /// How long an inactive token keeps being rejected without asking again.
+        #[serde(default = "default_negative_cache_secs")]
+        negative_cache_secs: u64,
+        #[serde(default = "default_introspection_timeout_ms")]
+        timeout_ms: u64,
+        #[serde(default = "default_subject_header")]
+        subject_header: String,
+        #[serde(default = "default_scope_header")]
+        scope_header: String,
+        #[serde(default = "default_client_id_header")]
+        client_id_header: String,
+    },
+    HmacSignature {
+        /// Accepted signing secrets; list the new one next to the old while rotating.
+        secrets: Vec<String>,
+        /// Header carrying the signature, e.g. `X-Hub-Signature-256`.
+        header: String,
+        #[serde(default)]
+        format: SignatureFormatConfig,
+        #[serde(default)]
+        encoding: SignatureEncodingConfig,
+        #[serde(default)]
+        algorithm: HmacAlgorithmConfig,
+        /// What was signed: `{body}` is the raw body, next to `{timestamp}`,
+        /// `{method}` and `{path}`, e.g. `{timestamp}.{body}` for Stripe.
+        #[serde(default = "default_signing_string")]
+        signing_string: String,
+        /// Header with the signature's Unix timestamp, e.g. Slack's
+        /// `X-Slack-Request-Timestamp`.
+        #[serde(default)]
+        timestamp_header: Option<String>,
+        /// Largest difference between the timestamp and the gateway's clock.
+        #[serde(default = "default_tolerance_secs")]
+        tolerance_secs: u64,
+        /// Larger bodies are answered with 413 before they are verified.
+        #[serde(default = "default_max_body_bytes")]
+        max_body_bytes: usize,
+    },
+    Cors(CorsConfig),
+    /// Every header is sent with a default value unless overridden here or
+    /// listed in `disable`.
+    SecureHeaders {
+        #[serde(default)]
+        strict_transport_security: Option<String>,
+        #[serde(default)]
+        x_content_type_options: Option<String>,
+        #[serde(default)]
+        x_frame_options: Option<String>,
+        #[serde(default)]
+        referrer_policy: Option<String>,
+        #[serde(default)]
+        content_security_policy: Option<String>,
+        #[serde(default)]
+        permissions_policy: Option<String>,
+        /// Header names to leave out, e.g. `Content-Security-Policy`.
+        #[serde(default)]
+        disable: Vec<String>,
+    },
+    /// Runs ahead of the route's other filters. As a default filter it also
+    /// covers requests that match no route.
+    RequestId {
+        /// Reused from the client when valid, and forwarded and echoed.
+        #[serde(default = "default_request_id_header")]
+        header: String,
+        #[serde(default)]
+        generator: IdGeneratorConfig,
+        /// Longer incoming ids are replaced.
+        #[serde(default = "default_request_id_max_length")]
+        max_length: usize,
+        #[serde(default = "default_echo")]
+        echo: bool,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+pub enum IdGeneratorConfig {
+    #[default]
+    Uuid,
+    Ulid,
+}
+
+fn default_request_id_header() -> String {
+    "X-Request-Id".to_string()
+}
+
+fn default_request_id_max_length() -> usize {
+    128
+}
+
+fn default_echo() -> bool {
+    true
+}
+
+/// How requests are logged once their response is sent.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AccessLogConfig {
+    #[serde(default)]
+    pub format: AccessLogFormatConfig,
+    #[serde(default)]
+    pub output: AccessLogOutputConfig,
+    /// Lines waiting to be written; further lines are dropped when full.
+    #[serde(default = "default_access_log_buffer")]
+    pub buffer: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(tag = "type")]
+pub enum AccessLogFormatConfig {
+    /// One JSON object per line.
+    #[default]
+    Json,
+    /// NCSA Common Log Format.
+    Common,
+    /// Common Log Format plus referer and user agent.
+    Combined,
+    /// `{name}` placeholders for the fields of a record, e.g.
+    /// `{method} {uri} {status} {latency_ms}ms`.
+    Template { template: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(tag = "type")]
+pub enum AccessLogOutputConfig {
+    #[default]
+    Stdout,
+    /// Rotated to `path.1`, `path.2`, ... once larger than `max_bytes`.
+    File {
+        path: String,
+        #[serde(default = "default_access_log_max_bytes")]
+        max_bytes: u64,
+        /// Rotated files kept besides the current one.
+        #[serde(default = "default_access_log_max_files")]
+        max_files: usize,
+    },
+}
+
+fn default_access_log_buffer() -> usize {
+    8192
+}
+
+fn default_access_log_max_bytes() -> u64 {
+    100 * 1024 * 1024
+}
+
+fn default_access_log_max_files() -> usize {
+    5
+}
+
+/// Spans for each request, exported over OTLP/HTTP.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TracingConfig {
+    /// OTLP/HTTP traces URL, e.g. `http://collector:4318/v1/traces`.
+    pub endpoint: String,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Share of new traces that are recorded. Traces started upstream of
+    /// the gateway follow the caller's decision.
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+    /// Header formats read from requests, first match wins, and all
+    /// written to forwarded requests.
+    #[serde(default = "default_propagation")]
+    pub propagation: Vec<PropagationConfig>,
+    /// Extra headers for the export requests, e.g. for authentication.
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    /// Spans sent per export request.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Longest a finished span waits before it is exported.
+    #[serde(default = "default_flush_interval_ms")]
+    pub flush_interval_ms: u64,
+    /// Finished spans waiting for export; further spans are dropped.
+    #[serde(default = "default_max_queue")]
+    pub max_queue: usize,
+    #[serde(default = "default_export_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropagationConfig {
+    /// `traceparent` and `tracestate`.
+    W3C,
+    /// The single `b3` header.
+    B3,
+    /// `X-B3-TraceId`, `X-B3-SpanId` and `X-B3-Sampled`.
+    B3Multi,
+}
+
+fn default_service_name() -> String {
+    "cloud-gateway".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_propagation() -> Vec<PropagationConfig> {
+    vec![PropagationConfig::W3C]
+}
+
+fn default_batch_size() -> usize {
+    512
+}
+
+fn default_flush_interval_ms() -> u64 {
+    5000
+}
+
+fn default_max_queue() -> usize {
+    2048
+}
+
+fn default_export_timeout_ms() -> u64 {
+    10_000
+}
+
+/// Which cross-origin browser requests are allowed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CorsConfig {
+    /// Exact origins like `https://app.example.com`, wildcards like
+    /// `https://*.example.com`, or `*` for any.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// Regexes matched against the whole origin.
+    #[serde(default)]
+    pub allowed_origin_patterns: Vec<String>,
+    /// `*` allows any method.
+    #[serde(default = "default_cors_methods")]
+    pub allowed_methods: Vec<String>,
+    /// Request headers a preflight may ask for; `*` allows any.
+    #[serde(default = "default_cors_headers")]
+    pub allowed_headers: Vec<String>,
+    /// Response headers scripts may read beyond the safelisted ones.
+    #[serde(default)]
+    pub exposed_headers: Vec<String>,
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight answer.
+    #[serde(default = "default_cors_max_age_secs")]
+    pub max_age_secs: u64,
+}
+
+fn default_cors_methods() -> Vec<String> {
+    vec!["GET".to_string(), "HEAD".to_string(), "POST".to_string()]
+}
+
+fn default_cors_headers() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn default_cors_max_age_secs() -> u64 {
+    1800
+}
+
+fn default_requested_tokens() -> u32 {
+    1
+}
+
+fn default_deny_empty_key() -> bool {
+    true
+}
+
+/// What a rate limiter counts requests by.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(tag = "type")]
+pub enum KeyResolverConfig {
+    /// IP address of the connected client.
+    #[default]
+    RemoteAddr,
+    /// Client IP from `X-Forwarded-For`, counting `max_trusted_index` entries
+    /// from the right, i.e. the number of trusted proxies in front.
+    XForwardedFor {
+        #[serde(default = "default_max_trusted_index")]
+        max_trusted_index: usize,
+    },
+    Header { name: String },
+    /// Identity set by an authentication filter earlier in the chain.
+    Principal,
+    /// One bucket shared by every client of the route.
+    RouteId,
+}
+
+fn default_max_trusted_index() -> usize {
+    1
+}
+
+/// Where a rate limiter keeps its buckets.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(tag = "type")]
+pub enum RateLimiterStoreConfig {
+    /// In gateway memory; each replica enforces the full limit on its own.
+    #[default]
+    Local,
+    /// Shared by all replicas through a Redis-compatible server.
+    Redis {
+        url: String,
+        /// Let requests through while the store is unreachable instead of
+        /// answering 503.
+        #[serde(default = "default_fail_open")]
+        fail_open: bool,
+        /// Budget for connecting and for each bucket update.
+        #[serde(default = "default_store_timeout_ms")]
+        timeout_ms: u64,
+    },
+}
+
+fn default_queue_timeout_ms() -> u64 {
+    1000
+}
+
+/// Moves a concurrency limit with observed upstream latency.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum AdaptiveLimitConfig {
+    /// Grows by one per fast success and shrinks by `backoff_ratio` when a
+    /// call fails or takes longer than `latency_threshold_ms`.
+    Aimd {
+        #[serde(default = "default_min_limit")]
+        min_limit: u32,
+        max_limit: u32,
+        latency_threshold_ms: u64,
+        #[serde(default = "default_backoff_ratio")]
+        backoff_ratio: f64,
+    },
+    /// Scales the limit by how far current latency is above its long-term
+    /// average, tolerating `tolerance` times the average before shrinking.
+    Gradient {
+        #[serde(default = "default_min_limit")]
+        min_limit: u32,
+        max_limit: u32,
+        #[serde(default = "default_tolerance")]
+        tolerance: f64,
+        #[serde(default = "default_smoothing")]
+        smoothing: f64,
+    },
+}
+
+fn default_min_limit() -> u32 {
+    1
+}
+
+fn default_backoff_ratio() -> f64 {
+    0.9
+}
+
+fn default_tolerance() -> f64 {
+    1.5
+}
+
+fn default_smoothing() -> f64 {
+    0.2
+}
+
+fn default_clock_skew_secs() -> u64 {
+    60
+}
+
+/// Where a JWT filter gets its verification keys.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum JwtKeysConfig {
+    /// Shared HMAC secret.
+    Secret { secret: String },
+    JwksFile { path: String },
+    /// Fetched on first use, then again every `refresh_secs` or when a token
+    /// names a key id the cached set lacks.
+    JwksUrl {
+        url: String,
+        #[serde(default = "default_jwks_refresh_secs")]
+        refresh_secs: u64,
+    },
+}
+
+fn default_jwks_refresh_secs() -> u64 {
+    300
+}
+
+fn default_api_key_header() -> String {
+    "X-API-Key".to_string()
+}
+
+fn default_realm() -> String {
+    "gateway".to_string()
+}
+
+/// How a signature header is written.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum SignatureFormatConfig {
+    /// The value after a fixed prefix such as GitHub's `sha256=`.
+    Prefixed {
+        #[serde(default)]
+        prefix: String,
+    },
+    /// `key=value` elements such as Stripe's `t=...,v1=...`.
+    KeyValue {
+        signature_key: String,
+        /// Element holding the signature's Unix timestamp, e.g. `t`.
+        #[serde(default)]
+        timestamp_key: Option<String>,
+        #[serde(default = "default_element_separator")]
+        separator: String,
+    },
+}
+
+impl Default for SignatureFormatConfig {
+    fn default() -> Self {
+        SignatureFormatConfig::Prefixed { prefix: String::new() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+pub enum SignatureEncodingConfig {
+    #[default]
+    Hex,
+    Base64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy)]
+pub enum HmacAlgorithmConfig {
+    Sha1,
+    #[default]
+    Sha256,
+    Sha512,
+}
+
+fn default_signing_string() -> String {
+    "{body}".to_string()
+}
+
+fn default_tolerance_secs() -> u64 {
+    300
+}
+
+fn default_max_body_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_element_separator() -> String {
+    ",".to_string()
+}
+
+fn default_introspection_cache_secs() -> u64 {
+    60
+}
+
+fn default_negative_cache_secs() -> u64 {
+    30
+}
+
+fn default_introspection_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_subject_header() -> String {
+    "X-Auth-Subject".to_string()
+}
+
+fn default_scope_header() -> String {
+    "X-Auth-Scope".to_string()
+}
+
+fn default_client_id_header() -> String {
+    "X-Auth-Client-Id".to_string()
+}
+
+fn default_fail_open() -> bool {
+    true
+}
+
+fn default_store_timeout_ms() -> u64 {
+    100
 }
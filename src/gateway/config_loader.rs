@@ -1,99 +1,729 @@
 use async_trait::async_trait;
+use http::header::{
+    CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS,
+    X_FRAME_OPTIONS,
+};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use regex::RegexBuilder;
 use serde_yaml::from_str;
-use std::collections::HashSet;
 use std::error::Error;
-use std::iter::FromIterator;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs;
 
-use crate::gateway::config::{Config, FilterConfig, PredicateConfig};
+use crate::gateway::access_log::{AccessLog, LogFormat, LogOutput};
+use crate::gateway::config::{
+    AccessLogConfig, AccessLogFormatConfig, AccessLogOutputConfig, AdaptiveLimitConfig, Config,
+    CorsConfig, FilterConfig, FilterEntry, HmacAlgorithmConfig, IdGeneratorConfig, JwtKeysConfig,
+    KeyResolverConfig, MatchMode, PredicateConfig, PropagationConfig, RateLimiterStoreConfig,
+    RepeatedValues, RouteConfig, SignatureEncodingConfig, SignatureFormatConfig,
+    StaticResponseConfig, StickyConfig, TracingConfig,
+};
+use crate::gateway::errors::GatewayError;
 use crate::gateway::filters::*;
+use crate::gateway::predicates::weight::link_weight_groups;
 use crate::gateway::predicates::*;
-use crate::gateway::route::Route;
+use crate::gateway::route::{Destination, Route, StaticResponse};
+use crate::gateway::route_table::RouteTable;
+use crate::gateway::trace::{ExportSettings, Propagation, Tracer};
 
 #[async_trait]
 pub trait ConfigLoader {
-    async fn load_config(file_path: &str) -> Result<Vec<Route>, Box<dyn Error + Send + Sync>>;
+    async fn load_config(file_path: &str) -> Result<RouteTable, Box<dyn Error + Send + Sync>>;
 }
 
 pub struct YamlConfigLoader;
 
 #[async_trait]
 impl ConfigLoader for YamlConfigLoader {
-    async fn load_config(file_path: &str) -> Result<Vec<Route>, Box<dyn Error + Send + Sync>> {
+    async fn load_config(file_path: &str) -> Result<RouteTable, Box<dyn Error + Send + Sync>> {
         // Asynchronously read the entire file into memory
         let contents = fs::read_to_string(file_path).await?;
         // Parse YAML in memory
         let config: Config = from_str(&contents)?;
 
-        let routes = config
-            .routes
-            .into_iter()
-            .map(|route_config| {
-                let predicates = route_config
-                    .predicates
-                    .into_iter()
-                    .map(|predicate_config| match predicate_config {
-                        PredicateConfig::Path { path } => Predicate::Path(PathPredicate { path }),
-                        PredicateConfig::Header { header, value } => {
-                            Predicate::Header(HeaderPredicate { header, value })
-                        }
-                        PredicateConfig::QueryParam { param, value } => {
-                            Predicate::QueryParam(QueryParamPredicate::new(param, value))
-                        }
-                        PredicateConfig::Method { method } => {
-                            Predicate::Method(MethodPredicate { method })
-                        }
-                        PredicateConfig::Cookie { name, value } => {
-                            Predicate::Cookie(CookiePredicate::new(name, value))
-                        }
-                        PredicateConfig::Host { patterns } => {
-                            Predicate::Host(HostPredicate { patterns })
-                        }
-                        PredicateConfig::RemoteAddr { addrs } => {
-                            Predicate::RemoteAddr(RemoteAddrPredicate {
-                                addrs: addrs
-                                    .into_iter()
-                                    .map(|addr| addr.parse().unwrap())
-                                    .collect(),
-                            })
-                        }
-                        PredicateConfig::XForwardedRemoteAddr { addrs } => {
-                            Predicate::XForwardedRemoteAddr(XForwardedRemoteAddrPredicate {
-                                addrs: HashSet::from_iter(
-                                    addrs.into_iter().map(|addr| addr.parse().unwrap()),
-                                ),
-                            })
-                        }
-                    })
-                    .collect();
-
-                let filters = route_config
-                    .filters
-                    .into_iter()
-                    .map(|filter_config| match filter_config {
-                        FilterConfig::AddRequestHeader { name, value } => {
-                            Filter::AddRequestHeader(AddRequestHeader::new(name, value))
-                        }
-                        FilterConfig::AddRequestParameter { name, value } => {
-                            Filter::AddRequestParameters(AddRequestParameter::new(name, value))
-                        }
-                        FilterConfig::AddRequestHeadersIfNotPresent { headers } => {
-                            Filter::AddRequestHeadersIfNotPresent(
-                                AddRequestHeadersIfNotPresent::new(headers),
-                            )
-                        }
-                    })
-                    .collect();
-
-                Route {
-                    id: route_config.id,
-                    predicates,
-                    filters,
-                    destination: route_config.destination,
-                }
+        Ok(build_route_table(config)?)
+    }
+}
+
+/// Builds the routes and the gateway-wide settings that come with them.
+pub fn build_route_table(config: Config) -> Result<RouteTable, GatewayError> {
+    if config.admin.as_ref().is_some_and(|admin| admin.token.is_empty()) {
+        return Err(GatewayError::InvalidConfig("admin token must not be empty".to_string()));
+    }
+    let request_id = config.default_filters.iter().find_map(|entry| match &entry.filter {
+        FilterConfig::RequestId { .. } => Some(entry.filter.clone()),
+        _ => None,
+    });
+    let request_id = match request_id.map(|filter| build_filter("", filter)).transpose()? {
+        Some(Filter::RequestId(request_id)) => Some(request_id),
+        _ => None,
+    };
+    let access_log = config.access_log.clone().map(build_access_log).transpose()?;
+    let tracer = config.tracing.clone().map(build_tracer).transpose()?;
+    let mut table = RouteTable::new(build_routes(config.clone())?);
+    table.request_id = request_id;
+    table.access_log = access_log;
+    table.tracer = tracer;
+    table.config = config;
+    Ok(table)
+}
+
+fn build_tracer(config: TracingConfig) -> Result<Tracer, GatewayError> {
+    let mut headers = Vec::new();
+    for (name, value) in config.headers {
+        let invalid = || GatewayError::InvalidConfig(format!("invalid tracing header '{}'", name));
+        headers.push((
+            name.parse::<HeaderName>().map_err(|_| invalid())?,
+            value.parse::<HeaderValue>().map_err(|_| invalid())?,
+        ));
+    }
+    let settings = ExportSettings {
+        endpoint: config.endpoint,
+        service_name: config.service_name,
+        headers,
+        batch_size: config.batch_size,
+        flush_interval: Duration::from_millis(config.flush_interval_ms),
+        max_queue: config.max_queue,
+        timeout: Duration::from_millis(config.timeout_ms),
+    };
+    let propagation = config
+        .propagation
+        .into_iter()
+        .map(|format| match format {
+            PropagationConfig::W3C => Propagation::W3C,
+            PropagationConfig::B3 => Propagation::B3,
+            PropagationConfig::B3Multi => Propagation::B3Multi,
+        })
+        .collect();
+    Tracer::new(settings, config.sample_ratio, propagation)
+}
+
+fn build_access_log(config: AccessLogConfig) -> Result<AccessLog, GatewayError> {
+    let format = match config.format {
+        AccessLogFormatConfig::Json => LogFormat::Json,
+        AccessLogFormatConfig::Common => LogFormat::Common,
+        AccessLogFormatConfig::Combined => LogFormat::Combined,
+        AccessLogFormatConfig::Template { template } => LogFormat::template(&template)?,
+    };
+    let output = match config.output {
+        AccessLogOutputConfig::Stdout => LogOutput::Stdout,
+        AccessLogOutputConfig::File { path, max_bytes, max_files } => {
+            LogOutput::File { path: path.into(), max_bytes, max_files }
+        }
+    };
+    AccessLog::new(format, output, config.buffer)
+}
+
+/// Turns a parsed config into a validated route table ready to serve.
+pub fn build_routes(config: Config) -> Result<Vec<Route>, GatewayError> {
+    let defaults = &config.default_filters;
+    let mut routes = config
+        .routes
+        .into_iter()
+        .map(|route| build_route(route, defaults))
+        .collect::<Result<Vec<_>, _>>()?;
+    link_weight_groups(&mut routes)?;
+    let limits = routes.iter().flat_map(|route| &route.filters).filter_map(|filter| match filter {
+        Filter::ConcurrencyLimit(limit) => Some(limit),
+        _ => None,
+    });
+    configure_groups(limits)?;
+    if let Some(cors) = config.cors {
+        let cors = build_cors(cors)?;
+        for route in routes.iter_mut().filter(|route| route.cors().is_none()) {
+            route.filters.insert(0, Filter::Cors(cors.clone()));
+        }
+    }
+    Ok(routes)
+}
+
+fn build_route(route_config: RouteConfig, defaults: &[FilterEntry]) -> Result<Route, GatewayError> {
+    let predicates =
+        route_config.predicates.into_iter().map(build_predicate).collect::<Result<Vec<_>, _>>()?;
+
+    let skips = |entry: &FilterEntry, skip: &String| {
+        entry.id.as_ref() == Some(skip) || filter_type(&entry.filter) == *skip
+    };
+    for skip in &route_config.skip_default_filters {
+        if !defaults.iter().any(|entry| skips(entry, skip)) {
+            return Err(GatewayError::InvalidConfig(format!(
+                "route '{}' skips unknown default filter '{}'",
+                route_config.id, skip
+            )));
+        }
+    }
+    let kept_defaults = defaults
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| {
+            !route_config.skip_default_filters.iter().any(|skip| skips(entry, skip))
+        })
+        .map(|(position, entry)| (entry.order, position, entry.filter.clone()));
+    let own = route_config
+        .filters
+        .into_iter()
+        .enumerate()
+        .map(|(position, entry)| (entry.order, position, entry.filter));
+    // Stable, so default filters stay ahead of route filters on equal order
+    let mut ordered: Vec<_> = kept_defaults
+        .chain(own)
+        .map(|(order, position, filter)| (order.unwrap_or(position as i32 + 1), filter))
+        .collect();
+    ordered.sort_by_key(|(order, _)| *order);
+    let mut filters = ordered
+        .into_iter()
+        .map(|(_, filter)| build_filter(&route_config.id, filter))
+        .collect::<Result<Vec<_>, _>>()?;
+    for (position, filter) in filters.iter_mut().enumerate() {
+        if let Filter::RequestRateLimiter(limiter) = filter {
+            limiter.position = position;
+        }
+    }
+
+    let destination = match (route_config.destination, route_config.response) {
+        (Some(uri), None) => Destination::Upstream(uri),
+        (None, Some(response)) => Destination::Static(build_static_response(response)?),
+        _ => {
+            return Err(GatewayError::InvalidConfig(format!(
+                "route '{}' needs exactly one of destination or response",
+                route_config.id
+            )))
+        }
+    };
+
+    Ok(Route { id: route_config.id, order: route_config.order, predicates, filters, destination })
+}
+
+/// The `type` a filter is written with in the config.
+fn filter_type(filter: &FilterConfig) -> String {
+    let value = serde_yaml::to_value(filter).unwrap_or_default();
+    value.get("type").and_then(|kind| kind.as_str()).unwrap_or_default().to_string()
+}
+
+fn build_static_response(config: StaticResponseConfig) -> Result<StaticResponse, GatewayError> {
+    let status = StatusCode::from_u16(config.status).map_err(|_| {
+        GatewayError::InvalidConfig(format!("invalid response status {}", config.status))
+    })?;
+    let mut headers = HeaderMap::new();
+    for (name, value) in config.headers {
+        let invalid = || GatewayError::InvalidConfig(format!("invalid response header '{}'", name));
+        let header_name = name.parse::<HeaderName>().map_err(|_| invalid())?;
+        headers.append(header_name, value.parse::<HeaderValue>().map_err(|_| invalid())?);
+    }
+    Ok(StaticResponse { status, headers, body: config.body.into() })
+}
+
+fn build_predicate(predicate_config: PredicateConfig) -> Result<Predicate, GatewayError> {
+    let predicate = match predicate_config {
+        PredicateConfig::Path { path, pattern } => {
+            Predicate::Path(PathPredicate::new(&path, pattern)?)
+        }
+        PredicateConfig::Header { header, value, mode, repeated, ignore_case } => {
+            let matcher = build_value_match(mode, value, ignore_case)?;
+            Predicate::Header(HeaderPredicate::new(&header, matcher, build_repeated(repeated))?)
+        }
+        PredicateConfig::QueryParam { param, value, mode, repeated, ignore_case } => {
+            let matcher = build_value_match(mode, value, ignore_case)?;
+            Predicate::QueryParam(QueryParamPredicate::new(
+                param,
+                matcher,
+                build_repeated(repeated),
+            ))
+        }
+        PredicateConfig::Method { method } => Predicate::Method(MethodPredicate { method }),
+        PredicateConfig::Cookie { name, value, mode, repeated, ignore_case } => {
+            let matcher = build_value_match(mode, value, ignore_case)?;
+            Predicate::Cookie(CookiePredicate::new(name, matcher, build_repeated(repeated)))
+        }
+        PredicateConfig::Host { patterns } => Predicate::Host(HostPredicate::new(&patterns)?),
+        PredicateConfig::RemoteAddr { addrs } => Predicate::RemoteAddr(RemoteAddrPredicate {
+            addrs: addrs.iter().map(|addr| parse_addr(addr)).collect::<Result<_, _>>()?,
+        }),
+        PredicateConfig::XForwardedRemoteAddr { addrs } => {
+            Predicate::XForwardedRemoteAddr(XForwardedRemoteAddrPredicate {
+                addrs: addrs.iter().map(|addr| parse_addr(addr)).collect::<Result<_, _>>()?,
             })
-            .collect();
+        }
+        PredicateConfig::Weight { group, weight, sticky } => {
+            let sticky = sticky.map(|sticky| match sticky {
+                StickyConfig::Cookie { name } => StickyKey::Cookie(name),
+                StickyConfig::Header { name } => StickyKey::Header(name),
+            });
+            Predicate::Weight(WeightPredicate::new(group, weight, sticky))
+        }
+        PredicateConfig::AllOf { predicates } => {
+            Predicate::AllOf(AllOfPredicate { predicates: build_group("AllOf", predicates)? })
+        }
+        PredicateConfig::AnyOf { predicates } => {
+            Predicate::AnyOf(AnyOfPredicate { predicates: build_group("AnyOf", predicates)? })
+        }
+        PredicateConfig::Not { predicate } => {
+            Predicate::Not(NotPredicate { predicate: Box::new(build_predicate(*predicate)?) })
+        }
+    };
+    Ok(predicate)
+}
+
+fn build_group(
+    kind: &str,
+    predicates: Vec<PredicateConfig>,
+) -> Result<Vec<Predicate>, GatewayError> {
+    if predicates.is_empty() {
+        return Err(GatewayError::InvalidConfig(format!("{} group has no predicates", kind)));
+    }
+    predicates.into_iter().map(build_predicate).collect()
+}
+
+fn build_value_match(
+    mode: Option<MatchMode>,
+    value: Option<String>,
+    ignore_case: bool,
+) -> Result<ValueMatch, GatewayError> {
+    let required = |value: Option<String>| {
+        value.ok_or_else(|| {
+            GatewayError::InvalidConfig(format!("match mode {:?} requires a value", mode))
+        })
+    };
+    let mode = mode.unwrap_or(if value.is_some() { MatchMode::Regex } else { MatchMode::Present });
+    let matcher = match mode {
+        MatchMode::Present => ValueMatch::Present,
+        MatchMode::Absent => ValueMatch::Absent,
+        MatchMode::Exact => ValueMatch::Exact { value: required(value)?, ignore_case },
+        MatchMode::Prefix => ValueMatch::Prefix { value: required(value)?, ignore_case },
+        MatchMode::Regex => {
+            let value = required(value)?;
+            let regex =
+                RegexBuilder::new(&value).case_insensitive(ignore_case).build().map_err(|err| {
+                    GatewayError::InvalidConfig(format!("invalid regex '{}': {}", value, err))
+                })?;
+            ValueMatch::Regex(regex)
+        }
+    };
+    Ok(matcher)
+}
+
+fn build_repeated(repeated: RepeatedValues) -> Repeated {
+    match repeated {
+        RepeatedValues::Any => Repeated::Any,
+        RepeatedValues::All => Repeated::All,
+    }
+}
+
+fn build_filter(route_id: &str, filter_config: FilterConfig) -> Result<Filter, GatewayError> {
+    let filter = match filter_config {
+        FilterConfig::AddRequestHeader { name, value } => {
+            Filter::AddRequestHeader(AddRequestHeader::new(&name, &value)?)
+        }
+        FilterConfig::AddRequestParameter { name, value } => {
+            Filter::AddRequestParameters(AddRequestParameter::new(name, value))
+        }
+        FilterConfig::AddRequestHeadersIfNotPresent { headers } => {
+            Filter::AddRequestHeadersIfNotPresent(AddRequestHeadersIfNotPresent::new(headers)?)
+        }
+        FilterConfig::StripPrefix { parts } => Filter::StripPrefix(StripPrefix::new(parts)),
+        FilterConfig::PrefixPath { prefix } => Filter::PrefixPath(PrefixPath::new(prefix)?),
+        FilterConfig::SetPath { template } => Filter::SetPath(SetPath::new(&template)?),
+        FilterConfig::RewritePath { regexp, replacement } => {
+            Filter::RewritePath(RewritePath::new(&regexp, &replacement)?)
+        }
+        FilterConfig::RemoveRequestHeader { name } => {
+            Filter::RemoveRequestHeader(RemoveRequestHeader::new(&name)?)
+        }
+        FilterConfig::SetRequestHeader { name, value } => {
+            Filter::SetRequestHeader(SetRequestHeader::new(&name, &value)?)
+        }
+        FilterConfig::MapRequestHeader { from, to } => {
+            Filter::MapRequestHeader(MapRequestHeader::new(&from, &to)?)
+        }
+        FilterConfig::RemoveRequestParameter { name } => {
+            Filter::RemoveRequestParameter(RemoveRequestParameter::new(name))
+        }
+        FilterConfig::PreserveHostHeader => Filter::PreserveHostHeader(PreserveHostHeader),
+        FilterConfig::SetRequestHostHeader { host } => {
+            Filter::SetRequestHostHeader(SetRequestHostHeader::new(&host)?)
+        }
+        FilterConfig::RedirectTo { status, url, include_query } => {
+            Filter::RedirectTo(RedirectTo::new(status, &url, include_query)?)
+        }
+        FilterConfig::RequestRateLimiter {
+            replenish_rate,
+            burst_capacity,
+            requested_tokens,
+            key_resolver,
+            deny_empty_key,
+            store,
+        } => {
+            let limits = Limits { replenish_rate, burst_capacity, requested_tokens };
+            let key_resolver = build_key_resolver(key_resolver)?;
+            let backend = build_bucket_backend(store)?;
+            let limiter = RequestRateLimiter::new(limits, key_resolver, deny_empty_key, backend)?;
+            Filter::RequestRateLimiter(limiter)
+        }
+        FilterConfig::ConcurrencyLimit {
+            max_concurrent,
+            max_queue,
+            queue_timeout_ms,
+            group,
+            adaptive,
+        } => {
+            let settings = LimitSettings {
+                max_concurrent,
+                max_queue,
+                queue_timeout: Duration::from_millis(queue_timeout_ms),
+                algorithm: adaptive.map_or(LimitAlgorithm::Fixed, build_limit_algorithm),
+            };
+            let group = group.as_deref().unwrap_or(route_id);
+            Filter::ConcurrencyLimit(ConcurrencyLimit::new(group, settings)?)
+        }
+        FilterConfig::JwtAuth {
+            keys,
+            algorithms,
+            issuer,
+            audiences,
+            clock_skew_secs,
+            claim_headers,
+            required_claims,
+        } => Filter::JwtAuth(Box::new(JwtAuth::new(
+            build_key_source(keys)?,
+            &algorithms,
+            issuer.as_deref(),
+            &audiences,
+            clock_skew_secs,
+            claim_headers,
+            required_claims,
+        )?)),
+        FilterConfig::ApiKeyAuth {
+            header,
+            query_param,
+            keys,
+            keys_file,
+            consumers,
+            consumer_header,
+        } => Filter::ApiKeyAuth(ApiKeyAuth::new(
+            &header,
+            query_param,
+            keys,
+            keys_file.as_deref(),
+            consumers,
+            consumer_header.as_deref(),
+        )?),
+        FilterConfig::BasicAuth { htpasswd_file, realm, users } => {
+            Filter::BasicAuth(BasicAuth::new(&htpasswd_file, realm, users)?)
+        }
+        FilterConfig::TokenIntrospection {
+            endpoint,
+            client_id,
+            client_secret,
+            required_scopes,
+            cache_secs,
+            negative_cache_secs,
+            timeout_ms,
+            subject_header,
+            scope_header,
+            client_id_header,
+        } => Filter::TokenIntrospection(TokenIntrospection::new(
+            endpoint,
+            (&client_id, &client_secret),
+            required_scopes,
+            Duration::from_secs(cache_secs),
+            Duration::from_secs(negative_cache_secs),
+            Duration::from_millis(timeout_ms),
+            IntrospectionHeaders::new(&subject_header, &scope_header, &client_id_header)?,
+        )?),
+        FilterConfig::Cors(cors) => Filter::Cors(build_cors(cors)?),
+        FilterConfig::SecureHeaders {
+            strict_transport_security,
+            x_content_type_options,
+            x_frame_options,
+            referrer_policy,
+            content_security_policy,
+            permissions_policy,
+            disable,
+        } => {
+            let overrides = [
+                (STRICT_TRANSPORT_SECURITY, strict_transport_security),
+                (X_CONTENT_TYPE_OPTIONS, x_content_type_options),
+                (X_FRAME_OPTIONS, x_frame_options),
+                (REFERRER_POLICY, referrer_policy),
+                (CONTENT_SECURITY_POLICY, content_security_policy),
+                (PERMISSIONS_POLICY, permissions_policy),
+            ];
+            Filter::SecureHeaders(SecureHeaders::new(&overrides, &disable)?)
+        }
+        FilterConfig::RequestId { header, generator, max_length, echo } => {
+            let generator = match generator {
+                IdGeneratorConfig::Uuid => IdGenerator::Uuid,
+                IdGeneratorConfig::Ulid => IdGenerator::Ulid,
+            };
+            Filter::RequestId(RequestId::new(&header, generator, max_length, echo)?)
+        }
+        FilterConfig::HmacSignature {
+            secrets,
+            header,
+            format,
+            encoding,
+            algorithm,
+            signing_string,
+            timestamp_header,
+            tolerance_secs,
+            max_body_bytes,
+        } => {
+            let (format, timestamp_key) = match format {
+                SignatureFormatConfig::Prefixed { prefix } => {
+                    (SignatureFormat::Prefixed(prefix), None)
+                }
+                SignatureFormatConfig::KeyValue { signature_key, timestamp_key, separator } => {
+                    (SignatureFormat::KeyValue { signature_key, separator }, timestamp_key)
+                }
+            };
+            let timestamp = match (timestamp_key, timestamp_header) {
+                (Some(_), Some(_)) => {
+                    return Err(GatewayError::InvalidConfig(
+                        "HmacSignature takes a timestamp_key or a timestamp_header, not both"
+                            .into(),
+                    ))
+                }
+                (Some(key), None) => Some(TimestampSource::Element(key)),
+                (None, Some(name)) => Some(TimestampSource::header(&name)?),
+                (None, None) => None,
+            };
+            let encoding = match encoding {
+                SignatureEncodingConfig::Hex => SignatureEncoding::Hex,
+                SignatureEncodingConfig::Base64 => SignatureEncoding::Base64,
+            };
+            let algorithm = match algorithm {
+                HmacAlgorithmConfig::Sha1 => HmacAlgorithm::Sha1,
+                HmacAlgorithmConfig::Sha256 => HmacAlgorithm::Sha256,
+                HmacAlgorithmConfig::Sha512 => HmacAlgorithm::Sha512,
+            };
+            Filter::HmacSignature(Box::new(HmacSignature::new(
+                SignatureHeader::new(&header, format, encoding)?,
+                timestamp,
+                Duration::from_secs(tolerance_secs),
+                algorithm,
+                &signing_string,
+                secrets,
+                max_body_bytes,
+            )?))
+        }
+    };
+    Ok(filter)
+}
+
+fn build_cors(config: CorsConfig) -> Result<Cors, GatewayError> {
+    let mut origins = config
+        .allowed_origins
+        .iter()
+        .map(|origin| OriginPattern::wildcard(origin))
+        .collect::<Result<Vec<_>, _>>()?;
+    for pattern in &config.allowed_origin_patterns {
+        origins.push(OriginPattern::regex(pattern)?);
+    }
+    Cors::new(
+        origins,
+        &config.allowed_methods,
+        &config.allowed_headers,
+        &config.exposed_headers,
+        config.allow_credentials,
+        config.max_age_secs,
+    )
+}
+
+fn build_key_resolver(config: KeyResolverConfig) -> Result<KeyResolver, GatewayError> {
+    let resolver = match config {
+        KeyResolverConfig::RemoteAddr => KeyResolver::RemoteAddr,
+        KeyResolverConfig::XForwardedFor { max_trusted_index } => {
+            KeyResolver::XForwardedFor { max_trusted_index }
+        }
+        KeyResolverConfig::Header { name } => KeyResolver::header(&name)?,
+        KeyResolverConfig::Principal => KeyResolver::Principal,
+        KeyResolverConfig::RouteId => KeyResolver::RouteId,
+    };
+    Ok(resolver)
+}
+
+fn build_bucket_backend(config: RateLimiterStoreConfig) -> Result<BucketBackend, GatewayError> {
+    let backend = match config {
+        RateLimiterStoreConfig::Local => BucketBackend::Local,
+        RateLimiterStoreConfig::Redis { url, fail_open, timeout_ms } => {
+            let timeout = Duration::from_millis(timeout_ms);
+            BucketBackend::Redis(Arc::new(RedisBuckets::new(&url, fail_open, timeout)?))
+        }
+    };
+    Ok(backend)
+}
+
+fn build_limit_algorithm(config: AdaptiveLimitConfig) -> LimitAlgorithm {
+    match config {
+        AdaptiveLimitConfig::Aimd { min_limit, max_limit, latency_threshold_ms, backoff_ratio } => {
+            LimitAlgorithm::Aimd {
+                min_limit,
+                max_limit,
+                latency_threshold: Duration::from_millis(latency_threshold_ms),
+                backoff_ratio,
+            }
+        }
+        AdaptiveLimitConfig::Gradient { min_limit, max_limit, tolerance, smoothing } => {
+            LimitAlgorithm::Gradient { min_limit, max_limit, tolerance, smoothing }
+        }
+    }
+}
+
+fn build_key_source(config: JwtKeysConfig) -> Result<KeySource, GatewayError> {
+    match config {
+        JwtKeysConfig::Secret { secret } => {
+            Ok(KeySource::Static(Arc::new(vec![JwtKey::secret(secret.as_bytes())])))
+        }
+        JwtKeysConfig::JwksFile { path } => KeySource::jwks_file(&path),
+        JwtKeysConfig::JwksUrl { url, refresh_secs } => {
+            KeySource::jwks_url(&url, Duration::from_secs(refresh_secs))
+        }
+    }
+}
+
+fn parse_addr(addr: &str) -> Result<IpAddr, GatewayError> {
+    addr.parse().map_err(|_| GatewayError::InvalidConfig(format!("invalid IP address '{}'", addr)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+default_filters:
+  - type: PrefixPath
+    prefix: /api
+  - type: RemoveRequestHeader
+    id: strip-cookies
+    order: 10
+    name: cookie
+  - type: SetRequestHeader
+    name: x-gateway
+    value: "1"
+routes:
+  - id: merged
+    destination: http://localhost:9000
+    predicates:
+      - type: Path
+        path: /merged
+    filters:
+      - type: StripPrefix
+        parts: 1
+      - type: AddRequestHeader
+        order: 5
+        name: x-route
+        value: merged
+  - id: skipping
+    destination: http://localhost:9000
+    skip_default_filters: [strip-cookies, SetRequestHeader]
+    predicates:
+      - type: Path
+        path: /skipping
+    filters: []
+"#;
+
+    fn filter_names(route: &Route) -> Vec<&'static str> {
+        let name = |filter: &Filter| match filter {
+            Filter::Cors(_) => "Cors",
+            Filter::PrefixPath(_) => "PrefixPath",
+            Filter::StripPrefix(_) => "StripPrefix",
+            Filter::AddRequestHeader(_) => "AddRequestHeader",
+            Filter::SetRequestHeader(_) => "SetRequestHeader",
+            Filter::RemoveRequestHeader(_) => "RemoveRequestHeader",
+            _ => "other",
+        };
+        route.filters.iter().map(name).collect()
+    }
+
+    #[test]
+    fn merges_default_filters_by_order() {
+        let routes = build_routes(from_str(CONFIG).unwrap()).unwrap();
+        // Equal orders keep default filters first
+        let merged = [
+            "PrefixPath",
+            "StripPrefix",
+            "SetRequestHeader",
+            "AddRequestHeader",
+            "RemoveRequestHeader",
+        ];
+        assert_eq!(filter_names(&routes[0]), merged);
+        assert_eq!(filter_names(&routes[1]), ["PrefixPath"]);
+    }
+
+    #[test]
+    fn rejects_skipping_unknown_default_filters() {
+        let config = CONFIG.replace("[strip-cookies, SetRequestHeader]", "[strip-cookies, Retry]");
+        let err = build_routes(from_str(&config).unwrap()).unwrap_err();
+        assert!(err.to_string().contains("skips unknown default filter 'Retry'"), "{}", err);
+    }
+
+    #[test]
+    fn puts_global_cors_ahead_of_every_chain() {
+        let config = format!("cors:\n  allowed_origins: ['*']\n{}", CONFIG);
+        let routes = build_routes(from_str(&config).unwrap()).unwrap();
+        assert!(routes.iter().all(|route| filter_names(route)[0] == "Cors"));
+    }
+
+    #[test]
+    fn builds_nested_combinators_and_rejects_empty_groups() {
+        let nested = r#"
+type: AllOf
+predicates:
+  - type: Method
+    method: GET
+  - type: Not
+    predicate:
+      type: AnyOf
+      predicates:
+        - type: Path
+          path: /internal
+        - type: Path
+          path: /admin
+"#;
+        let predicate = build_predicate(from_str(nested).unwrap()).unwrap();
+        let request = |path: &str| http::Request::builder().uri(path).body(()).unwrap();
+        assert!(predicate.evaluate(&request("/items")));
+        assert!(!predicate.evaluate(&request("/admin")));
+
+        for empty in ["{type: AllOf, predicates: []}", "{type: AnyOf, predicates: []}"] {
+            let err = build_predicate(from_str(empty).unwrap()).unwrap_err();
+            assert!(err.to_string().contains("group has no predicates"), "{}", err);
+        }
+        let hidden = "{type: Not, predicate: {type: AnyOf, predicates: []}}";
+        assert!(build_predicate(from_str(hidden).unwrap()).is_err());
+    }
+
+    #[tokio::test]
+    async fn builds_static_responses() {
+        use http_body_util::BodyExt;
+
+        let route = |response: &str| {
+            let config = format!(
+                "routes:\n  - {{id: robots, predicates: [{{type: Path, path: /robots.txt}}], {}}}",
+                response
+            );
+            build_routes(from_str(&config).unwrap())
+        };
+        let routes = route(
+            "response: {headers: [[content-type, text/plain], [vary, a], [vary, b]], body: 'x'}",
+        )
+        .unwrap();
+        let Destination::Static(response) = &routes[0].destination else { panic!() };
+        let response = response.respond();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert_eq!(response.headers().get_all("vary").iter().count(), 2);
+        assert_eq!(response.into_body().collect().await.unwrap().to_bytes(), "x");
+
+        let routes = route("response: {status: 503}").unwrap();
+        let Destination::Static(response) = &routes[0].destination else { panic!() };
+        assert_eq!(response.respond().status(), StatusCode::SERVICE_UNAVAILABLE);
 
-        Ok(routes)
+        assert!(route("response: {status: 1000}").is_err());
+        assert!(route("response: {headers: [[bad name, x]]}").is_err());
+        assert!(route("response: {}, destination: http://localhost:9000").is_err());
+        assert!(route("filters: []").is_err());
     }
 }
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use tokio::fs;
+use tokio::sync::RwLock;
+
+use crate::gateway::config_loader::ConfigLoader;
+use crate::gateway::metrics::METRICS;
+use crate::gateway::route_table::RouteTable;
+
+/// Polls the config file and swaps in a fresh route table whenever it changes.
+///
+/// A file that fails to load or validate leaves the current routes in place.
+pub async fn watch_config<L: ConfigLoader>(
+    file_path: String,
+    routes: Arc<RwLock<Arc<RouteTable>>>,
+    interval: Duration,
+) {
+    let mut last_modified = modified_at(&file_path).await;
+    let mut ticker = tokio::time::interval(interval);
+
+    loop {
+        ticker.tick().await;
+        let modified = modified_at(&file_path).await;
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        match L::load_config(&file_path).await {
+            Ok(table) => {
+                *routes.write().await = Arc::new(table);
+                METRICS.config_reloaded(true);
+            }
+            Err(err) => {
+                eprintln!("Config reload failed, keeping current routes: {err}");
+                METRICS.config_reloaded(false);
+            }
+        }
+    }
+}
+
+async fn modified_at(file_path: &str) -> Option<SystemTime> {
+    fs::metadata(file_path).await.ok()?.modified().ok()
+}
@@ -1,40 +1,343 @@
 use async_trait::async_trait;
+use std::net::SocketAddr;
+
+use bytes::{Bytes, BytesMut};
+use http::header::{AUTHORIZATION, CONTENT_LENGTH, WWW_AUTHENTICATE};
+use http::uri::PathAndQuery;
+use http::{HeaderName, HeaderValue, Response, StatusCode, Uri};
+use http_body_util::BodyExt;
 use hyper::body::Incoming;
 
 pub use add_request_header::AddRequestHeader;
 pub use add_request_headers_if_not_present::AddRequestHeadersIfNotPresent;
 pub use add_request_parameter::AddRequestParameter;
+pub use api_key_auth::ApiKeyAuth;
+pub use basic_auth::BasicAuth;
+pub use cors::{is_preflight, Cors, OriginPattern};
+pub use concurrency_limit::{
+    configure_groups, ConcurrencyLimit, ConcurrencyPermits, LimitAlgorithm, LimitSettings,
+};
+pub use hmac_signature::{
+    HmacAlgorithm, HmacSignature, SignatureEncoding, SignatureFormat, SignatureHeader,
+    TimestampSource,
+};
+pub use jwt_auth::{JwtAuth, JwtClaims, JwtKey, KeySource};
+pub use map_request_header::MapRequestHeader;
+pub use prefix_path::PrefixPath;
+pub use redirect_to::RedirectTo;
+pub use preserve_host_header::{restore_host, PreserveHostHeader, PreservedHost};
+pub use remove_request_header::RemoveRequestHeader;
+pub use remove_request_parameter::RemoveRequestParameter;
+pub use request_id::{request_id, IdGenerator, RequestId, RequestIdValue};
+pub use request_rate_limiter::{
+    BucketBackend, KeyResolver, Limits, RedisBuckets, RequestRateLimiter,
+};
+pub use rewrite_path::RewritePath;
+pub use secure_headers::{SecureHeaders, PERMISSIONS_POLICY};
+pub use set_path::SetPath;
+pub use set_request_header::SetRequestHeader;
+pub use set_request_host_header::SetRequestHostHeader;
+pub use strip_prefix::StripPrefix;
+pub use token_introspection::{IntrospectionHeaders, TokenIntrospection};
 
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::predicates::UriTemplateVariables;
+use crate::gateway::route::RouteId;
+use crate::gateway::template::Template;
 use crate::gateway::Request;
 
 pub mod add_request_header;
 pub mod add_request_headers_if_not_present;
 pub mod add_request_parameter;
+pub mod api_key_auth;
+pub mod basic_auth;
+pub mod concurrency_limit;
+pub mod cors;
+pub mod hmac_signature;
+pub mod jwt_auth;
+pub mod map_request_header;
+pub mod prefix_path;
+pub mod redirect_to;
+pub mod preserve_host_header;
+pub mod remove_request_header;
+pub mod remove_request_parameter;
+pub mod request_id;
+pub mod request_rate_limiter;
+pub mod rewrite_path;
+pub mod secure_headers;
+pub mod set_path;
+pub mod set_request_header;
+pub mod set_request_host_header;
+pub mod strip_prefix;
+pub mod token_introspection;
+
+/// `Err` stops the filter chain and sends the response straight back to the
+/// client instead of forwarding the request.
+pub type FilteredResult = Result<Request<Incoming>, Response<BoxBody>>;
+
+/// The request URI as received, before any filter rewrote it.
+#[derive(Clone, Debug)]
+pub struct OriginalUri(pub Uri);
 
-pub type FilteredResult = Result<Request<Incoming>, hyper::Error>;
+/// Request body read into memory by a filter that had to inspect it. It is
+/// forwarded in place of the drained `Incoming` body.
+#[derive(Clone, Debug)]
+pub struct BufferedBody(pub Bytes);
+
+/// Identity of the client, set by an authentication filter.
+#[derive(Clone, Debug)]
+pub struct Principal(pub String);
 
 #[async_trait]
 pub trait Filterable: Send + Sync {
     async fn apply(&self, req: Request<Incoming>) -> FilteredResult;
+
+    /// Adjusts the response on its way back to the client, whether it came
+    /// from the upstream or from a filter. `req` holds the client's request
+    /// head as matched, before any filter changed it.
+    fn apply_response(&self, _req: &Request<()>, _response: &mut Response<BoxBody>) {}
 }
 
 #[derive(Clone, Debug)]
 pub enum Filter {
     AddRequestHeader(AddRequestHeader),
     AddRequestHeadersIfNotPresent(AddRequestHeadersIfNotPresent),
-    AddRequestParameters(AddRequestParameter)
+    AddRequestParameters(AddRequestParameter),
+    StripPrefix(StripPrefix),
+    PrefixPath(PrefixPath),
+    SetPath(SetPath),
+    RewritePath(RewritePath),
+    RemoveRequestHeader(RemoveRequestHeader),
+    SetRequestHeader(SetRequestHeader),
+    MapRequestHeader(MapRequestHeader),
+    RemoveRequestParameter(RemoveRequestParameter),
+    PreserveHostHeader(PreserveHostHeader),
+    SetRequestHostHeader(SetRequestHostHeader),
+    RedirectTo(RedirectTo),
+    RequestRateLimiter(RequestRateLimiter),
+    ConcurrencyLimit(ConcurrencyLimit),
+    JwtAuth(Box<JwtAuth>),
+    ApiKeyAuth(ApiKeyAuth),
+    BasicAuth(BasicAuth),
+    TokenIntrospection(TokenIntrospection),
+    HmacSignature(Box<HmacSignature>),
+    Cors(Cors),
+    SecureHeaders(SecureHeaders),
+    RequestId(RequestId),
     // Add other filter variants here...
 }
 
+impl Filter {
+    /// The filter's type as written in the config, for metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Filter::AddRequestHeader(_) => "AddRequestHeader",
+            Filter::AddRequestHeadersIfNotPresent(_) => "AddRequestHeadersIfNotPresent",
+            Filter::AddRequestParameters(_) => "AddRequestParameter",
+            Filter::StripPrefix(_) => "StripPrefix",
+            Filter::PrefixPath(_) => "PrefixPath",
+            Filter::SetPath(_) => "SetPath",
+            Filter::RewritePath(_) => "RewritePath",
+            Filter::RemoveRequestHeader(_) => "RemoveRequestHeader",
+            Filter::SetRequestHeader(_) => "SetRequestHeader",
+            Filter::MapRequestHeader(_) => "MapRequestHeader",
+            Filter::RemoveRequestParameter(_) => "RemoveRequestParameter",
+            Filter::PreserveHostHeader(_) => "PreserveHostHeader",
+            Filter::SetRequestHostHeader(_) => "SetRequestHostHeader",
+            Filter::RedirectTo(_) => "RedirectTo",
+            Filter::RequestRateLimiter(_) => "RequestRateLimiter",
+            Filter::ConcurrencyLimit(_) => "ConcurrencyLimit",
+            Filter::JwtAuth(_) => "JwtAuth",
+            Filter::ApiKeyAuth(_) => "ApiKeyAuth",
+            Filter::BasicAuth(_) => "BasicAuth",
+            Filter::TokenIntrospection(_) => "TokenIntrospection",
+            Filter::HmacSignature(_) => "HmacSignature",
+            Filter::Cors(_) => "Cors",
+            Filter::SecureHeaders(_) => "SecureHeaders",
+            Filter::RequestId(_) => "RequestId",
+            // Match other filter variants here...
+        }
+    }
+}
+
 #[async_trait]
 impl Filterable for Filter {
     async fn apply(&self,  req: Request<Incoming>) -> FilteredResult {
         match self {
             Filter::AddRequestHeader(f) => f.apply(req).await,
             Filter::AddRequestHeadersIfNotPresent(f) => f.apply(req).await,
-            Filter::AddRequestParameters(f) => f.apply(req).await
+            Filter::AddRequestParameters(f) => f.apply(req).await,
+            Filter::StripPrefix(f) => f.apply(req).await,
+            Filter::PrefixPath(f) => f.apply(req).await,
+            Filter::SetPath(f) => f.apply(req).await,
+            Filter::RewritePath(f) => f.apply(req).await,
+            Filter::RemoveRequestHeader(f) => f.apply(req).await,
+            Filter::SetRequestHeader(f) => f.apply(req).await,
+            Filter::MapRequestHeader(f) => f.apply(req).await,
+            Filter::RemoveRequestParameter(f) => f.apply(req).await,
+            Filter::PreserveHostHeader(f) => f.apply(req).await,
+            Filter::SetRequestHostHeader(f) => f.apply(req).await,
+            Filter::RedirectTo(f) => f.apply(req).await,
+            Filter::RequestRateLimiter(f) => f.apply(req).await,
+            Filter::ConcurrencyLimit(f) => f.apply(req).await,
+            Filter::JwtAuth(f) => f.apply(req).await,
+            Filter::ApiKeyAuth(f) => f.apply(req).await,
+            Filter::BasicAuth(f) => f.apply(req).await,
+            Filter::TokenIntrospection(f) => f.apply(req).await,
+            Filter::HmacSignature(f) => f.apply(req).await,
+            Filter::Cors(f) => f.apply(req).await,
+            Filter::SecureHeaders(f) => f.apply(req).await,
+            Filter::RequestId(f) => f.apply(req).await,
             // Match other filter variants here...
         }
     }
+
+    fn apply_response(&self, req: &Request<()>, response: &mut Response<BoxBody>) {
+        match self {
+            Filter::Cors(f) => f.apply_response(req, response),
+            Filter::SecureHeaders(f) => f.apply_response(req, response),
+            Filter::RequestId(f) => f.apply_response(req, response),
+            // Other filters only act on requests
+            _ => {}
+        }
+    }
+}
+
+/// 500 response for a filter that could not process the request.
+pub fn filter_failed(reason: &str) -> Response<BoxBody> {
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .body(single_chunk_response_body(format!("Filter application failed: {}", reason)))
+        .unwrap()
+}
+
+/// Replaces the request path, keeping the query string.
+fn set_request_path<B>(req: &mut Request<B>, path: &str) -> Result<(), &'static str> {
+    let path_and_query = match req.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut uri_parts = req.uri().clone().into_parts();
+    uri_parts.path_and_query =
+        Some(path_and_query.parse::<PathAndQuery>().map_err(|_| "invalid rewritten path")?);
+    *req.uri_mut() = Uri::from_parts(uri_parts).map_err(|_| "invalid rewritten URI")?;
+    Ok(())
 }
 
+fn header_name(name: &str) -> Result<HeaderName, GatewayError> {
+    name.parse::<HeaderName>()
+        .map_err(|_| GatewayError::InvalidConfig(format!("invalid header name '{}'", name)))
+}
+
+/// Parses a header value template, rejecting literal text that could never
+/// form a valid header value.
+fn header_value_template(value: &str) -> Result<Template, GatewayError> {
+    let template = Template::parse(value)?;
+    let literal_text = template.render(|_| Some(String::new())).unwrap_or_default();
+    HeaderValue::from_str(&literal_text)
+        .map_err(|_| GatewayError::InvalidConfig(format!("invalid header value '{}'", value)))?;
+    Ok(template)
+}
+
+/// Fills a template for one request. Variables captured by the route's
+/// predicates come first, then `{client_ip}`, `{route_id}` and, after
+/// `JwtAuth`, `{claim.<name>}`.
+pub fn render_template<B>(template: &Template, req: &Request<B>) -> Result<String, String> {
+    let variables = req.extensions().get::<UriTemplateVariables>();
+    template
+        .render(|name| {
+            if let Some(value) = variables.and_then(|variables| variables.0.get(name)) {
+                return Some(value.clone());
+            }
+            match name {
+                "client_ip" => req.extensions().get::<SocketAddr>().map(|a| a.ip().to_string()),
+                "route_id" => req.extensions().get::<RouteId>().map(|id| id.0.clone()),
+                _ => {
+                    let claim = name.strip_prefix("claim.")?;
+                    req.extensions().get::<JwtClaims>()?.0.get(claim).map(jwt_auth::claim_text)
+                }
+            }
+        })
+        .map_err(|name| format!("unknown template variable '{}'", name))
+}
+
+/// Token from an `Authorization: Bearer` header.
+fn bearer_token<B>(req: &Request<B>) -> Option<&str> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    let token = token.trim();
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+/// RFC 6750 challenge: `invalid_token` for 401 and `insufficient_scope` for
+/// 403. The error attributes are left out when no token was sent at all.
+fn bearer_challenge(status: StatusCode, description: Option<&str>) -> Response<BoxBody> {
+    let challenge = match description {
+        Some(description) => {
+            let error =
+                if status == StatusCode::FORBIDDEN { "insufficient_scope" } else { "invalid_token" };
+            format!(
+                r#"Bearer realm="gateway", error="{}", error_description="{}""#,
+                error, description
+            )
+        }
+        None => r#"Bearer realm="gateway""#.to_string(),
+    };
+    Response::builder()
+        .status(status)
+        .header(WWW_AUTHENTICATE, challenge)
+        .body(single_chunk_response_body(status.canonical_reason().unwrap_or_default()))
+        .unwrap()
+}
+
+/// Compares secrets in time independent of where the first difference is.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Reads the request body into memory, at most `limit` bytes, and keeps it as
+/// a `BufferedBody` extension so later filters and forwarding reuse it.
+async fn buffer_body(
+    req: &mut Request<Incoming>,
+    limit: usize,
+) -> Result<Bytes, Response<BoxBody>> {
+    let too_large = || {
+        Response::builder()
+            .status(StatusCode::PAYLOAD_TOO_LARGE)
+            .body(single_chunk_response_body("Payload Too Large"))
+            .unwrap()
+    };
+    if let Some(BufferedBody(body)) = req.extensions().get::<BufferedBody>() {
+        return if body.len() > limit { Err(too_large()) } else { Ok(body.clone()) };
+    }
+    let declared = req.headers().get(CONTENT_LENGTH).and_then(|value| value.to_str().ok());
+    let declared = declared.and_then(|length| length.parse::<usize>().ok());
+    if declared.is_some_and(|length| length > limit) {
+        return Err(too_large());
+    }
+
+    let mut body = BytesMut::new();
+    while let Some(frame) = req.body_mut().frame().await {
+        let frame = frame.map_err(|_| {
+            Response::builder()
+                .status(StatusCode::BAD_REQUEST)
+                .body(single_chunk_response_body("Bad Request: cannot read body"))
+                .unwrap()
+        })?;
+        if let Ok(data) = frame.into_data() {
+            if body.len() + data.len() > limit {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&data);
+        }
+    }
+    let body = body.freeze();
+    req.extensions_mut().insert(BufferedBody(body.clone()));
+    Ok(body)
+}
+
+fn render_header_value<B>(template: &Template, req: &Request<B>) -> Result<HeaderValue, String> {
+    let value = render_template(template, req)?;
+    HeaderValue::from_str(&value).map_err(|_| format!("invalid header value '{}'", value))
+}
@@ -1,29 +1,91 @@
 use async_trait::async_trait;
-use http::{HeaderName, HeaderValue};
+use http::HeaderName;
 use hyper::{body::Incoming, Request};
 
-use super::Filterable;
-use super::FilteredResult;
+use super::{filter_failed, header_name, header_value_template, render_header_value};
+use super::{Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::template::Template;
 
+/// Appends a header value, keeping any values the request already has.
 #[derive(Clone, Debug)]
 pub struct AddRequestHeader {
-    pub name: String,
-    pub value: String,
+    pub name: HeaderName,
+    pub value: Template,
 }
 
 impl AddRequestHeader {
-    pub fn new(name: String, value: String) -> Self {
-        Self { name, value }
+    pub fn new(name: &str, value: &str) -> Result<Self, GatewayError> {
+        Ok(Self { name: header_name(name)?, value: header_value_template(value)? })
+    }
+
+    fn update<B>(&self, req: &mut Request<B>) -> Result<(), String> {
+        let value = render_header_value(&self.value, req)?;
+        req.headers_mut().append(self.name.clone(), value);
+        Ok(())
     }
 }
 
 #[async_trait]
 impl Filterable for AddRequestHeader {
     async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
-        req.headers_mut().insert(
-            self.name.parse::<HeaderName>().unwrap(),
-            self.value.parse::<HeaderValue>().unwrap(),
-        );
+        self.update(&mut req).map_err(|e| filter_failed(&e))?;
         Ok(req)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::gateway::filters::JwtClaims;
+    use crate::gateway::predicates::UriTemplateVariables;
+    use crate::gateway::route::RouteId;
+
+    fn values(req: &Request<()>, name: &str) -> Vec<String> {
+        let values = req.headers().get_all(name).iter();
+        values.map(|value| value.to_str().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn appends_to_existing_values() {
+        let mut req = Request::builder().header("x-tag", "client").body(()).unwrap();
+        AddRequestHeader::new("X-Tag", "gateway").unwrap().update(&mut req).unwrap();
+        assert_eq!(values(&req, "x-tag"), ["client", "gateway"]);
+    }
+
+    #[test]
+    fn renders_variables_client_ip_route_id_and_claims() {
+        let mut req = Request::new(());
+        let variables = [("sub".to_string(), "shop".to_string())].into_iter().collect();
+        req.extensions_mut().insert(UriTemplateVariables(variables));
+        req.extensions_mut().insert(SocketAddr::from(([10, 0, 0, 7], 5000)));
+        req.extensions_mut().insert(RouteId("orders".to_string()));
+        let claims = serde_json::json!({"sub": "alice", "roles": ["admin", "dev"], "level": 3});
+        let serde_json::Value::Object(claims) = claims else { unreachable!() };
+        req.extensions_mut().insert(JwtClaims(Arc::new(claims)));
+
+        let template = "{sub}|{client_ip}|{route_id}|{claim.sub}|{claim.roles}|{claim.level}";
+        AddRequestHeader::new("x-context", template).unwrap().update(&mut req).unwrap();
+        assert_eq!(values(&req, "x-context"), ["shop|10.0.0.7|orders|alice|admin,dev|3"]);
+    }
+
+    #[test]
+    fn fails_on_unknown_variables_and_invalid_values() {
+        let filter = AddRequestHeader::new("x-user", "{claim.sub}").unwrap();
+        assert!(filter.update(&mut Request::new(())).is_err());
+
+        let mut req = Request::new(());
+        let variables = [("sub".to_string(), "a\nb".to_string())].into_iter().collect();
+        req.extensions_mut().insert(UriTemplateVariables(variables));
+        let filter = AddRequestHeader::new("x-sub", "{sub}").unwrap();
+        assert!(filter.update(&mut req).is_err());
+        assert!(req.headers().is_empty());
+
+        assert!(AddRequestHeader::new("bad name", "x").is_err());
+        assert!(AddRequestHeader::new("x-ok", "bad\nvalue").is_err());
+        assert!(AddRequestHeader::new("x-ok", "{unclosed").is_err());
+    }
+}
@@ -1,33 +1,64 @@
 use async_trait::async_trait;
-use http::{HeaderName, HeaderValue};
+use http::HeaderName;
 use hyper::{body::Incoming, Request};
 
-use super::Filterable;
-use super::FilteredResult;
+use super::{filter_failed, header_name, header_value_template, render_header_value};
+use super::{Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::template::Template;
 
+/// Adds headers the client did not send. Listing a name more than once adds
+/// every one of its values.
 #[derive(Clone, Debug)]
 pub struct AddRequestHeadersIfNotPresent {
-    pub headers: Vec<(String, String)>,
+    pub headers: Vec<(HeaderName, Template)>,
 }
 
 impl AddRequestHeadersIfNotPresent {
-    pub fn new(headers: Vec<(String, String)>) -> Self {
-        Self { headers }
+    pub fn new(headers: Vec<(String, String)>) -> Result<Self, GatewayError> {
+        let headers = headers
+            .iter()
+            .map(|(name, value)| Ok((header_name(name)?, header_value_template(value)?)))
+            .collect::<Result<_, GatewayError>>()?;
+        Ok(Self { headers })
+    }
+
+    fn update<B>(&self, req: &mut Request<B>) -> Result<(), String> {
+        let missing: Vec<bool> =
+            self.headers.iter().map(|(name, _)| !req.headers().contains_key(name)).collect();
+        for ((name, value), missing) in self.headers.iter().zip(missing) {
+            if missing {
+                let value = render_header_value(value, req)?;
+                req.headers_mut().append(name.clone(), value);
+            }
+        }
+        Ok(())
     }
 }
 
 #[async_trait]
 impl Filterable for AddRequestHeadersIfNotPresent {
     async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
-        for (name, value) in &self.headers {
-            let header_name = name.parse::<HeaderName>().unwrap();
-            if !req.headers().contains_key(&header_name) {
-                req.headers_mut().insert(
-                    header_name,
-                    value.parse::<HeaderValue>().unwrap(),
-                );
-            }
-        }
+        self.update(&mut req).map_err(|e| filter_failed(&e))?;
         Ok(req)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(headers: &[(&str, &str)]) -> AddRequestHeadersIfNotPresent {
+        let headers = headers.iter().map(|(name, value)| (name.to_string(), value.to_string()));
+        AddRequestHeadersIfNotPresent::new(headers.collect()).unwrap()
+    }
+
+    #[test]
+    fn adds_only_headers_the_client_did_not_send() {
+        let mut req = Request::builder().header("x-tenant", "client").body(()).unwrap();
+        let filter = filter(&[("x-tenant", "default"), ("x-lang", "en"), ("x-lang", "de")]);
+        filter.update(&mut req).unwrap();
+        assert_eq!(req.headers().get_all("x-tenant").iter().collect::<Vec<_>>(), ["client"]);
+        assert_eq!(req.headers().get_all("x-lang").iter().collect::<Vec<_>>(), ["en", "de"]);
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::header::WWW_AUTHENTICATE;
+use http::{HeaderName, HeaderValue, Response, StatusCode};
+use hyper::{body::Incoming, Request};
+use sha2::{Digest, Sha256};
+
+use super::{
+    filter_failed, header_name, Filterable, FilteredResult, Principal, RemoveRequestParameter,
+};
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::errors::GatewayError;
+
+/// Checks an API key from a header or query parameter against SHA-256
+/// digests, so the gateway never stores usable keys. The key is removed from
+/// the forwarded request and its consumer becomes the request principal.
+#[derive(Clone, Debug)]
+pub struct ApiKeyAuth {
+    pub header: HeaderName,
+    pub query_param: Option<RemoveRequestParameter>,
+    /// Consumers by key digest.
+    pub keys: Arc<HashMap<[u8; 32], String>>,
+    /// Consumers allowed on the route; empty allows every known consumer.
+    pub consumers: Vec<String>,
+    pub consumer_header: Option<HeaderName>,
+}
+
+impl ApiKeyAuth {
+    pub fn new(
+        header: &str,
+        query_param: Option<String>,
+        keys: Vec<(String, String)>,
+        keys_file: Option<&str>,
+        consumers: Vec<String>,
+        consumer_header: Option<&str>,
+    ) -> Result<Self, GatewayError> {
+        let mut entries = keys;
+        if let Some(path) = keys_file {
+            entries.extend(read_keys_file(path)?);
+        }
+        let mut digests = HashMap::new();
+        for (consumer, digest) in entries {
+            let invalid = || {
+                GatewayError::InvalidConfig(format!(
+                    "API key of '{}' must be a hex SHA-256 digest",
+                    consumer
+                ))
+            };
+            let digest = parse_digest(&digest).ok_or_else(invalid)?;
+            if let Some(other) = digests.get(&digest) {
+                return Err(GatewayError::InvalidConfig(format!(
+                    "API key of '{}' is also listed for '{}'",
+                    consumer, other
+                )));
+            }
+            digests.insert(digest, consumer);
+        }
+        Ok(Self {
+            header: header_name(header)?,
+            query_param: query_param.map(RemoveRequestParameter::new),
+            keys: Arc::new(digests),
+            consumers,
+            consumer_header: consumer_header.map(header_name).transpose()?,
+        })
+    }
+
+    fn presented_key<B>(&self, req: &Request<B>) -> Option<String> {
+        if let Some(value) = req.headers().get(&self.header) {
+            return value.to_str().ok().map(str::to_string);
+        }
+        let param = &self.query_param.as_ref()?.name;
+        let query = req.uri().query()?;
+        form_urlencoded::parse(query.as_bytes())
+            .find(|(key, _)| key == param)
+            .map(|(_, value)| value.into_owned())
+    }
+
+    /// The consumer owning the presented key, or the status and message
+    /// turning the request away.
+    fn consumer<B>(&self, req: &Request<B>) -> Result<&str, (StatusCode, &'static str)> {
+        let Some(key) = self.presented_key(req) else {
+            return Err((StatusCode::UNAUTHORIZED, "API key required"));
+        };
+        let digest: [u8; 32] = Sha256::digest(key.as_bytes()).into();
+        let Some(consumer) = self.keys.get(&digest) else {
+            return Err((StatusCode::UNAUTHORIZED, "Invalid API key"));
+        };
+        if !self.consumers.is_empty() && !self.consumers.contains(consumer) {
+            return Err((StatusCode::FORBIDDEN, "Forbidden"));
+        }
+        Ok(consumer)
+    }
+
+    /// Removes the key from the request and names its consumer instead.
+    fn admit<B>(&self, req: &mut Request<B>, consumer: &str) -> Result<(), &'static str> {
+        req.headers_mut().remove(&self.header);
+        if let Some(remove_param) = &self.query_param {
+            remove_param.rewrite(req)?;
+        }
+        if let Some(header) = &self.consumer_header {
+            match HeaderValue::from_str(consumer) {
+                Ok(value) => req.headers_mut().insert(header.clone(), value),
+                Err(_) => req.headers_mut().remove(header),
+            };
+        }
+        req.extensions_mut().insert(Principal(consumer.to_string()));
+        Ok(())
+    }
+}
+
+/// `consumer:sha256-hex` per line; blank lines and `#` comments are skipped.
+fn read_keys_file(path: &str) -> Result<Vec<(String, String)>, GatewayError> {
+    let contents = std::fs::read_to_string(path).map_err(|err| {
+        GatewayError::InvalidConfig(format!("cannot read API key file '{}': {}", path, err))
+    })?;
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (consumer, digest) = line.split_once(':').ok_or_else(|| {
+                GatewayError::InvalidConfig(format!("malformed line in '{}': {}", path, line))
+            })?;
+            Ok((consumer.to_string(), digest.to_string()))
+        })
+        .collect()
+}
+
+fn parse_digest(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut digest = [0; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+#[async_trait]
+impl Filterable for ApiKeyAuth {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        let consumer = match self.consumer(&req) {
+            Ok(consumer) => consumer.to_string(),
+            Err((status, message)) => return Err(rejected(status, message)),
+        };
+        self.admit(&mut req, &consumer).map_err(filter_failed)?;
+        Ok(req)
+    }
+}
+
+fn rejected(status: StatusCode, message: &'static str) -> Response<BoxBody> {
+    let mut response = Response::builder().status(status);
+    if status == StatusCode::UNAUTHORIZED {
+        response = response.header(WWW_AUTHENTICATE, r#"ApiKey realm="gateway""#);
+    }
+    response.body(single_chunk_response_body(message)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(key: &str) -> String {
+        Sha256::digest(key).iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    fn filter(consumers: &[&str]) -> ApiKeyAuth {
+        let keys = vec![
+            ("alice".to_string(), digest("alice-key")),
+            ("bob".to_string(), digest("bob-key")),
+        ];
+        let consumers = consumers.iter().map(|consumer| consumer.to_string()).collect();
+        let query_param = Some("api_key".to_string());
+        ApiKeyAuth::new("x-api-key", query_param, keys, None, consumers, Some("x-consumer"))
+            .unwrap()
+    }
+
+    fn admitted<B>(filter: &ApiKeyAuth, mut req: Request<B>) -> Request<B> {
+        let consumer = filter.consumer(&req).unwrap().to_string();
+        filter.admit(&mut req, &consumer).unwrap();
+        req
+    }
+
+    #[test]
+    fn accepts_a_key_in_the_header() {
+        let req = Request::get("/orders")
+            .header("x-api-key", "alice-key")
+            .header("x-consumer", "mallory")
+            .body(())
+            .unwrap();
+        let req = admitted(&filter(&[]), req);
+        assert!(req.headers().get("x-api-key").is_none());
+        assert_eq!(req.headers()["x-consumer"], "alice");
+        assert_eq!(req.extensions().get::<Principal>().unwrap().0, "alice");
+    }
+
+    #[test]
+    fn accepts_a_key_in_the_query_and_strips_it() {
+        let req = Request::get("/orders?page=2&api_key=bob-key&sort=asc").body(()).unwrap();
+        let req = admitted(&filter(&[]), req);
+        assert_eq!(req.uri(), "/orders?page=2&sort=asc");
+        assert_eq!(req.headers()["x-consumer"], "bob");
+
+        let req = admitted(&filter(&[]), Request::get("/orders?api_key=bob-key").body(()).unwrap());
+        assert_eq!(req.uri(), "/orders");
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_keys() {
+        let filter = filter(&[]);
+        let missing = Request::get("/orders?page=2").body(()).unwrap();
+        assert_eq!(filter.consumer(&missing), Err((StatusCode::UNAUTHORIZED, "API key required")));
+        let wrong = Request::get("/orders").header("x-api-key", "guess").body(()).unwrap();
+        assert_eq!(filter.consumer(&wrong), Err((StatusCode::UNAUTHORIZED, "Invalid API key")));
+        let wrong = Request::get("/orders?api_key=guess").body(()).unwrap();
+        assert_eq!(filter.consumer(&wrong), Err((StatusCode::UNAUTHORIZED, "Invalid API key")));
+    }
+
+    #[test]
+    fn forbids_consumers_outside_the_allowed_list() {
+        let filter = filter(&["alice"]);
+        let alice = Request::get("/").header("x-api-key", "alice-key").body(()).unwrap();
+        assert_eq!(filter.consumer(&alice), Ok("alice"));
+        let bob = Request::get("/").header("x-api-key", "bob-key").body(()).unwrap();
+        assert_eq!(filter.consumer(&bob), Err((StatusCode::FORBIDDEN, "Forbidden")));
+    }
+
+    #[test]
+    fn rejects_a_key_listed_twice() {
+        let keys = vec![
+            ("alice".to_string(), digest("shared-key")),
+            ("bob".to_string(), digest("shared-key")),
+        ];
+        let err = ApiKeyAuth::new("x-api-key", None, keys, None, Vec::new(), None).unwrap_err();
+        assert!(err.to_string().contains("also listed for 'alice'"), "{}", err);
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use argon2::password_hash::PasswordHash;
+use argon2::{Argon2, PasswordVerifier};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use http::{Response, StatusCode};
+use hyper::{body::Incoming, Request};
+use sha2::{Digest, Sha256};
+
+use super::{Filterable, FilteredResult, Principal};
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::errors::GatewayError;
+
+/// How long a verified user and password pair skips the slow hash check.
+const VERIFIED_TTL: Duration = Duration::from_secs(300);
+const MAX_VERIFIED: usize = 10_000;
+/// Checked for unknown users so that they take as long to reject as a wrong
+/// password, and response times do not reveal which user names exist.
+const DUMMY_HASH: &str = "$2b$10$JN.H3R.09lwZop6M0xN/pe1mf1b6BawR7ZogtHn6hXOdnYcUpPsCa";
+
+/// HTTP Basic authentication against an htpasswd file with bcrypt or argon2
+/// hashes. The user name becomes the request principal and the credentials
+/// are not passed on.
+#[derive(Clone, Debug)]
+pub struct BasicAuth {
+    pub users: Arc<HashMap<String, String>>,
+    pub realm: String,
+    /// Users allowed on the route; empty allows everyone in the file.
+    pub allowed: Vec<String>,
+    /// Digests of recently verified credentials, since bcrypt and argon2 are
+    /// deliberately too slow to run on every request.
+    verified: Arc<Mutex<HashMap<[u8; 32], Instant>>>,
+}
+
+impl BasicAuth {
+    pub fn new(
+        htpasswd_file: &str,
+        realm: String,
+        allowed: Vec<String>,
+    ) -> Result<Self, GatewayError> {
+        let invalid = |reason: String| {
+            GatewayError::InvalidConfig(format!("htpasswd file '{}': {}", htpasswd_file, reason))
+        };
+        let contents =
+            std::fs::read_to_string(htpasswd_file).map_err(|e| invalid(e.to_string()))?;
+        let mut users = HashMap::new();
+        for line in contents.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if line.starts_with('#') {
+                continue;
+            }
+            let (user, hash) = line
+                .split_once(':')
+                .ok_or_else(|| invalid(format!("malformed line for '{}'", line)))?;
+            if !is_bcrypt(hash) && PasswordHash::new(hash).is_err() {
+                return Err(invalid(format!("user '{}' needs a bcrypt or argon2 hash", user)));
+            }
+            users.insert(user.to_string(), hash.to_string());
+        }
+        Ok(Self { users: Arc::new(users), realm, allowed, verified: Arc::default() })
+    }
+
+    /// The user of the request's credentials, or the status turning it away.
+    async fn authenticate<B: Sync>(
+        &self,
+        req: &Request<B>,
+        now: Instant,
+    ) -> Result<String, StatusCode> {
+        let Some((user, password)) = basic_credentials(req) else {
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+        if !self.check_at(&user, &password, now).await {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        if !self.allowed.is_empty() && !self.allowed.contains(&user) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+        Ok(user)
+    }
+
+    async fn check_at(&self, user: &str, password: &str, now: Instant) -> bool {
+        let Some(hash) = self.users.get(user) else {
+            let password = password.to_string();
+            let _ = tokio::task::spawn_blocking(move || verify(DUMMY_HASH, &password)).await;
+            return false;
+        };
+        let digest: [u8; 32] = Sha256::new()
+            .chain_update(user)
+            .chain_update([0])
+            .chain_update(password)
+            .finalize()
+            .into();
+        let cached = self.verified.lock().unwrap().get(&digest).copied();
+        if cached.is_some_and(|at| now.saturating_duration_since(at) < VERIFIED_TTL) {
+            return true;
+        }
+
+        let (hash, password) = (hash.clone(), password.to_string());
+        let verified = tokio::task::spawn_blocking(move || verify(&hash, &password)).await;
+        if verified.unwrap_or(false) {
+            let mut cache = self.verified.lock().unwrap();
+            if cache.len() >= MAX_VERIFIED {
+                cache.clear();
+            }
+            cache.insert(digest, now);
+            return true;
+        }
+        false
+    }
+
+    fn challenge(&self) -> Response<BoxBody> {
+        Response::builder()
+            .status(StatusCode::UNAUTHORIZED)
+            .header(WWW_AUTHENTICATE, format!(r#"Basic realm="{}", charset="UTF-8""#, self.realm))
+            .body(single_chunk_response_body("Unauthorized"))
+            .unwrap()
+    }
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix))
+}
+
+fn verify(hash: &str, password: &str) -> bool {
+    if is_bcrypt(hash) {
+        return bcrypt::verify(password, hash).unwrap_or(false);
+    }
+    let Ok(parsed) = PasswordHash::new(hash) else { return false };
+    Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok()
+}
+
+fn basic_credentials<B>(req: &Request<B>) -> Option<(String, String)> {
+    let value = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, encoded) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+#[async_trait]
+impl Filterable for BasicAuth {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        let user = match self.authenticate(&req, Instant::now()).await {
+            Ok(user) => user,
+            Err(StatusCode::FORBIDDEN) => {
+                let response = Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .body(single_chunk_response_body("Forbidden"))
+                    .unwrap();
+                return Err(response);
+            }
+            Err(_) => return Err(self.challenge()),
+        };
+        admit(&mut req, user);
+        Ok(req)
+    }
+}
+
+/// The upstream gets the user, never the password.
+fn admit<B>(req: &mut Request<B>, user: String) {
+    req.headers_mut().remove(AUTHORIZATION);
+    req.extensions_mut().insert(Principal(user));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HTPASSWD: &str = "# test users
+alice:$2b$04$C9Q82NH2PjG7SxwGaQhCcOEVT8VkXBV3lEom5YMXLzO0vTlwXzE9.
+bob:$argon2id$v=19$m=19456,t=2,p=1$Zml4ZWQtdGVzdC1zYWx0IQ$I/sFIO4XFu32l1CUih4h0+GBBrcOxfm3E/4kV/Vj8k0
+";
+
+    fn filter(allowed: &[&str]) -> BasicAuth {
+        let path = std::env::temp_dir().join(format!("htpasswd-{}", std::process::id()));
+        std::fs::write(&path, HTPASSWD).unwrap();
+        let allowed = allowed.iter().map(|user| user.to_string()).collect();
+        BasicAuth::new(path.to_str().unwrap(), "test".to_string(), allowed).unwrap()
+    }
+
+    fn request(credentials: &str) -> Request<()> {
+        let authorization = format!("Basic {}", STANDARD.encode(credentials));
+        Request::get("/").header(AUTHORIZATION, authorization).body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn accepts_bcrypt_and_argon2_users_without_passing_credentials_on() {
+        let filter = filter(&[]);
+        let now = Instant::now();
+        let mut req = request("alice:alice-password");
+        let user = filter.authenticate(&req, now).await.unwrap();
+        admit(&mut req, user);
+        assert!(req.headers().get(AUTHORIZATION).is_none());
+        assert_eq!(req.extensions().get::<Principal>().unwrap().0, "alice");
+
+        assert_eq!(filter.authenticate(&request("bob:bob-password"), now).await.unwrap(), "bob");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_users_wrong_passwords_and_users_not_allowed() {
+        let filter = filter(&["bob"]);
+        let now = Instant::now();
+        let unauthorized = Err(StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            filter.authenticate(&Request::get("/").body(()).unwrap(), now).await,
+            unauthorized
+        );
+        assert_eq!(filter.authenticate(&request("alice:guess"), now).await, unauthorized);
+        assert_eq!(filter.authenticate(&request("carol:alice-password"), now).await, unauthorized);
+        let forbidden = Err(StatusCode::FORBIDDEN);
+        assert_eq!(filter.authenticate(&request("alice:alice-password"), now).await, forbidden);
+    }
+
+    #[tokio::test]
+    async fn trusts_verified_credentials_for_the_ttl() {
+        let filter = filter(&[]);
+        let start = Instant::now();
+        assert!(filter.check_at("alice", "alice-password", start).await);
+
+        // Same cache, but alice's hash no longer matches the old password
+        let mut users = (*filter.users).clone();
+        users.insert("alice".to_string(), DUMMY_HASH.to_string());
+        let changed = BasicAuth { users: Arc::new(users), ..filter.clone() };
+        assert!(changed.check_at("alice", "alice-password", start + VERIFIED_TTL / 2).await);
+        assert!(!changed.check_at("alice", "alice-password", start + VERIFIED_TTL).await);
+        assert!(!changed.check_at("alice", "other-password", start).await);
+    }
+}
@@ -0,0 +1,591 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use http::{Response, StatusCode};
+use hyper::{body::Incoming, Request};
+use tokio::sync::oneshot;
+
+use super::{Filterable, FilteredResult};
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody, CountingBody};
+use crate::gateway::errors::GatewayError;
+
+/// Limiters by group, shared by every route naming the group and kept across
+/// hot reloads so requests already in flight still count against the limit.
+static LIMITERS: LazyLock<Mutex<HashMap<String, Weak<Limiter>>>> = LazyLock::new(Default::default);
+
+/// Caps the requests a route has in flight to its upstream. The slot is taken
+/// here and released once the response body has been sent.
+#[derive(Clone, Debug)]
+pub struct ConcurrencyLimit {
+    pub group: String,
+    pub settings: LimitSettings,
+    pub limiter: Arc<Limiter>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LimitSettings {
+    pub max_concurrent: u32,
+    pub max_queue: usize,
+    pub queue_timeout: Duration,
+    pub algorithm: LimitAlgorithm,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LimitAlgorithm {
+    Fixed,
+    Aimd { min_limit: u32, max_limit: u32, latency_threshold: Duration, backoff_ratio: f64 },
+    Gradient { min_limit: u32, max_limit: u32, tolerance: f64, smoothing: f64 },
+}
+
+impl ConcurrencyLimit {
+    pub fn new(group: &str, settings: LimitSettings) -> Result<Self, GatewayError> {
+        let invalid = |reason: &str| {
+            GatewayError::InvalidConfig(format!("ConcurrencyLimit '{}': {}", group, reason))
+        };
+        if settings.max_concurrent == 0 {
+            return Err(invalid("max_concurrent must be positive"));
+        }
+        match settings.algorithm {
+            LimitAlgorithm::Fixed => {}
+            LimitAlgorithm::Aimd { min_limit, max_limit, backoff_ratio, .. } => {
+                check_bounds(min_limit, max_limit, settings.max_concurrent).map_err(invalid)?;
+                if !(0.0..1.0).contains(&backoff_ratio) {
+                    return Err(invalid("backoff_ratio must be below 1"));
+                }
+            }
+            LimitAlgorithm::Gradient { min_limit, max_limit, tolerance, smoothing } => {
+                check_bounds(min_limit, max_limit, settings.max_concurrent).map_err(invalid)?;
+                if tolerance < 1.0 || !(smoothing > 0.0 && smoothing <= 1.0) {
+                    return Err(invalid("tolerance must be at least 1 and smoothing in (0, 1]"));
+                }
+            }
+        }
+
+        let mut limiters = LIMITERS.lock().unwrap();
+        limiters.retain(|_, limiter| limiter.strong_count() > 0);
+        // An existing group only takes the settings in `configure_groups`, once
+        // the whole config has been accepted
+        let limiter = match limiters.get(group).and_then(Weak::upgrade) {
+            Some(limiter) => limiter,
+            None => {
+                let limiter = Arc::new(Limiter::new(settings.clone()));
+                limiters.insert(group.to_string(), Arc::downgrade(&limiter));
+                limiter
+            }
+        };
+        Ok(Self { group: group.to_string(), settings, limiter })
+    }
+}
+
+/// Checks that routes sharing a group agree on its settings, then applies
+/// them to the group's limiter.
+pub fn configure_groups<'a>(
+    limits: impl Iterator<Item = &'a ConcurrencyLimit>,
+) -> Result<(), GatewayError> {
+    let mut groups: HashMap<&str, &ConcurrencyLimit> = HashMap::new();
+    for limit in limits {
+        let first = *groups.entry(&limit.group).or_insert(limit);
+        if first.settings != limit.settings {
+            return Err(GatewayError::InvalidConfig(format!(
+                "ConcurrencyLimit group '{}' is configured with different settings",
+                limit.group
+            )));
+        }
+    }
+    for limit in groups.into_values() {
+        limit.limiter.configure(limit.settings.clone());
+    }
+    Ok(())
+}
+
+/// Usage of a limiter group, for metrics.
+#[derive(Debug)]
+pub struct LimiterStats {
+    pub limit: u64,
+    pub in_flight: u64,
+    pub queued: u64,
+    pub rejected: u64,
+}
+
+/// Every limiter group still in use, sorted by name.
+pub fn limiter_stats() -> Vec<(String, LimiterStats)> {
+    let limiters = LIMITERS.lock().unwrap();
+    let mut stats: Vec<_> = limiters
+        .iter()
+        .filter_map(|(group, limiter)| Some((group.clone(), limiter.upgrade()?.stats())))
+        .collect();
+    stats.sort_by(|(a, _), (b, _)| a.cmp(b));
+    stats
+}
+
+fn check_bounds(min_limit: u32, max_limit: u32, initial: u32) -> Result<(), &'static str> {
+    if min_limit == 0 || min_limit > initial || initial > max_limit {
+        return Err("limits must satisfy 0 < min_limit <= max_concurrent <= max_limit");
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl Filterable for ConcurrencyLimit {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        let Some(permit) = self.limiter.acquire().await else {
+            self.limiter.rejected.fetch_add(1, Ordering::Relaxed);
+            let response = Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(single_chunk_response_body(
+                    "Service Unavailable: too many requests in flight",
+                ))
+                .unwrap();
+            return Err(response);
+        };
+        let permits = req.extensions_mut().get_or_insert_default::<ConcurrencyPermits>();
+        permits.0.lock().unwrap().push(permit);
+        Ok(req)
+    }
+}
+
+/// Slots a request holds; dropping the request frees them without feeding
+/// the adaptive limit.
+#[derive(Clone, Debug, Default)]
+pub struct ConcurrencyPermits(Arc<Mutex<Vec<ConcurrencyPermit>>>);
+
+impl ConcurrencyPermits {
+    /// Keeps the slots until the upstream's response body has been sent or
+    /// dropped, so a slow download still counts against the limit.
+    pub fn hold_until_sent(self, response: Response<BoxBody>) -> Response<BoxBody> {
+        let status = response.status();
+        response.map(|body| -> BoxBody {
+            Box::pin(CountingBody::new(body, move |_| self.complete(status)))
+        })
+    }
+
+    /// Frees the slots, reporting the upstream latency and whether the call
+    /// succeeded.
+    fn complete(&self, status: StatusCode) {
+        let succeeded = !matches!(
+            status,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        );
+        for permit in self.0.lock().unwrap().drain(..) {
+            permit.finish(succeeded);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ConcurrencyPermit {
+    limiter: Arc<Limiter>,
+    started: Instant,
+    succeeded: Option<bool>,
+    /// Whether the slot counts towards `in_flight`. Not the case for one a
+    /// waiter gave up on before it was handed over.
+    counted: bool,
+}
+
+impl ConcurrencyPermit {
+    fn new(limiter: &Arc<Limiter>) -> Self {
+        Self { limiter: limiter.clone(), started: Instant::now(), succeeded: None, counted: true }
+    }
+}
+
+impl ConcurrencyPermit {
+    fn finish(mut self, succeeded: bool) {
+        self.succeeded = Some(succeeded);
+    }
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        if !self.counted {
+            return;
+        }
+        let sample = self.succeeded.map(|succeeded| (self.started.elapsed(), succeeded));
+        self.limiter.release(sample);
+    }
+}
+
+#[derive(Debug)]
+pub struct Limiter {
+    state: Mutex<LimiterState>,
+    /// Requests turned away because the queue was full or they waited too long.
+    pub rejected: AtomicU64,
+}
+
+#[derive(Debug)]
+struct LimiterState {
+    settings: LimitSettings,
+    limit: f64,
+    in_flight: usize,
+    queue: VecDeque<Waiter>,
+    next_waiter: u64,
+    /// Long-term average latency in seconds, for the gradient algorithm.
+    long_rtt: Option<f64>,
+}
+
+/// A queued request. The slot is handed over as a permit, so a request that
+/// goes away after it was woken still frees its slot.
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    wake: oneshot::Sender<ConcurrencyPermit>,
+}
+
+impl Limiter {
+    fn new(settings: LimitSettings) -> Self {
+        let state = LimiterState {
+            limit: settings.max_concurrent as f64,
+            settings,
+            in_flight: 0,
+            queue: VecDeque::new(),
+            next_waiter: 0,
+            long_rtt: None,
+        };
+        Self { state: Mutex::new(state), rejected: AtomicU64::new(0) }
+    }
+
+    /// Applies reloaded settings. An adaptive limit keeps what it has learned
+    /// as long as it fits the new bounds.
+    fn configure(self: &Arc<Self>, settings: LimitSettings) {
+        let mut state = self.state.lock().unwrap();
+        state.limit = match settings.algorithm {
+            LimitAlgorithm::Fixed => settings.max_concurrent as f64,
+            LimitAlgorithm::Aimd { min_limit, max_limit, .. }
+            | LimitAlgorithm::Gradient { min_limit, max_limit, .. } => {
+                match state.settings.algorithm {
+                    LimitAlgorithm::Fixed => settings.max_concurrent as f64,
+                    _ => state.limit.clamp(min_limit as f64, max_limit as f64),
+                }
+            }
+        };
+        state.settings = settings;
+        state.wake_waiters(self);
+    }
+
+    /// Takes a slot, waiting in the queue if allowed. `None` means rejected.
+    async fn acquire(self: &Arc<Self>) -> Option<ConcurrencyPermit> {
+        let (id, mut woken, queue_timeout) = {
+            let mut state = self.state.lock().unwrap();
+            if state.in_flight < state.current_limit() {
+                state.in_flight += 1;
+                return Some(ConcurrencyPermit::new(self));
+            }
+            if state.queue.len() >= state.settings.max_queue {
+                return None;
+            }
+            let (wake, woken) = oneshot::channel();
+            let id = state.next_waiter;
+            state.next_waiter += 1;
+            state.queue.push_back(Waiter { id, wake });
+            (id, woken, state.settings.queue_timeout)
+        };
+
+        if let Ok(Ok(permit)) = tokio::time::timeout(queue_timeout, &mut woken).await {
+            return Some(permit);
+        }
+        let mut state = self.state.lock().unwrap();
+        // A slot handed over just as the wait timed out is still ours
+        if let Ok(permit) = woken.try_recv() {
+            return Some(permit);
+        }
+        state.queue.retain(|waiter| waiter.id != id);
+        None
+    }
+
+    fn stats(&self) -> LimiterStats {
+        let state = self.state.lock().unwrap();
+        LimiterStats {
+            limit: state.current_limit() as u64,
+            in_flight: state.in_flight as u64,
+            queued: state.queue.len() as u64,
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    fn release(self: &Arc<Self>, sample: Option<(Duration, bool)>) {
+        let mut state = self.state.lock().unwrap();
+        if let Some((latency, succeeded)) = sample {
+            state.adapt(latency, succeeded);
+        }
+        state.in_flight -= 1;
+        state.wake_waiters(self);
+    }
+}
+
+impl LimiterState {
+    fn current_limit(&self) -> usize {
+        (self.limit as usize).max(1)
+    }
+
+    /// Hands free slots to queued requests, skipping ones that gave up.
+    fn wake_waiters(&mut self, limiter: &Arc<Limiter>) {
+        while self.in_flight < self.current_limit() {
+            let Some(waiter) = self.queue.pop_front() else { break };
+            self.in_flight += 1;
+            if let Err(mut permit) = waiter.wake.send(ConcurrencyPermit::new(limiter)) {
+                // Releasing it would take the lock held here
+                permit.counted = false;
+                self.in_flight -= 1;
+            }
+        }
+    }
+
+    fn adapt(&mut self, latency: Duration, succeeded: bool) {
+        // Only grow when the limit is actually being used
+        let saturated = self.in_flight * 2 >= self.current_limit();
+        match self.settings.algorithm {
+            LimitAlgorithm::Fixed => {}
+            LimitAlgorithm::Aimd { min_limit, max_limit, latency_threshold, backoff_ratio } => {
+                if !succeeded || latency > latency_threshold {
+                    self.limit = (self.limit * backoff_ratio).floor().max(min_limit as f64);
+                } else if saturated {
+                    self.limit = (self.limit + 1.0).min(max_limit as f64);
+                }
+            }
+            LimitAlgorithm::Gradient { min_limit, max_limit, tolerance, smoothing } => {
+                let rtt = latency.as_secs_f64().max(1e-6);
+                let long_rtt = match self.long_rtt {
+                    // Decay towards the current latency when it drops a lot, so a
+                    // slow period does not keep the limit high afterwards
+                    Some(long_rtt) if long_rtt / rtt > 2.0 => long_rtt * 0.95,
+                    Some(long_rtt) => long_rtt + (rtt - long_rtt) / 600.0,
+                    None => rtt,
+                };
+                self.long_rtt = Some(long_rtt);
+                let gradient =
+                    if succeeded { (tolerance * long_rtt / rtt).clamp(0.5, 1.0) } else { 0.5 };
+                if gradient >= 1.0 && !saturated {
+                    return;
+                }
+                let target = self.limit * gradient + self.limit.sqrt();
+                let limit = self.limit * (1.0 - smoothing) + target * smoothing;
+                self.limit = limit.clamp(min_limit as f64, max_limit as f64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::config_loader::build_routes;
+    use crate::gateway::filters::Filter;
+    use crate::gateway::route::Route;
+
+    fn limiter(max_concurrent: u32, max_queue: usize, algorithm: LimitAlgorithm) -> Arc<Limiter> {
+        Arc::new(Limiter::new(LimitSettings {
+            max_concurrent,
+            max_queue,
+            queue_timeout: Duration::from_secs(5),
+            algorithm,
+        }))
+    }
+
+    #[tokio::test]
+    async fn queues_until_a_slot_is_free() {
+        let limiter = limiter(1, 1, LimitAlgorithm::Fixed);
+        let first = limiter.acquire().await.unwrap();
+        let queued = tokio::spawn({
+            let limiter = limiter.clone();
+            async move { limiter.acquire().await.is_some() }
+        });
+        while limiter.stats().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        // The queue is full
+        assert!(limiter.acquire().await.is_none());
+
+        drop(first);
+        assert!(queued.await.unwrap());
+        let stats = limiter.stats();
+        assert_eq!((stats.in_flight, stats.queued), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_queue_timeout() {
+        let limiter = limiter(1, 1, LimitAlgorithm::Fixed);
+        limiter.state.lock().unwrap().settings.queue_timeout = Duration::from_millis(20);
+        let _held = limiter.acquire().await.unwrap();
+        let started = Instant::now();
+        assert!(limiter.acquire().await.is_none());
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(limiter.stats().queued, 0);
+    }
+
+    #[tokio::test]
+    async fn waiter_dropped_after_hand_over_frees_its_slot() {
+        let limiter = limiter(1, 1, LimitAlgorithm::Fixed);
+        let first = limiter.acquire().await.unwrap();
+        let mut waiting = Box::pin(limiter.acquire());
+        // Polls once, which queues it
+        assert!(tokio::time::timeout(Duration::ZERO, &mut waiting).await.is_err());
+
+        drop(first);
+        assert_eq!(limiter.stats().in_flight, 1);
+        drop(waiting);
+        assert_eq!(limiter.stats().in_flight, 0);
+        assert!(limiter.acquire().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn waiter_dropped_before_hand_over_is_skipped() {
+        let limiter = limiter(1, 2, LimitAlgorithm::Fixed);
+        let first = limiter.acquire().await.unwrap();
+        let mut gone = Box::pin(limiter.acquire());
+        assert!(tokio::time::timeout(Duration::ZERO, &mut gone).await.is_err());
+        let mut next = Box::pin(limiter.acquire());
+        assert!(tokio::time::timeout(Duration::ZERO, &mut next).await.is_err());
+        drop(gone);
+
+        drop(first);
+        let permit = next.await.unwrap();
+        assert_eq!(limiter.stats().in_flight, 1);
+        drop(permit);
+        assert_eq!(limiter.stats().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn aimd_grows_when_saturated_and_backs_off() {
+        let algorithm = LimitAlgorithm::Aimd {
+            min_limit: 1,
+            max_limit: 10,
+            latency_threshold: Duration::from_secs(1),
+            backoff_ratio: 0.5,
+        };
+        let limiter = limiter(4, 0, algorithm);
+        let first = limiter.acquire().await.unwrap();
+        let _second = limiter.acquire().await.unwrap();
+        first.finish(true);
+        assert_eq!(limiter.stats().limit, 5);
+
+        limiter.acquire().await.unwrap().finish(false);
+        assert_eq!(limiter.stats().limit, 2);
+
+        // Only successful calls under the threshold grow the limit
+        let mut state = limiter.state.lock().unwrap();
+        state.in_flight = 2;
+        state.adapt(Duration::from_secs(2), true);
+        assert_eq!(state.current_limit(), 1);
+        state.adapt(Duration::ZERO, false);
+        assert_eq!(state.current_limit(), 1);
+    }
+
+    #[test]
+    fn gradient_follows_latency() {
+        let algorithm = LimitAlgorithm::Gradient {
+            min_limit: 2,
+            max_limit: 12,
+            tolerance: 2.0,
+            smoothing: 1.0,
+        };
+        let limiter = limiter(10, 0, algorithm);
+        let mut state = limiter.state.lock().unwrap();
+        state.in_flight = 10;
+        state.adapt(Duration::from_millis(100), true);
+        assert_eq!(state.current_limit(), 12);
+
+        // Latency well above the long-term average halves it
+        state.adapt(Duration::from_secs(1), true);
+        assert_eq!(state.current_limit(), 9);
+        state.adapt(Duration::from_millis(100), false);
+        assert!(state.current_limit() < 9);
+        // Failures settle where halving is offset by the sqrt(limit) headroom
+        for _ in 0..20 {
+            state.adapt(Duration::from_millis(100), false);
+        }
+        assert_eq!(state.current_limit(), 4);
+
+        state.limit = 12.0;
+        state.settings.algorithm = LimitAlgorithm::Gradient {
+            min_limit: 8,
+            max_limit: 12,
+            tolerance: 2.0,
+            smoothing: 1.0,
+        };
+        state.adapt(Duration::from_millis(100), false);
+        state.adapt(Duration::from_millis(100), false);
+        assert_eq!(state.current_limit(), 8);
+    }
+
+    #[tokio::test]
+    async fn holds_slots_until_the_response_body_is_gone() {
+        use http_body_util::BodyExt;
+
+        let algorithm = LimitAlgorithm::Aimd {
+            min_limit: 1,
+            max_limit: 10,
+            latency_threshold: Duration::from_secs(1),
+            backoff_ratio: 0.5,
+        };
+        let limiter = limiter(4, 0, algorithm);
+        let respond = |permit, status| {
+            let permits = ConcurrencyPermits::default();
+            permits.0.lock().unwrap().push(permit);
+            let response = Response::builder()
+                .status(status)
+                .body(single_chunk_response_body("body"))
+                .unwrap();
+            permits.hold_until_sent(response)
+        };
+
+        let response = respond(limiter.acquire().await.unwrap(), StatusCode::OK);
+        assert_eq!(limiter.stats().in_flight, 1);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(body, "body");
+        assert_eq!(limiter.stats().in_flight, 0);
+
+        // A body never read still frees the slot, reporting the upstream's status
+        drop(respond(limiter.acquire().await.unwrap(), StatusCode::SERVICE_UNAVAILABLE));
+        let stats = limiter.stats();
+        assert_eq!((stats.in_flight, stats.limit), (0, 2));
+    }
+
+    fn route(id: &str, group: &str, max_concurrent: u32) -> String {
+        format!(
+            "  - {{id: {}, destination: http://localhost:9000, predicates: [], \
+             filters: [{{type: ConcurrencyLimit, group: {}, max_concurrent: {}}}]}}",
+            id, group, max_concurrent
+        )
+    }
+
+    fn build(routes: &[String]) -> Result<Vec<Route>, GatewayError> {
+        let config = format!("routes:\n{}", routes.join("\n"));
+        build_routes(serde_yaml::from_str(&config).unwrap())
+    }
+
+    fn group_limit(routes: &[Route]) -> &ConcurrencyLimit {
+        routes[0]
+            .filters
+            .iter()
+            .find_map(|filter| match filter {
+                Filter::ConcurrencyLimit(limit) => Some(limit),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn shares_a_group_only_with_matching_settings() {
+        let routes = build(&[route("a", "shared", 2), route("b", "shared", 2)]).unwrap();
+        assert!(Arc::ptr_eq(&group_limit(&routes).limiter, &group_limit(&routes[1..]).limiter));
+
+        let err = build(&[route("a", "clashing", 2), route("b", "clashing", 3)]).unwrap_err();
+        assert!(err.to_string().contains("group 'clashing'"), "{}", err);
+    }
+
+    #[test]
+    fn applies_reloaded_settings_only_once_the_config_is_accepted() {
+        let routes = build(&[route("a", "reloaded", 2)]).unwrap();
+        let limiter = group_limit(&routes).limiter.clone();
+        assert_eq!(limiter.stats().limit, 2);
+
+        assert!(build(&[route("a", "reloaded", 5), route("b", "reloaded", 6)]).is_err());
+        assert_eq!(limiter.stats().limit, 2);
+
+        let reloaded = build(&[route("a", "reloaded", 5)]).unwrap();
+        assert!(Arc::ptr_eq(&group_limit(&reloaded).limiter, &limiter));
+        assert_eq!(limiter.stats().limit, 5);
+    }
+}
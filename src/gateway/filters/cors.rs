@@ -0,0 +1,323 @@
+use async_trait::async_trait;
+use http::header::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+};
+use http::{HeaderName, HeaderValue, Method, Response, StatusCode};
+use hyper::{body::Incoming, Request};
+use regex::Regex;
+
+use super::{header_name, Filterable, FilteredResult};
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::errors::GatewayError;
+
+/// Cross-origin resource sharing for browser clients. Preflights are answered
+/// by the gateway and actual responses get the `Access-Control-*` headers,
+/// replacing whatever the upstream sent.
+#[derive(Clone, Debug)]
+pub struct Cors {
+    pub origins: Vec<OriginPattern>,
+    /// `None` allows any method or header.
+    pub methods: Option<Vec<Method>>,
+    pub headers: Option<Vec<HeaderName>>,
+    pub exposed_headers: Option<HeaderValue>,
+    pub credentials: bool,
+    pub max_age: HeaderValue,
+}
+
+#[derive(Clone, Debug)]
+pub enum OriginPattern {
+    Any,
+    /// Compared case-insensitively, e.g. `https://app.example.com`.
+    Exact(String),
+    /// From a wildcard such as `https://*.example.com` or a configured regex.
+    Regex(Regex),
+}
+
+impl OriginPattern {
+    /// `*` allows every origin and other `*` match within the origin, so
+    /// `https://*.example.com` matches any subdomain but no other site.
+    pub fn wildcard(origin: &str) -> Result<Self, GatewayError> {
+        if origin == "*" {
+            return Ok(OriginPattern::Any);
+        }
+        if !origin.contains('*') {
+            return Ok(OriginPattern::Exact(origin.trim_end_matches('/').to_string()));
+        }
+        let pattern = origin.split('*').map(regex::escape).collect::<Vec<_>>().join("[^/]*");
+        OriginPattern::regex(&pattern)
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, GatewayError> {
+        let regex = Regex::new(&format!("(?i)^(?:{})$", pattern)).map_err(|err| {
+            GatewayError::InvalidConfig(format!("invalid origin pattern '{}': {}", pattern, err))
+        })?;
+        Ok(OriginPattern::Regex(regex))
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        match self {
+            OriginPattern::Any => true,
+            OriginPattern::Exact(allowed) => allowed.eq_ignore_ascii_case(origin),
+            OriginPattern::Regex(regex) => regex.is_match(origin),
+        }
+    }
+}
+
+impl Cors {
+    pub fn new(
+        origins: Vec<OriginPattern>,
+        methods: &[String],
+        headers: &[String],
+        exposed_headers: &[String],
+        credentials: bool,
+        max_age_secs: u64,
+    ) -> Result<Self, GatewayError> {
+        let any = |values: &[String]| values.iter().any(|value| value == "*");
+        let methods = if any(methods) {
+            None
+        } else {
+            let parse = |method: &String| {
+                method.to_ascii_uppercase().parse::<Method>().map_err(|_| {
+                    GatewayError::InvalidConfig(format!("invalid CORS method '{}'", method))
+                })
+            };
+            Some(methods.iter().map(parse).collect::<Result<_, _>>()?)
+        };
+        let headers = if any(headers) {
+            None
+        } else {
+            Some(headers.iter().map(|name| header_name(name)).collect::<Result<_, _>>()?)
+        };
+        let exposed_headers = if exposed_headers.is_empty() {
+            None
+        } else {
+            let exposed = exposed_headers.join(", ");
+            let value = HeaderValue::from_str(&exposed).map_err(|_| {
+                GatewayError::InvalidConfig(format!("invalid exposed headers '{}'", exposed))
+            })?;
+            Some(value)
+        };
+        Ok(Self {
+            origins,
+            methods,
+            headers,
+            exposed_headers,
+            credentials,
+            max_age: HeaderValue::from(max_age_secs),
+        })
+    }
+
+    /// The `Access-Control-Allow-Origin` value for an allowed origin. A
+    /// wildcard is only sent back as `*` when no credentials are involved.
+    fn allowed_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        let text = origin.to_str().ok()?;
+        let pattern = self.origins.iter().find(|pattern| pattern.matches(text))?;
+        if matches!(pattern, OriginPattern::Any) && !self.credentials {
+            return Some(HeaderValue::from_static("*"));
+        }
+        Some(origin.clone())
+    }
+
+    fn allows_method(&self, method: &Method) -> bool {
+        self.methods.as_ref().is_none_or(|methods| methods.contains(method))
+    }
+
+    /// Answers a preflight: 200 with the grants, or 403 when the origin,
+    /// method or any requested header is not allowed.
+    pub fn preflight<B>(&self, req: &Request<B>) -> Response<BoxBody> {
+        let origin = req.headers().get(ORIGIN).and_then(|origin| self.allowed_origin(origin));
+        let method = req
+            .headers()
+            .get(ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|method| Method::from_bytes(method.as_bytes()).ok())
+            .filter(|method| self.allows_method(method));
+        let requested_headers = requested_headers(req);
+        let headers_allowed = match &self.headers {
+            None => true,
+            Some(allowed) => requested_headers.iter().all(|name| {
+                allowed.iter().any(|allowed| allowed.as_str().eq_ignore_ascii_case(name))
+            }),
+        };
+        let (Some(origin), Some(method), true) = (origin, method, headers_allowed) else {
+            return forbidden();
+        };
+
+        let mut response = Response::builder()
+            .status(StatusCode::OK)
+            .header(ACCESS_CONTROL_ALLOW_ORIGIN, origin)
+            .header(VARY, "Origin, Access-Control-Request-Method, Access-Control-Request-Headers")
+            .header(ACCESS_CONTROL_MAX_AGE, self.max_age.clone());
+        let methods = match &self.methods {
+            None => method.to_string(),
+            Some(methods) => methods.iter().map(Method::as_str).collect::<Vec<_>>().join(", "),
+        };
+        response = response.header(ACCESS_CONTROL_ALLOW_METHODS, methods);
+        if !requested_headers.is_empty() {
+            response = response.header(ACCESS_CONTROL_ALLOW_HEADERS, requested_headers.join(", "));
+        }
+        if self.credentials {
+            response = response.header(ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+        response.body(single_chunk_response_body("")).unwrap()
+    }
+}
+
+/// Whether the request is a CORS preflight rather than a plain `OPTIONS`.
+pub fn is_preflight<B>(req: &Request<B>) -> bool {
+    req.method() == Method::OPTIONS
+        && req.headers().contains_key(ORIGIN)
+        && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+fn requested_headers<B>(req: &Request<B>) -> Vec<String> {
+    req.headers()
+        .get_all(ACCESS_CONTROL_REQUEST_HEADERS)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|name| name.trim().to_ascii_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn forbidden() -> Response<BoxBody> {
+    Response::builder()
+        .status(StatusCode::FORBIDDEN)
+        .body(single_chunk_response_body("Invalid CORS request"))
+        .unwrap()
+}
+
+#[async_trait]
+impl Filterable for Cors {
+    /// Rejects cross-origin requests from origins that are not allowed.
+    /// Preflights never get here; the responder answers them first.
+    async fn apply(&self, req: Request<Incoming>) -> FilteredResult {
+        match req.headers().get(ORIGIN) {
+            Some(origin) if self.allowed_origin(origin).is_none() => Err(forbidden()),
+            _ => Ok(req),
+        }
+    }
+
+    fn apply_response(&self, req: &Request<()>, response: &mut Response<BoxBody>) {
+        let Some(origin) = req.headers().get(ORIGIN).and_then(|o| self.allowed_origin(o)) else {
+            return;
+        };
+        let headers = response.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+        headers.append(VARY, HeaderValue::from_static("Origin"));
+        headers.remove(ACCESS_CONTROL_ALLOW_CREDENTIALS);
+        if self.credentials {
+            headers.insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, HeaderValue::from_static("true"));
+        }
+        headers.remove(ACCESS_CONTROL_EXPOSE_HEADERS);
+        if let Some(exposed) = &self.exposed_headers {
+            headers.insert(ACCESS_CONTROL_EXPOSE_HEADERS, exposed.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cors(origins: &[&str], credentials: bool) -> Cors {
+        let origins = origins.iter().map(|origin| OriginPattern::wildcard(origin).unwrap());
+        let strings =
+            |values: &[&str]| values.iter().map(|value| value.to_string()).collect::<Vec<_>>();
+        Cors::new(
+            origins.collect(),
+            &strings(&["get", "POST"]),
+            &strings(&["Content-Type", "X-Api-Key"]),
+            &strings(&["X-Request-Id"]),
+            credentials,
+            600,
+        )
+        .unwrap()
+    }
+
+    fn preflight(origin: &str, method: &str, headers: Option<&str>) -> Request<()> {
+        let mut req = Request::options("/api")
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, method);
+        if let Some(headers) = headers {
+            req = req.header(ACCESS_CONTROL_REQUEST_HEADERS, headers);
+        }
+        req.body(()).unwrap()
+    }
+
+    #[test]
+    fn wildcard_origins_stay_within_the_site() {
+        let pattern = OriginPattern::wildcard("https://*.example.com").unwrap();
+        assert!(pattern.matches("https://app.example.com"));
+        assert!(pattern.matches("HTTPS://App.Example.COM"));
+        assert!(pattern.matches("https://a.b.example.com"));
+        assert!(!pattern.matches("https://example.com"));
+        assert!(!pattern.matches("https://appxexample.com"));
+        assert!(!pattern.matches("https://app.example.com.evil.com"));
+        assert!(!pattern.matches("https://evil.com/.example.com"));
+        assert!(!pattern.matches("http://app.example.com"));
+
+        let exact = OriginPattern::wildcard("https://app.example.com/").unwrap();
+        assert!(exact.matches("https://APP.example.com"));
+        assert!(!exact.matches("https://app.example.com.evil.com"));
+        assert!(OriginPattern::regex("https://(").is_err());
+    }
+
+    #[test]
+    fn answers_allowed_preflights() {
+        let filter = cors(&["https://*.example.com"], true);
+        let response =
+            filter.preflight(&preflight("https://app.example.com", "POST", Some("x-api-key")));
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, POST");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "x-api-key");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+
+        let any = cors(&["*"], false);
+        let response = any.preflight(&preflight("https://elsewhere.org", "GET", None));
+        assert_eq!(response.headers()[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+    }
+
+    #[test]
+    fn refuses_preflights_that_are_not_allowed() {
+        let filter = cors(&["https://*.example.com"], false);
+        let refused = [
+            preflight("https://evil.com", "GET", None),
+            preflight("https://app.example.com", "DELETE", None),
+            preflight("https://app.example.com", "not a method", None),
+            preflight("https://app.example.com", "GET", Some("content-type, x-secret")),
+        ];
+        for req in refused {
+            let response = filter.preflight(&req);
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+        }
+        assert!(is_preflight(&preflight("https://app.example.com", "GET", None)));
+        assert!(!is_preflight(&Request::options("/api").body(()).unwrap()));
+    }
+
+    #[test]
+    fn replaces_upstream_cors_headers() {
+        let filter = cors(&["https://app.example.com"], false);
+        let mut response = Response::new(single_chunk_response_body(""));
+        response.headers_mut().insert(ACCESS_CONTROL_ALLOW_CREDENTIALS, "true".parse().unwrap());
+        response.headers_mut().insert(ACCESS_CONTROL_EXPOSE_HEADERS, "x-internal".parse().unwrap());
+        let req = Request::get("/api").header(ORIGIN, "https://app.example.com").body(()).unwrap();
+        filter.apply_response(&req, &mut response);
+        let headers = response.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "https://app.example.com");
+        assert_eq!(headers[ACCESS_CONTROL_EXPOSE_HEADERS], "X-Request-Id");
+        assert_eq!(headers[VARY], "Origin");
+        assert!(!headers.contains_key(ACCESS_CONTROL_ALLOW_CREDENTIALS));
+
+        let mut untouched = Response::new(single_chunk_response_body(""));
+        let req = Request::get("/api").header(ORIGIN, "https://evil.com").body(()).unwrap();
+        filter.apply_response(&req, &mut untouched);
+        assert!(untouched.headers().is_empty());
+    }
+}
@@ -0,0 +1,424 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use http::{HeaderName, Response, StatusCode};
+use hyper::{body::Incoming, Request};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512};
+
+use super::{buffer_body, constant_time_eq, header_name, Filterable, FilteredResult};
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::template::Template;
+
+const SIGNING_VARIABLES: [&str; 3] = ["timestamp", "method", "path"];
+
+/// Verifies an HMAC over the raw request body and optionally a timestamp,
+/// e.g. GitHub's `X-Hub-Signature-256` or Stripe's `Stripe-Signature`. The
+/// body is buffered up to a limit and forwarded unchanged.
+#[derive(Clone, Debug)]
+pub struct HmacSignature {
+    pub signature: SignatureHeader,
+    pub timestamp: Option<TimestampSource>,
+    /// Largest accepted difference between the timestamp and now.
+    pub tolerance: Duration,
+    pub algorithm: HmacAlgorithm,
+    /// Text signed before and after the body; `has_body` is false when the
+    /// signing string leaves the body out.
+    pub signing_prefix: Template,
+    pub signing_suffix: Template,
+    pub has_body: bool,
+    /// Any of these may have signed the request, so secrets can be rotated.
+    pub secrets: Vec<Vec<u8>>,
+    pub max_body_bytes: usize,
+}
+
+/// Where the signature is and how it is written.
+#[derive(Clone, Debug)]
+pub struct SignatureHeader {
+    pub name: HeaderName,
+    pub format: SignatureFormat,
+    pub encoding: SignatureEncoding,
+}
+
+#[derive(Clone, Debug)]
+pub enum SignatureFormat {
+    /// The whole value after a fixed prefix such as `sha256=`.
+    Prefixed(String),
+    /// `key=value` elements like `t=1700000000,v1=5257a8...`; every element
+    /// named `signature_key` is a candidate.
+    KeyValue { signature_key: String, separator: String },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SignatureEncoding {
+    Hex,
+    Base64,
+}
+
+#[derive(Clone, Debug)]
+pub enum TimestampSource {
+    Header(HeaderName),
+    /// An element of a `KeyValue` signature header, e.g. Stripe's `t`.
+    Element(String),
+}
+
+impl TimestampSource {
+    pub fn header(name: &str) -> Result<Self, GatewayError> {
+        Ok(TimestampSource::Header(header_name(name)?))
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum HmacAlgorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl HmacAlgorithm {
+    fn sign(self, secret: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+        fn tag<M: Mac + hmac::digest::KeyInit>(secret: &[u8], parts: &[&[u8]]) -> Vec<u8> {
+            let mut mac = <M as Mac>::new_from_slice(secret).expect("HMAC takes keys of any size");
+            for part in parts {
+                mac.update(part);
+            }
+            mac.finalize().into_bytes().to_vec()
+        }
+        match self {
+            HmacAlgorithm::Sha1 => tag::<Hmac<Sha1>>(secret, parts),
+            HmacAlgorithm::Sha256 => tag::<Hmac<Sha256>>(secret, parts),
+            HmacAlgorithm::Sha512 => tag::<Hmac<Sha512>>(secret, parts),
+        }
+    }
+}
+
+impl SignatureHeader {
+    pub fn new(
+        name: &str,
+        format: SignatureFormat,
+        encoding: SignatureEncoding,
+    ) -> Result<Self, GatewayError> {
+        Ok(Self { name: header_name(name)?, format, encoding })
+    }
+
+    /// Decoded candidate signatures from the header value.
+    fn signatures(&self, value: &str) -> Vec<Vec<u8>> {
+        let encoded: Vec<&str> = match &self.format {
+            SignatureFormat::Prefixed(prefix) => {
+                value.strip_prefix(prefix.as_str()).into_iter().collect()
+            }
+            SignatureFormat::KeyValue { signature_key, separator } => elements(value, separator)
+                .filter(|(key, _)| key == signature_key)
+                .map(|(_, v)| v)
+                .collect(),
+        };
+        encoded
+            .into_iter()
+            .filter_map(|signature| match self.encoding {
+                SignatureEncoding::Hex => decode_hex(signature.trim()),
+                SignatureEncoding::Base64 => STANDARD.decode(signature.trim()).ok(),
+            })
+            .collect()
+    }
+}
+
+fn elements<'a>(value: &'a str, separator: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+    value.split(separator).filter_map(|element| {
+        let (key, value) = element.split_once('=')?;
+        Some((key.trim(), value.trim()))
+    })
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+impl HmacSignature {
+    pub fn new(
+        signature: SignatureHeader,
+        timestamp: Option<TimestampSource>,
+        tolerance: Duration,
+        algorithm: HmacAlgorithm,
+        signing_string: &str,
+        secrets: Vec<String>,
+        max_body_bytes: usize,
+    ) -> Result<Self, GatewayError> {
+        if secrets.is_empty() {
+            return Err(GatewayError::InvalidConfig("HmacSignature needs a secret".into()));
+        }
+        if matches!(timestamp, Some(TimestampSource::Element(_)))
+            && !matches!(signature.format, SignatureFormat::KeyValue { .. })
+        {
+            return Err(GatewayError::InvalidConfig(
+                "a timestamp element needs a KeyValue signature format".into(),
+            ));
+        }
+
+        let mut pieces = signing_string.split("{body}");
+        let signing_prefix = Template::parse(pieces.next().unwrap_or_default())?;
+        let suffix = pieces.next();
+        if pieces.next().is_some() {
+            return Err(GatewayError::InvalidConfig(format!(
+                "signing string '{}' names the body twice",
+                signing_string
+            )));
+        }
+        let signing_suffix = Template::parse(suffix.unwrap_or_default())?;
+        for template in [&signing_prefix, &signing_suffix] {
+            let known = |name: &str| {
+                let usable = SIGNING_VARIABLES.contains(&name)
+                    && (name != "timestamp" || timestamp.is_some());
+                usable.then(String::new)
+            };
+            template.render(known).map_err(|name| {
+                GatewayError::InvalidConfig(format!(
+                    "signing string '{}' uses unknown variable '{}'",
+                    signing_string, name
+                ))
+            })?;
+        }
+
+        Ok(Self {
+            signature,
+            timestamp,
+            tolerance,
+            algorithm,
+            signing_prefix,
+            signing_suffix,
+            has_body: suffix.is_some(),
+            secrets: secrets.into_iter().map(String::into_bytes).collect(),
+            max_body_bytes,
+        })
+    }
+
+    /// Timestamp sent with the request, if the filter expects one.
+    fn timestamp<B>(
+        &self,
+        req: &Request<B>,
+        signature: &str,
+    ) -> Result<Option<String>, &'static str> {
+        let timestamp = match &self.timestamp {
+            None => return Ok(None),
+            Some(TimestampSource::Header(name)) => {
+                req.headers().get(name).and_then(|value| value.to_str().ok())
+            }
+            Some(TimestampSource::Element(key)) => {
+                let SignatureFormat::KeyValue { separator, .. } = &self.signature.format else {
+                    return Err("missing signature timestamp");
+                };
+                elements(signature, separator).find(|(name, _)| name == key).map(|(_, v)| v)
+            }
+        };
+        let timestamp = timestamp.ok_or("missing signature timestamp")?.trim();
+        let seconds: u64 = timestamp.parse().map_err(|_| "invalid signature timestamp")?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        if Duration::from_secs(seconds).abs_diff(now) > self.tolerance {
+            return Err("signature timestamp outside tolerance");
+        }
+        Ok(Some(timestamp.to_string()))
+    }
+}
+
+/// What a request claims to be signed with, taken from its headers before
+/// the body is read.
+struct SignedRequest {
+    candidates: Vec<Vec<u8>>,
+    prefix: String,
+    suffix: String,
+}
+
+impl HmacSignature {
+    fn signed_request<B>(&self, req: &Request<B>) -> Result<SignedRequest, &'static str> {
+        let header = req.headers().get(&self.signature.name).and_then(|value| value.to_str().ok());
+        let header = header.ok_or("missing signature")?;
+        let candidates = self.signature.signatures(header);
+        if candidates.is_empty() {
+            return Err("malformed signature");
+        }
+        let timestamp = self.timestamp(req, header)?;
+
+        let lookup = |name: &str| match name {
+            "timestamp" => timestamp.clone(),
+            "method" => Some(req.method().to_string()),
+            "path" => Some(req.uri().path().to_string()),
+            _ => None,
+        };
+        let prefix = self.signing_prefix.render(lookup).unwrap_or_default();
+        let suffix = self.signing_suffix.render(lookup).unwrap_or_default();
+        Ok(SignedRequest { candidates, prefix, suffix })
+    }
+
+    /// Whether any secret signed `body` as the request claims.
+    fn is_valid(&self, signed: &SignedRequest, body: &[u8]) -> bool {
+        let parts = [signed.prefix.as_bytes(), body, signed.suffix.as_bytes()];
+        self.secrets.iter().any(|secret| {
+            let expected = self.algorithm.sign(secret, &parts);
+            signed.candidates.iter().any(|candidate| constant_time_eq(candidate, &expected))
+        })
+    }
+}
+
+#[async_trait]
+impl Filterable for HmacSignature {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        let signed = self.signed_request(&req).map_err(rejected)?;
+        let body = if self.has_body {
+            buffer_body(&mut req, self.max_body_bytes).await?
+        } else {
+            Default::default()
+        };
+        if !self.is_valid(&signed, &body) {
+            return Err(rejected("invalid signature"));
+        }
+        Ok(req)
+    }
+}
+
+fn rejected(reason: &str) -> Response<BoxBody> {
+    Response::builder()
+        .status(StatusCode::UNAUTHORIZED)
+        .body(single_chunk_response_body(format!("Unauthorized: {}", reason)))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"The quick brown fox jumps over the lazy dog";
+    /// HMAC-SHA256 of `BODY` under the key `key`.
+    const GITHUB_SIGNATURE: &str =
+        "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8";
+
+    fn github() -> HmacSignature {
+        let header = SignatureHeader::new(
+            "x-hub-signature-256",
+            SignatureFormat::Prefixed("sha256=".into()),
+            SignatureEncoding::Hex,
+        )
+        .unwrap();
+        let secrets = vec!["rotated".into(), "key".into()];
+        let tolerance = Duration::from_secs(300);
+        HmacSignature::new(header, None, tolerance, HmacAlgorithm::Sha256, "{body}", secrets, 1024)
+            .unwrap()
+    }
+
+    fn stripe() -> HmacSignature {
+        let format =
+            SignatureFormat::KeyValue { signature_key: "v1".into(), separator: ",".into() };
+        let header =
+            SignatureHeader::new("stripe-signature", format, SignatureEncoding::Hex).unwrap();
+        let timestamp = Some(TimestampSource::Element("t".into()));
+        let tolerance = Duration::from_secs(300);
+        let secrets = vec!["whsec".into()];
+        HmacSignature::new(
+            header,
+            timestamp,
+            tolerance,
+            HmacAlgorithm::Sha256,
+            "{timestamp}.{body}",
+            secrets,
+            1024,
+        )
+        .unwrap()
+    }
+
+    fn request(name: &str, value: &str) -> Request<()> {
+        Request::post("/hooks").header(name, value).body(()).unwrap()
+    }
+
+    fn verify(filter: &HmacSignature, req: &Request<()>, body: &[u8]) -> Result<(), &'static str> {
+        let signed = filter.signed_request(req)?;
+        filter.is_valid(&signed, body).then_some(()).ok_or("invalid signature")
+    }
+
+    fn stripe_header(timestamp: u64, body: &[u8]) -> String {
+        let signed = format!("{}.", timestamp);
+        let signature = HmacAlgorithm::Sha256.sign(b"whsec", &[signed.as_bytes(), body]);
+        let hex: String = signature.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("t={},v1=00ff,v1={}", timestamp, hex)
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[test]
+    fn verifies_prefixed_signatures_with_any_secret() {
+        let filter = github();
+        let req = request("x-hub-signature-256", GITHUB_SIGNATURE);
+        assert_eq!(verify(&filter, &req, BODY), Ok(()));
+        assert_eq!(verify(&filter, &req, b"tampered"), Err("invalid signature"));
+
+        let uppercase = GITHUB_SIGNATURE.to_uppercase().replace("SHA256=", "sha256=");
+        assert_eq!(verify(&filter, &request("x-hub-signature-256", &uppercase), BODY), Ok(()));
+        let unprefixed = GITHUB_SIGNATURE.trim_start_matches("sha256=");
+        let req = request("x-hub-signature-256", unprefixed);
+        assert_eq!(verify(&filter, &req, BODY), Err("malformed signature"));
+        let req = Request::post("/hooks").body(()).unwrap();
+        assert_eq!(verify(&filter, &req, BODY), Err("missing signature"));
+    }
+
+    #[test]
+    fn verifies_timestamped_signatures_within_tolerance() {
+        let filter = stripe();
+        let timestamp = now();
+        let header = stripe_header(timestamp, BODY);
+        assert_eq!(verify(&filter, &request("stripe-signature", &header), BODY), Ok(()));
+        // The timestamp is signed, so it cannot be moved
+        let moved = header.replacen(&timestamp.to_string(), &(timestamp - 60).to_string(), 1);
+        let req = request("stripe-signature", &moved);
+        assert_eq!(verify(&filter, &req, BODY), Err("invalid signature"));
+
+        for timestamp in [now() - 301, now() + 301] {
+            let req = request("stripe-signature", &stripe_header(timestamp, BODY));
+            assert_eq!(verify(&filter, &req, BODY), Err("signature timestamp outside tolerance"));
+        }
+        let undated = stripe_header(now(), BODY).replacen("t=", "x=", 1);
+        let req = request("stripe-signature", &undated);
+        assert_eq!(verify(&filter, &req, BODY), Err("missing signature timestamp"));
+        let req = request("stripe-signature", "t=soon,v1=00");
+        assert_eq!(verify(&filter, &req, BODY), Err("invalid signature timestamp"));
+    }
+
+    #[test]
+    fn rejects_invalid_configs() {
+        let header = SignatureHeader::new(
+            "x-signature",
+            SignatureFormat::Prefixed(String::new()),
+            SignatureEncoding::Base64,
+        )
+        .unwrap();
+        let new =
+            |timestamp: Option<TimestampSource>, signing_string: &str, secrets: Vec<String>| {
+                let tolerance = Duration::from_secs(300);
+                let algorithm = HmacAlgorithm::Sha1;
+                HmacSignature::new(
+                    header.clone(),
+                    timestamp,
+                    tolerance,
+                    algorithm,
+                    signing_string,
+                    secrets,
+                    1024,
+                )
+            };
+        let secret = || vec!["secret".to_string()];
+        assert!(new(None, "{body}", Vec::new()).is_err());
+        assert!(new(Some(TimestampSource::Element("t".into())), "{body}", secret()).is_err());
+        assert!(new(None, "{timestamp}.{body}", secret()).is_err());
+        assert!(new(None, "{body}{body}", secret()).is_err());
+        assert!(new(None, "{method} {path} {query}", secret()).is_err());
+        assert!(!new(None, "{method} {path}", secret()).unwrap().has_body);
+    }
+}
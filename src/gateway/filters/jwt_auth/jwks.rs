@@ -0,0 +1,348 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet, PublicKeyUse};
+use jsonwebtoken::{Algorithm, DecodingKey};
+use tokio::sync::{Mutex, RwLock};
+
+use crate::gateway::errors::GatewayError;
+use crate::gateway::http_client;
+
+/// Earliest refetch after the last one when a token names an unknown `kid`,
+/// so junk tokens cannot hammer the key server.
+const UNKNOWN_KID_REFETCH: Duration = Duration::from_secs(30);
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A verification key with what it may verify.
+#[derive(Clone)]
+pub struct JwtKey {
+    pub kid: Option<String>,
+    family: KeyFamily,
+    /// Pinned by the JWK `alg` member.
+    algorithm: Option<Algorithm>,
+    key: DecodingKey,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum KeyFamily {
+    Hmac,
+    Rsa,
+    Ec,
+    Ed,
+}
+
+impl JwtKey {
+    pub fn secret(secret: &[u8]) -> Self {
+        let key = DecodingKey::from_secret(secret);
+        Self { kid: None, family: KeyFamily::Hmac, algorithm: None, key }
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.key
+    }
+
+    /// Whether the key can check a signature made with `algorithm`.
+    pub fn fits(&self, algorithm: Algorithm) -> bool {
+        let family = match algorithm {
+            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => KeyFamily::Hmac,
+            Algorithm::RS256
+            | Algorithm::RS384
+            | Algorithm::RS512
+            | Algorithm::PS256
+            | Algorithm::PS384
+            | Algorithm::PS512 => KeyFamily::Rsa,
+            Algorithm::ES256 | Algorithm::ES384 => KeyFamily::Ec,
+            Algorithm::EdDSA => KeyFamily::Ed,
+        };
+        family == self.family && self.algorithm.is_none_or(|pinned| pinned == algorithm)
+    }
+}
+
+impl fmt::Debug for JwtKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtKey")
+            .field("kid", &self.kid)
+            .field("family", &self.family)
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
+
+/// Signature keys of a JWKS document; encryption keys and algorithms the
+/// library cannot verify are skipped.
+pub fn parse_jwks(document: &[u8]) -> Result<Vec<JwtKey>, String> {
+    let set: JwkSet = serde_json::from_slice(document).map_err(|err| err.to_string())?;
+    let mut keys = Vec::new();
+    for jwk in &set.keys {
+        if matches!(jwk.common.public_key_use, Some(PublicKeyUse::Encryption)) {
+            continue;
+        }
+        let algorithm = match jwk.common.key_algorithm.map(|alg| alg.to_string().parse()) {
+            Some(Ok(algorithm)) => Some(algorithm),
+            Some(Err(_)) => continue,
+            None => None,
+        };
+        let family = match jwk.algorithm {
+            AlgorithmParameters::OctetKey(_) => KeyFamily::Hmac,
+            AlgorithmParameters::RSA(_) => KeyFamily::Rsa,
+            AlgorithmParameters::EllipticCurve(_) => KeyFamily::Ec,
+            AlgorithmParameters::OctetKeyPair(_) => KeyFamily::Ed,
+        };
+        let key = DecodingKey::from_jwk(jwk).map_err(|err| err.to_string())?;
+        keys.push(JwtKey { kid: jwk.common.key_id.clone(), family, algorithm, key });
+    }
+    Ok(keys)
+}
+
+/// Where a filter gets its verification keys.
+#[derive(Clone, Debug)]
+pub enum KeySource {
+    Static(Arc<Vec<JwtKey>>),
+    Remote(Arc<RemoteJwks>),
+}
+
+impl KeySource {
+    pub fn jwks_file(path: &str) -> Result<Self, GatewayError> {
+        let invalid = |reason: String| {
+            GatewayError::InvalidConfig(format!("invalid JWKS file '{}': {}", path, reason))
+        };
+        let document = std::fs::read(path).map_err(|err| invalid(err.to_string()))?;
+        Ok(Self::Static(Arc::new(parse_jwks(&document).map_err(invalid)?)))
+    }
+
+    pub fn jwks_url(url: &str, refresh: Duration) -> Result<Self, GatewayError> {
+        if !url.starts_with("https://") && !url.starts_with("http://") {
+            return Err(GatewayError::InvalidConfig(format!("invalid JWKS URL '{}'", url)));
+        }
+        let remote = RemoteJwks {
+            url: url.to_string(),
+            refresh,
+            cached: RwLock::default(),
+            fetching: Mutex::default(),
+        };
+        Ok(Self::Remote(Arc::new(remote)))
+    }
+
+    /// Current keys, refetched when stale or when `kid` is not among them.
+    pub async fn keys(&self, kid: Option<&str>) -> Result<Arc<Vec<JwtKey>>, String> {
+        match self {
+            KeySource::Static(keys) => Ok(keys.clone()),
+            KeySource::Remote(remote) => remote.keys_at(kid, Instant::now()).await,
+        }
+    }
+}
+
+/// A JWKS endpoint with the keys it last served.
+pub struct RemoteJwks {
+    url: String,
+    refresh: Duration,
+    cached: RwLock<Option<CachedKeys>>,
+    /// Lets one request fetch while the others wait for its result.
+    fetching: Mutex<()>,
+}
+
+struct CachedKeys {
+    keys: Arc<Vec<JwtKey>>,
+    fetched_at: Instant,
+    refresh_at: Instant,
+}
+
+impl RemoteJwks {
+    async fn keys_at(&self, kid: Option<&str>, now: Instant) -> Result<Arc<Vec<JwtKey>>, String> {
+        if let Some(keys) = self.fresh(kid, now).await {
+            return Ok(keys);
+        }
+        let _fetching = self.fetching.lock().await;
+        // Another request may have refreshed the keys while this one waited
+        if let Some(keys) = self.fresh(kid, now).await {
+            return Ok(keys);
+        }
+
+        let fetched = match http_client::get(&self.url, FETCH_TIMEOUT).await {
+            Ok(document) => parse_jwks(&document),
+            Err(err) => Err(err),
+        };
+        let mut cached = self.cached.write().await;
+        match (fetched, cached.as_mut()) {
+            (Ok(keys), _) => {
+                let keys = Arc::new(keys);
+                let refresh_at = now + self.refresh;
+                *cached = Some(CachedKeys { keys: keys.clone(), fetched_at: now, refresh_at });
+                Ok(keys)
+            }
+            // Keep verifying with the old keys while the endpoint is down
+            (Err(err), Some(stale)) => {
+                eprintln!("JWKS refresh from {} failed, keeping cached keys: {}", self.url, err);
+                stale.fetched_at = now;
+                stale.refresh_at = now + UNKNOWN_KID_REFETCH;
+                Ok(stale.keys.clone())
+            }
+            (Err(err), None) => Err(format!("cannot fetch JWKS: {}", err)),
+        }
+    }
+
+    /// Cached keys unless they are stale, or lack `kid` and were fetched long
+    /// enough ago to look for a rotated key.
+    async fn fresh(&self, kid: Option<&str>, now: Instant) -> Option<Arc<Vec<JwtKey>>> {
+        let cached = self.cached.read().await;
+        let cached = cached.as_ref()?;
+        let unknown_kid =
+            kid.is_some_and(|kid| !cached.keys.iter().any(|key| key.kid.as_deref() == Some(kid)));
+        if now >= cached.refresh_at
+            || (unknown_kid
+                && now.saturating_duration_since(cached.fetched_at) >= UNKNOWN_KID_REFETCH)
+        {
+            return None;
+        }
+        Some(cached.keys.clone())
+    }
+}
+
+impl fmt::Debug for RemoteJwks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RemoteJwks")
+            .field("url", &self.url)
+            .field("refresh", &self.refresh)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use bytes::Bytes;
+    use http::{Response, StatusCode};
+    use http_body_util::Full;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const JWKS: &str = include_str!("testdata/jwks.json");
+    const REFRESH: Duration = Duration::from_secs(60);
+
+    /// A JWKS endpoint that counts its requests and answers 500 while `down`.
+    #[derive(Default)]
+    struct Endpoint {
+        document: std::sync::Mutex<String>,
+        down: AtomicBool,
+        hits: AtomicUsize,
+    }
+
+    async fn endpoint() -> (SocketAddr, Arc<Endpoint>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let endpoint = Arc::new(Endpoint::default());
+        *endpoint.document.lock().unwrap() = JWKS.to_string();
+        let served = endpoint.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let endpoint = served.clone();
+                let service = service_fn(move |_| {
+                    let endpoint = endpoint.clone();
+                    async move {
+                        endpoint.hits.fetch_add(1, Ordering::SeqCst);
+                        let mut response = Response::new(Full::new(Bytes::from(
+                            endpoint.document.lock().unwrap().clone(),
+                        )));
+                        if endpoint.down.load(Ordering::SeqCst) {
+                            *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                        }
+                        Ok::<_, Infallible>(response)
+                    }
+                });
+                tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), service));
+            }
+        });
+        (address, endpoint)
+    }
+
+    fn remote(address: SocketAddr) -> Arc<RemoteJwks> {
+        match KeySource::jwks_url(&format!("http://{}/jwks.json", address), REFRESH).unwrap() {
+            KeySource::Remote(remote) => remote,
+            KeySource::Static(_) => unreachable!(),
+        }
+    }
+
+    fn kids(keys: &[JwtKey]) -> Vec<&str> {
+        keys.iter().filter_map(|key| key.kid.as_deref()).collect()
+    }
+
+    #[test]
+    fn parses_signature_keys_with_their_family() {
+        let keys = parse_jwks(JWKS.as_bytes()).unwrap();
+        assert_eq!(kids(&keys), ["r1", "e1"]);
+        // r1 is pinned to RS256 by its `alg` member, e1 takes any EC algorithm
+        assert!(keys[0].fits(Algorithm::RS256));
+        assert!(!keys[0].fits(Algorithm::PS256));
+        assert!(!keys[0].fits(Algorithm::HS256));
+        assert!(keys[1].fits(Algorithm::ES256));
+        assert!(!keys[1].fits(Algorithm::RS256));
+    }
+
+    #[tokio::test]
+    async fn refetches_once_the_refresh_interval_passes() {
+        let (address, endpoint) = endpoint().await;
+        let remote = remote(address);
+        let start = Instant::now();
+
+        assert_eq!(kids(&remote.keys_at(None, start).await.unwrap()), ["r1", "e1"]);
+        remote.keys_at(None, start + REFRESH / 2).await.unwrap();
+        assert_eq!(endpoint.hits.load(Ordering::SeqCst), 1);
+
+        *endpoint.document.lock().unwrap() = JWKS.replace("\"r1\"", "\"r2\"");
+        let rotated = remote.keys_at(None, start + REFRESH).await.unwrap();
+        assert_eq!(kids(&rotated), ["r2", "e1"]);
+        assert_eq!(endpoint.hits.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn throttles_refetches_for_unknown_kids() {
+        let (address, endpoint) = endpoint().await;
+        let remote = remote(address);
+        let start = Instant::now();
+        remote.keys_at(None, start).await.unwrap();
+
+        // A known kid never refetches, an unknown one at most once per interval
+        remote.keys_at(Some("r1"), start + UNKNOWN_KID_REFETCH).await.unwrap();
+        remote.keys_at(Some("junk"), start + UNKNOWN_KID_REFETCH / 2).await.unwrap();
+        assert_eq!(endpoint.hits.load(Ordering::SeqCst), 1);
+
+        *endpoint.document.lock().unwrap() = JWKS.replace("\"r1\"", "\"r2\"");
+        let later = start + UNKNOWN_KID_REFETCH;
+        assert_eq!(kids(&remote.keys_at(Some("r2"), later).await.unwrap()), ["r2", "e1"]);
+        remote.keys_at(Some("junk"), later + Duration::from_secs(1)).await.unwrap();
+        assert_eq!(endpoint.hits.load(Ordering::SeqCst), 2);
+        remote.keys_at(Some("junk"), later + UNKNOWN_KID_REFETCH).await.unwrap();
+        assert_eq!(endpoint.hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn keeps_stale_keys_while_the_endpoint_fails() {
+        let (address, endpoint) = endpoint().await;
+        let remote = remote(address);
+        let start = Instant::now();
+        endpoint.down.store(true, Ordering::SeqCst);
+        assert!(remote.keys_at(None, start).await.is_err());
+
+        endpoint.down.store(false, Ordering::SeqCst);
+        remote.keys_at(None, start).await.unwrap();
+        endpoint.down.store(true, Ordering::SeqCst);
+        let failed_at = start + REFRESH;
+        assert_eq!(kids(&remote.keys_at(None, failed_at).await.unwrap()), ["r1", "e1"]);
+        assert_eq!(endpoint.hits.load(Ordering::SeqCst), 3);
+
+        // The failed fetch is retried after UNKNOWN_KID_REFETCH, not on every request
+        remote.keys_at(None, failed_at + Duration::from_secs(1)).await.unwrap();
+        assert_eq!(endpoint.hits.load(Ordering::SeqCst), 3);
+        remote.keys_at(None, failed_at + UNKNOWN_KID_REFETCH).await.unwrap();
+        assert_eq!(endpoint.hits.load(Ordering::SeqCst), 4);
+    }
+}
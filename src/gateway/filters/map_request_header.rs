@@ -0,0 +1,61 @@
+use async_trait::async_trait;
+use http::HeaderName;
+use hyper::{body::Incoming, Request};
+
+use super::{header_name, Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+
+/// Copies every value of one header onto another, appending to any values
+/// the target already has. Does nothing when the source header is missing.
+#[derive(Clone, Debug)]
+pub struct MapRequestHeader {
+    pub from: HeaderName,
+    pub to: HeaderName,
+}
+
+impl MapRequestHeader {
+    pub fn new(from: &str, to: &str) -> Result<Self, GatewayError> {
+        Ok(Self { from: header_name(from)?, to: header_name(to)? })
+    }
+
+    fn update<B>(&self, req: &mut Request<B>) {
+        let values: Vec<_> = req.headers().get_all(&self.from).iter().cloned().collect();
+        for value in values {
+            req.headers_mut().append(self.to.clone(), value);
+        }
+    }
+}
+
+#[async_trait]
+impl Filterable for MapRequestHeader {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.update(&mut req);
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_every_value_onto_the_target() {
+        let mut req = Request::builder()
+            .header("x-user", "alice")
+            .header("x-user", "bob")
+            .header("x-forwarded-user", "carol")
+            .body(())
+            .unwrap();
+        MapRequestHeader::new("x-user", "x-forwarded-user").unwrap().update(&mut req);
+        let values: Vec<_> = req.headers().get_all("x-forwarded-user").iter().collect();
+        assert_eq!(values, ["carol", "alice", "bob"]);
+        assert_eq!(req.headers().get_all("x-user").iter().count(), 2);
+    }
+
+    #[test]
+    fn does_nothing_without_the_source() {
+        let mut req = Request::new(());
+        MapRequestHeader::new("x-user", "x-forwarded-user").unwrap().update(&mut req);
+        assert!(req.headers().is_empty());
+    }
+}
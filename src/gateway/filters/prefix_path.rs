@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use hyper::{body::Incoming, Request};
+
+use super::{filter_failed, set_request_path, Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+
+/// Prepends a fixed prefix to the request path.
+#[derive(Clone, Debug)]
+pub struct PrefixPath {
+    pub prefix: String,
+}
+
+impl PrefixPath {
+    pub fn new(prefix: String) -> Result<Self, GatewayError> {
+        if !prefix.starts_with('/') {
+            return Err(GatewayError::InvalidConfig(format!(
+                "PrefixPath prefix '{}' must start with '/'",
+                prefix
+            )));
+        }
+        Ok(Self { prefix: prefix.trim_end_matches('/').to_string() })
+    }
+
+    fn rewrite<B>(&self, req: &mut Request<B>) -> Result<(), &'static str> {
+        let path = format!("{}{}", self.prefix, req.uri().path());
+        set_request_path(req, &path)
+    }
+}
+
+#[async_trait]
+impl Filterable for PrefixPath {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.rewrite(&mut req).map_err(filter_failed)?;
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefixed(prefix: &str, uri: &str) -> String {
+        let mut req = Request::builder().uri(uri).body(()).unwrap();
+        PrefixPath::new(prefix.to_string()).unwrap().rewrite(&mut req).unwrap();
+        req.uri().to_string()
+    }
+
+    #[test]
+    fn prepends_the_prefix_and_keeps_the_query() {
+        assert_eq!(prefixed("/api", "/items?page=2"), "/api/items?page=2");
+        assert_eq!(prefixed("/api/", "/items"), "/api/items");
+        assert_eq!(prefixed("/api", "/"), "/api/");
+        assert_eq!(prefixed("/", "/items"), "/items");
+    }
+
+    #[test]
+    fn keeps_absolute_request_targets() {
+        assert_eq!(prefixed("/api", "http://example.org/items"), "http://example.org/api/items");
+    }
+
+    #[test]
+    fn rejects_relative_prefixes() {
+        assert!(PrefixPath::new("api".to_string()).is_err());
+        assert!(PrefixPath::new(String::new()).is_err());
+    }
+}
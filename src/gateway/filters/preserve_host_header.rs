@@ -0,0 +1,106 @@
+use async_trait::async_trait;
+use http::header::HOST;
+use http::HeaderValue;
+use hyper::{body::Incoming, Request};
+
+use super::{Filterable, FilteredResult};
+
+/// The `Host` header pinned by `PreserveHostHeader`, or `None` when the
+/// request had none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreservedHost(pub Option<HeaderValue>);
+
+/// Forwards the `Host` header as it is when this filter runs, even if a later
+/// filter, such as a `SetRequestHeader` among the default filters, rewrites or
+/// removes it. Runs first to keep the client's. A later `SetRequestHostHeader`
+/// still wins.
+#[derive(Clone, Debug)]
+pub struct PreserveHostHeader;
+
+impl PreserveHostHeader {
+    fn pin<B>(&self, req: &mut Request<B>) {
+        let host = req.headers().get(HOST).cloned();
+        req.extensions_mut().insert(PreservedHost(host));
+    }
+}
+
+#[async_trait]
+impl Filterable for PreserveHostHeader {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.pin(&mut req);
+        Ok(req)
+    }
+}
+
+/// Puts back a `Host` header pinned by `PreserveHostHeader` before the
+/// request is forwarded.
+pub fn restore_host<B>(req: &mut Request<B>) {
+    let Some(PreservedHost(host)) = req.extensions_mut().remove::<PreservedHost>() else {
+        return;
+    };
+    match host {
+        Some(host) => req.headers_mut().insert(HOST, host),
+        None => req.headers_mut().remove(HOST),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::filters::{RemoveRequestHeader, SetRequestHeader, SetRequestHostHeader};
+
+    fn request(host: Option<&str>) -> Request<()> {
+        let mut req = Request::new(());
+        if let Some(host) = host {
+            req.headers_mut().insert(HOST, host.parse().unwrap());
+        }
+        req
+    }
+
+    #[test]
+    fn forwards_the_host_as_the_filters_left_it_by_default() {
+        let mut req = request(Some("client.example.org"));
+        restore_host(&mut req);
+        assert_eq!(req.headers()[HOST], "client.example.org");
+
+        SetRequestHeader::new("host", "other.example.org").unwrap().update(&mut req).unwrap();
+        restore_host(&mut req);
+        assert_eq!(req.headers()[HOST], "other.example.org");
+    }
+
+    #[test]
+    fn keeps_the_pinned_host_through_later_header_filters() {
+        let mut req = request(Some("client.example.org"));
+        PreserveHostHeader.pin(&mut req);
+        SetRequestHeader::new("host", "other.example.org").unwrap().update(&mut req).unwrap();
+        restore_host(&mut req);
+        assert_eq!(req.headers()[HOST], "client.example.org");
+
+        let mut req = request(None);
+        PreserveHostHeader.pin(&mut req);
+        SetRequestHeader::new("host", "other.example.org").unwrap().update(&mut req).unwrap();
+        restore_host(&mut req);
+        assert!(!req.headers().contains_key(HOST));
+
+        let mut req = request(Some("client.example.org"));
+        PreserveHostHeader.pin(&mut req);
+        RemoveRequestHeader::new("host").unwrap().update(&mut req);
+        restore_host(&mut req);
+        assert_eq!(req.headers()[HOST], "client.example.org");
+    }
+
+    #[test]
+    fn gives_way_to_a_later_set_request_host_header() {
+        let mut req = request(Some("client.example.org"));
+        PreserveHostHeader.pin(&mut req);
+        SetRequestHostHeader::new("internal.example.org").unwrap().update(&mut req).unwrap();
+        restore_host(&mut req);
+        assert_eq!(req.headers()[HOST], "internal.example.org");
+
+        // The last filter wins either way
+        PreserveHostHeader.pin(&mut req);
+        SetRequestHeader::new("host", "other.example.org").unwrap().update(&mut req).unwrap();
+        restore_host(&mut req);
+        assert_eq!(req.headers()[HOST], "internal.example.org");
+    }
+}
@@ -0,0 +1,103 @@
+use async_trait::async_trait;
+use http::header::LOCATION;
+use http::{Response, StatusCode};
+use hyper::{body::Incoming, Request};
+
+use super::{filter_failed, header_value_template, render_header_value};
+use super::{Filterable, FilteredResult};
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::template::Template;
+
+/// Answers with a redirect instead of forwarding the request.
+#[derive(Clone, Debug)]
+pub struct RedirectTo {
+    pub status: StatusCode,
+    pub url: Template,
+    /// Appends the request's query string to the rendered location.
+    pub include_query: bool,
+}
+
+impl RedirectTo {
+    pub fn new(status: u16, url: &str, include_query: bool) -> Result<Self, GatewayError> {
+        let status = StatusCode::from_u16(status)
+            .ok()
+            .filter(StatusCode::is_redirection)
+            .ok_or_else(|| {
+                GatewayError::InvalidConfig(format!("RedirectTo status {} is not a 3xx", status))
+            })?;
+        Ok(Self { status, url: header_value_template(url)?, include_query })
+    }
+
+    fn redirect<B>(&self, req: &Request<B>) -> Result<Response<BoxBody>, String> {
+        let mut location = render_header_value(&self.url, req)?;
+        if let Some(query) = req.uri().query().filter(|_| self.include_query) {
+            let base = location.to_str().unwrap_or_default();
+            let separator = if base.contains('?') { '&' } else { '?' };
+            location = format!("{}{}{}", base, separator, query)
+                .parse()
+                .map_err(|_| "invalid redirect location".to_string())?;
+        }
+
+        let response = Response::builder()
+            .status(self.status)
+            .header(LOCATION, location)
+            .body(single_chunk_response_body(""))
+            .unwrap();
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Filterable for RedirectTo {
+    async fn apply(&self, req: Request<Incoming>) -> FilteredResult {
+        let response = self.redirect(&req).map_err(|e| filter_failed(&e))?;
+        Err(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http_body_util::BodyExt;
+
+    use super::*;
+    use crate::gateway::predicates::UriTemplateVariables;
+
+    fn request(uri: &str) -> Request<()> {
+        let mut req = Request::builder().uri(uri).body(()).unwrap();
+        let variables = [("segment".to_string(), "blue".to_string())].into_iter().collect();
+        req.extensions_mut().insert(UriTemplateVariables(variables));
+        req
+    }
+
+    #[tokio::test]
+    async fn answers_with_the_status_and_rendered_location() {
+        let filter = RedirectTo::new(301, "https://example.org/{segment}", false).unwrap();
+        let response = filter.redirect(&request("/red/blue?page=2")).unwrap();
+        assert_eq!(response.status(), StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.headers()[LOCATION], "https://example.org/blue");
+        assert!(response.into_body().collect().await.unwrap().to_bytes().is_empty());
+    }
+
+    #[test]
+    fn appends_the_query_when_asked() {
+        let filter = RedirectTo::new(302, "/new/{segment}", true).unwrap();
+        let location = |uri| filter.redirect(&request(uri)).unwrap().headers()[LOCATION].clone();
+        assert_eq!(location("/red/blue?page=2"), "/new/blue?page=2");
+        assert_eq!(location("/red/blue"), "/new/blue");
+
+        let filter = RedirectTo::new(307, "/new?from=gw", true).unwrap();
+        let response = filter.redirect(&request("/old?page=2")).unwrap();
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()[LOCATION], "/new?from=gw&page=2");
+    }
+
+    #[test]
+    fn rejects_other_statuses_and_fails_on_missing_variables() {
+        assert!(RedirectTo::new(200, "/new", false).is_err());
+        assert!(RedirectTo::new(404, "/new", false).is_err());
+        assert!(RedirectTo::new(1000, "/new", false).is_err());
+        let filter = RedirectTo::new(302, "/{missing}", false).unwrap();
+        assert!(filter.redirect(&request("/")).is_err());
+    }
+}
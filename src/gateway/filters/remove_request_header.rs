@@ -0,0 +1,48 @@
+use async_trait::async_trait;
+use http::HeaderName;
+use hyper::{body::Incoming, Request};
+
+use super::{header_name, Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+
+/// Drops every value of a header before forwarding.
+#[derive(Clone, Debug)]
+pub struct RemoveRequestHeader {
+    pub name: HeaderName,
+}
+
+impl RemoveRequestHeader {
+    pub fn new(name: &str) -> Result<Self, GatewayError> {
+        Ok(Self { name: header_name(name)? })
+    }
+
+    pub(super) fn update<B>(&self, req: &mut Request<B>) {
+        req.headers_mut().remove(&self.name);
+    }
+}
+
+#[async_trait]
+impl Filterable for RemoveRequestHeader {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.update(&mut req);
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_every_value() {
+        let mut req = Request::builder()
+            .header("cookie", "a=1")
+            .header("cookie", "b=2")
+            .header("accept", "*/*")
+            .body(())
+            .unwrap();
+        RemoveRequestHeader::new("Cookie").unwrap().update(&mut req);
+        assert!(!req.headers().contains_key("cookie"));
+        assert!(req.headers().contains_key("accept"));
+    }
+}
@@ -0,0 +1,51 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use http::uri::PathAndQuery;
+use http::Uri;
+use hyper::{body::Incoming, Request};
+
+use super::{filter_failed, Filterable, FilteredResult};
+
+/// Drops every occurrence of a query parameter. The remaining parameters
+/// keep their original encoding.
+#[derive(Clone, Debug)]
+pub struct RemoveRequestParameter {
+    pub name: String,
+}
+
+impl RemoveRequestParameter {
+    pub fn new(name: String) -> Self {
+        Self { name }
+    }
+
+    pub(super) fn rewrite<B>(&self, req: &mut Request<B>) -> Result<(), &'static str> {
+        let Some(query) = req.uri().query() else { return Ok(()) };
+        let kept: Vec<&str> = query
+            .split('&')
+            .filter(|pair| {
+                let key = pair.split('=').next().unwrap_or_default();
+                form_urlencoded::parse(key.as_bytes())
+                    .next()
+                    .is_none_or(|(key, _)| key != self.name)
+            })
+            .collect();
+
+        let path = req.uri().path();
+        let path_and_query =
+            if kept.is_empty() { path.to_string() } else { format!("{}?{}", path, kept.join("&")) };
+        let mut uri_parts = req.uri().clone().into_parts();
+        uri_parts.path_and_query =
+            Some(PathAndQuery::from_str(&path_and_query).map_err(|_| "invalid query")?);
+        *req.uri_mut() = Uri::from_parts(uri_parts).map_err(|_| "invalid URI")?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Filterable for RemoveRequestParameter {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.rewrite(&mut req).map_err(filter_failed)?;
+        Ok(req)
+    }
+}
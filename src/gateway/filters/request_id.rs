@@ -0,0 +1,153 @@
+use async_trait::async_trait;
+use http::{HeaderName, HeaderValue, Response};
+use hyper::{body::Incoming, Request};
+
+use super::{header_name, Filterable, FilteredResult};
+use crate::gateway::bodies::BoxBody;
+use crate::gateway::errors::GatewayError;
+
+/// Id of the request, stored in its extensions for logs and error bodies.
+#[derive(Clone, Debug)]
+pub struct RequestIdValue(pub String);
+
+/// The request's id for log lines, or `-` when it has none.
+pub fn request_id<B>(req: &Request<B>) -> &str {
+    req.extensions().get::<RequestIdValue>().map_or("-", |id| id.0.as_str())
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum IdGenerator {
+    Uuid,
+    Ulid,
+}
+
+impl IdGenerator {
+    fn generate(self) -> String {
+        match self {
+            IdGenerator::Uuid => uuid::Uuid::new_v4().to_string(),
+            IdGenerator::Ulid => ulid::Ulid::new().to_string(),
+        }
+    }
+}
+
+/// Gives each request an id: the client's own when it sent a usable one,
+/// otherwise a fresh one. The id is forwarded upstream and echoed back.
+#[derive(Clone, Debug)]
+pub struct RequestId {
+    pub header: HeaderName,
+    pub generator: IdGenerator,
+    /// Longest incoming id that is reused.
+    pub max_length: usize,
+    pub echo: bool,
+}
+
+impl RequestId {
+    pub fn new(
+        header: &str,
+        generator: IdGenerator,
+        max_length: usize,
+        echo: bool,
+    ) -> Result<Self, GatewayError> {
+        Ok(Self { header: header_name(header)?, generator, max_length, echo })
+    }
+
+    /// Reuses an id assigned earlier, e.g. before routing, or else the
+    /// client's, or generates one; then sets it on the forwarded request.
+    pub fn assign<B>(&self, req: &mut Request<B>) {
+        let id = match req.extensions().get::<RequestIdValue>() {
+            Some(id) => id.0.clone(),
+            None => {
+                let incoming = req.headers().get(&self.header).and_then(|id| id.to_str().ok());
+                match incoming.filter(|id| self.is_valid(id)) {
+                    Some(id) => id.to_string(),
+                    None => self.generator.generate(),
+                }
+            }
+        };
+        if let Ok(value) = HeaderValue::from_str(&id) {
+            req.headers_mut().insert(self.header.clone(), value);
+        }
+        req.extensions_mut().insert(RequestIdValue(id));
+    }
+
+    /// Incoming ids are only trusted when short and made of characters that
+    /// are safe in logs.
+    fn is_valid(&self, id: &str) -> bool {
+        !id.is_empty()
+            && id.len() <= self.max_length
+            && id.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.:".contains(&b))
+    }
+}
+
+#[async_trait]
+impl Filterable for RequestId {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.assign(&mut req);
+        Ok(req)
+    }
+
+    fn apply_response(&self, req: &Request<()>, response: &mut Response<BoxBody>) {
+        let Some(RequestIdValue(id)) = req.extensions().get::<RequestIdValue>() else {
+            return;
+        };
+        if let (true, Ok(value)) = (self.echo, HeaderValue::from_str(id)) {
+            response.headers_mut().insert(self.header.clone(), value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::bodies::single_chunk_response_body;
+
+    fn filter(generator: IdGenerator) -> RequestId {
+        RequestId::new("x-request-id", generator, 16, true).unwrap()
+    }
+
+    fn assigned(filter: &RequestId, incoming: Option<&str>) -> String {
+        let mut req = Request::get("/");
+        if let Some(id) = incoming {
+            req = req.header("x-request-id", id);
+        }
+        let mut req = req.body(()).unwrap();
+        filter.assign(&mut req);
+        let id = request_id(&req).to_string();
+        assert_eq!(req.headers()["x-request-id"], id.as_str());
+        id
+    }
+
+    #[test]
+    fn reuses_valid_client_ids() {
+        let filter = filter(IdGenerator::Uuid);
+        assert_eq!(assigned(&filter, Some("abc-123_x.y:z")), "abc-123_x.y:z");
+        assert_eq!(assigned(&filter, Some("0123456789abcdef")), "0123456789abcdef");
+    }
+
+    #[test]
+    fn replaces_unsafe_or_long_ids() {
+        let ulids = filter(IdGenerator::Ulid);
+        let filter = filter(IdGenerator::Uuid);
+        for incoming in [None, Some(""), Some("0123456789abcdefg"), Some("a b"), Some("a\"b")] {
+            let id = assigned(&filter, incoming);
+            assert!(uuid::Uuid::parse_str(&id).is_ok(), "{:?} became {}", incoming, id);
+        }
+        let ulid = assigned(&ulids, Some("new\tline"));
+        assert!(ulid::Ulid::from_string(&ulid).is_ok(), "{}", ulid);
+    }
+
+    #[test]
+    fn keeps_ids_assigned_before_routing() {
+        let filter = filter(IdGenerator::Uuid);
+        let mut req = Request::get("/").header("x-request-id", "from-client").body(()).unwrap();
+        req.extensions_mut().insert(RequestIdValue("assigned".to_string()));
+        filter.assign(&mut req);
+        assert_eq!(request_id(&req), "assigned");
+        assert_eq!(req.headers()["x-request-id"], "assigned");
+
+        let mut response = Response::new(single_chunk_response_body(""));
+        filter.apply_response(&req, &mut response);
+        assert_eq!(response.headers()["x-request-id"], "assigned");
+        assert_eq!(request_id(&Request::new(())), "-");
+    }
+}
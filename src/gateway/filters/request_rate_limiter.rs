@@ -0,0 +1,448 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use http::{HeaderName, Response, StatusCode};
+use hyper::{body::Incoming, Request};
+
+use super::{header_name, request_id, Filterable, FilteredResult, Principal};
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::route::RouteId;
+
+pub use redis_store::RedisBuckets;
+
+mod redis_store;
+
+const X_RATELIMIT_REMAINING: &str = "x-ratelimit-remaining";
+const X_RATELIMIT_LIMIT: &str = "x-ratelimit-limit";
+const X_RATELIMIT_RESET: &str = "x-ratelimit-reset";
+
+/// Buckets live outside the route table so hot reloads keep their state.
+static BUCKETS: LazyLock<BucketStore> = LazyLock::new(BucketStore::new);
+
+/// Token bucket per route and client key, as in Spring's `RequestRateLimiter`.
+#[derive(Clone, Debug)]
+pub struct RequestRateLimiter {
+    pub limits: Limits,
+    pub key_resolver: KeyResolver,
+    pub deny_empty_key: bool,
+    pub backend: BucketBackend,
+    /// Place in the route's filter chain, so that two limiters on one route,
+    /// e.g. a default filter and the route's own, keep separate buckets.
+    pub position: usize,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    pub replenish_rate: u32,
+    pub burst_capacity: u32,
+    pub requested_tokens: u32,
+}
+
+#[derive(Clone, Debug)]
+pub enum KeyResolver {
+    RemoteAddr,
+    XForwardedFor { max_trusted_index: usize },
+    Header(HeaderName),
+    Principal,
+    RouteId,
+}
+
+/// Where a rate limiter keeps its buckets.
+#[derive(Clone, Debug)]
+pub enum BucketBackend {
+    /// In this process, so every replica enforces the limit on its own.
+    Local,
+    Redis(Arc<RedisBuckets>),
+}
+
+impl RequestRateLimiter {
+    pub fn new(
+        limits: Limits,
+        key_resolver: KeyResolver,
+        deny_empty_key: bool,
+        backend: BucketBackend,
+    ) -> Result<Self, GatewayError> {
+        let invalid =
+            |reason: &str| GatewayError::InvalidConfig(format!("RequestRateLimiter {}", reason));
+        if limits.replenish_rate == 0 {
+            return Err(invalid("replenish_rate must be positive"));
+        }
+        if limits.requested_tokens == 0 || limits.requested_tokens > limits.burst_capacity {
+            return Err(invalid("requested_tokens must be between 1 and burst_capacity"));
+        }
+        if let KeyResolver::XForwardedFor { max_trusted_index: 0 } = key_resolver {
+            return Err(invalid("max_trusted_index must be positive"));
+        }
+        Ok(Self { limits, key_resolver, deny_empty_key, backend, position: 0 })
+    }
+}
+
+impl KeyResolver {
+    pub fn header(name: &str) -> Result<Self, GatewayError> {
+        Ok(Self::Header(header_name(name)?))
+    }
+
+    fn resolve<B>(&self, req: &Request<B>) -> Option<String> {
+        let remote_ip = || req.extensions().get::<SocketAddr>().map(|addr| addr.ip().to_string());
+        match self {
+            KeyResolver::RemoteAddr => remote_ip(),
+            KeyResolver::XForwardedFor { max_trusted_index } => {
+                let forwarded: Vec<&str> = req
+                    .headers()
+                    .get_all("x-forwarded-for")
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .flat_map(|value| value.split(','))
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty())
+                    .collect();
+                if forwarded.is_empty() {
+                    return remote_ip();
+                }
+                // Entries left of the trusted proxies may be spoofed by the client
+                let position = forwarded.len().saturating_sub(*max_trusted_index);
+                Some(forwarded[position].to_string())
+            }
+            KeyResolver::Header(name) => {
+                let value = req.headers().get(name)?.to_str().ok()?;
+                (!value.is_empty()).then(|| value.to_string())
+            }
+            KeyResolver::Principal => req.extensions().get::<Principal>().map(|p| p.0.clone()),
+            KeyResolver::RouteId => Some(String::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl Filterable for RequestRateLimiter {
+    async fn apply(&self, req: Request<Incoming>) -> FilteredResult {
+        self.check(&req).await.map(|()| req)
+    }
+}
+
+impl RequestRateLimiter {
+    /// `Err` holds the response turning the request away.
+    async fn check<B: Sync>(&self, req: &Request<B>) -> Result<(), Response<BoxBody>> {
+        let Some(key) = self.key_resolver.resolve(req) else {
+            if !self.deny_empty_key {
+                return Ok(());
+            }
+            let response = Response::builder()
+                .status(StatusCode::FORBIDDEN)
+                .body(single_chunk_response_body("Rate limit key could not be resolved"))
+                .unwrap();
+            return Err(response);
+        };
+
+        let route = req.extensions().get::<RouteId>().map_or("", |id| id.0.as_str());
+        let (position, limits) = (self.position, self.limits);
+        let outcome = match &self.backend {
+            BucketBackend::Local => BUCKETS.take(route, position, &key, limits),
+            BucketBackend::Redis(store) => match store.take(route, position, &key, limits).await {
+                Ok(outcome) => outcome,
+                Err(err) => {
+                    eprintln!("[{}] Rate limiter store error: {err}", request_id(req));
+                    if store.fail_open {
+                        return Ok(());
+                    }
+                    let response = Response::builder()
+                        .status(StatusCode::SERVICE_UNAVAILABLE)
+                        .body(single_chunk_response_body("Rate limiter unavailable"))
+                        .unwrap();
+                    return Err(response);
+                }
+            },
+        };
+        if outcome.allowed {
+            return Ok(());
+        }
+        let response = Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header(X_RATELIMIT_REMAINING, outcome.remaining)
+            .header(X_RATELIMIT_LIMIT, self.limits.burst_capacity)
+            .header(X_RATELIMIT_RESET, outcome.reset_secs)
+            .body(single_chunk_response_body("Too Many Requests"))
+            .unwrap();
+        Err(response)
+    }
+}
+
+/// Upper bound on buckets across all routes, at roughly 40 bytes each.
+const MAX_BUCKETS: usize = 1 << 22;
+const SHARDS: usize = 64;
+
+struct Bucket {
+    tokens: f64,
+    /// Microseconds since the store epoch.
+    updated: u64,
+    /// When the bucket will be full again and is no different from a new one.
+    full_at: u64,
+}
+
+struct Outcome {
+    allowed: bool,
+    remaining: u64,
+    /// Seconds until the rejected request would be allowed.
+    reset_secs: u64,
+}
+
+/// Sharded bucket map keyed by a hash of route and client key, so memory per
+/// key stays fixed however long the keys are.
+struct BucketStore {
+    epoch: Instant,
+    hasher: RandomState,
+    shards: Vec<Mutex<HashMap<u64, Bucket>>>,
+}
+
+impl BucketStore {
+    fn new() -> Self {
+        let shards = (0..SHARDS).map(|_| Mutex::default()).collect();
+        Self { epoch: Instant::now(), hasher: RandomState::new(), shards }
+    }
+
+    fn take(&self, route: &str, position: usize, key: &str, limits: Limits) -> Outcome {
+        let now = self.epoch.elapsed().as_micros() as u64;
+        self.take_at(now, route, position, key, limits)
+    }
+
+    /// `take` at `now` microseconds since the store epoch.
+    fn take_at(
+        &self,
+        now: u64,
+        route: &str,
+        position: usize,
+        key: &str,
+        limits: Limits,
+    ) -> Outcome {
+        let hash = self.hasher.hash_one((route, position, key));
+        let rate = limits.replenish_rate as f64 / 1e6;
+        let capacity = limits.burst_capacity as f64;
+        let requested = limits.requested_tokens as f64;
+
+        let mut shard = self.shards[hash as usize % SHARDS].lock().unwrap();
+        if shard.len() >= MAX_BUCKETS / SHARDS && !shard.contains_key(&hash) {
+            make_room(&mut shard, now);
+        }
+        let bucket =
+            shard.entry(hash).or_insert(Bucket { tokens: capacity, updated: now, full_at: now });
+        let elapsed = now.saturating_sub(bucket.updated) as f64;
+        // A reload may have lowered the capacity since the last request
+        let mut tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        let allowed = tokens >= requested;
+        if allowed {
+            tokens -= requested;
+        }
+        bucket.tokens = tokens;
+        bucket.updated = now;
+        bucket.full_at = now + ((capacity - tokens) / rate) as u64;
+
+        let reset_secs =
+            if allowed { 0 } else { ((requested - tokens) / rate / 1e6).ceil() as u64 };
+        Outcome { allowed, remaining: tokens as u64, reset_secs }
+    }
+}
+
+/// Drops buckets that have refilled, which loses nothing, and if the shard is
+/// still full the eighth closest to refilled.
+fn make_room(shard: &mut HashMap<u64, Bucket>, now: u64) {
+    shard.retain(|_, bucket| bucket.full_at > now);
+    if shard.len() < MAX_BUCKETS / SHARDS {
+        return;
+    }
+    let mut full_at: Vec<u64> = shard.values().map(|bucket| bucket.full_at).collect();
+    let eighth = full_at.len() / 8;
+    let (_, &mut cutoff, _) = full_at.select_nth_unstable(eighth);
+    shard.retain(|_, bucket| bucket.full_at > cutoff);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::config_loader::build_routes;
+    use crate::gateway::filters::Filter;
+
+    const LIMITS: Limits = Limits { replenish_rate: 2, burst_capacity: 3, requested_tokens: 1 };
+    const SECOND: u64 = 1_000_000;
+
+    fn filled(full_at: u64) -> Bucket {
+        Bucket { tokens: 0.0, updated: 0, full_at }
+    }
+
+    #[test]
+    fn takes_tokens_until_the_bucket_is_empty_and_refills_over_time() {
+        let store = BucketStore::new();
+        for remaining in [2, 1, 0] {
+            let outcome = store.take_at(0, "route", 0, "client", LIMITS);
+            assert!(outcome.allowed);
+            assert_eq!(outcome.remaining, remaining);
+        }
+        let denied = store.take_at(SECOND / 4, "route", 0, "client", LIMITS);
+        assert!(!denied.allowed);
+        assert_eq!(denied.reset_secs, 1);
+
+        // Half a second later the rate of 2 per second has put one token back
+        assert!(store.take_at(SECOND / 2, "route", 0, "client", LIMITS).allowed);
+        assert!(!store.take_at(SECOND / 2, "route", 0, "client", LIMITS).allowed);
+        // Never more than the burst capacity, however long the bucket sat idle
+        let outcome = store.take_at(100 * SECOND, "route", 0, "client", LIMITS);
+        assert_eq!(outcome.remaining, 2);
+    }
+
+    #[test]
+    fn keeps_buckets_apart_by_route_position_and_key() {
+        let store = BucketStore::new();
+        let single = Limits { burst_capacity: 1, ..LIMITS };
+        assert!(store.take_at(0, "route", 0, "client", single).allowed);
+        assert!(!store.take_at(0, "route", 0, "client", single).allowed);
+        assert!(store.take_at(0, "route", 1, "client", single).allowed);
+        assert!(store.take_at(0, "other", 0, "client", single).allowed);
+        assert!(store.take_at(0, "route", 0, "other", single).allowed);
+    }
+
+    #[test]
+    fn charges_requested_tokens_and_honours_a_lowered_capacity() {
+        let store = BucketStore::new();
+        let costly = Limits { requested_tokens: 2, ..LIMITS };
+        assert_eq!(store.take_at(0, "route", 0, "client", costly).remaining, 1);
+        let denied = store.take_at(0, "route", 0, "client", costly);
+        assert!(!denied.allowed);
+        assert_eq!(denied.reset_secs, 1);
+
+        let store = BucketStore::new();
+        store.take_at(0, "route", 0, "client", LIMITS);
+        let lowered = Limits { burst_capacity: 1, ..LIMITS };
+        let outcome = store.take_at(0, "route", 0, "client", lowered);
+        assert!(outcome.allowed);
+        assert_eq!(outcome.remaining, 0);
+    }
+
+    #[test]
+    fn makes_room_by_dropping_refilled_buckets_first() {
+        let per_shard = MAX_BUCKETS / SHARDS;
+        let mut shard: HashMap<u64, Bucket> =
+            (0..per_shard as u64).map(|hash| (hash, filled(hash % 2 * 100))).collect();
+        make_room(&mut shard, 50);
+        assert_eq!(shard.len(), per_shard / 2);
+        assert!(shard.values().all(|bucket| bucket.full_at == 100));
+    }
+
+    #[test]
+    fn makes_room_by_dropping_the_eighth_closest_to_refilled() {
+        let per_shard = MAX_BUCKETS / SHARDS;
+        let mut shard: HashMap<u64, Bucket> =
+            (0..per_shard as u64).map(|hash| (hash, filled(1_000 + hash))).collect();
+        make_room(&mut shard, 0);
+        let cutoff = 1_000 + (per_shard / 8) as u64;
+        assert_eq!(shard.len(), per_shard - per_shard / 8 - 1);
+        assert!(shard.values().all(|bucket| bucket.full_at > cutoff));
+    }
+
+    #[test]
+    fn evicts_only_when_a_shard_is_full() {
+        let store = BucketStore::new();
+        let per_shard = MAX_BUCKETS / SHARDS;
+        let index = store.hasher.hash_one(("route", 0usize, "new")) as usize % SHARDS;
+        let fill = |store: &BucketStore, count: usize| {
+            let mut shard = store.shards[index].lock().unwrap();
+            shard.clear();
+            shard.extend((0..count as u64).map(|hash| (hash, filled(1_000 + hash))));
+        };
+
+        fill(&store, per_shard - 1);
+        store.take_at(0, "route", 0, "new", LIMITS);
+        assert_eq!(store.shards[index].lock().unwrap().len(), per_shard);
+
+        fill(&store, per_shard);
+        store.take_at(0, "route", 0, "new", LIMITS);
+        let len = store.shards[index].lock().unwrap().len();
+        assert!(len < per_shard && len > per_shard / 2, "{}", len);
+    }
+
+    fn forwarded(values: &[&str]) -> Request<()> {
+        let mut req = Request::new(());
+        for value in values {
+            req.headers_mut().append("x-forwarded-for", value.parse().unwrap());
+        }
+        req.extensions_mut().insert(SocketAddr::from(([10, 0, 0, 1], 4000)));
+        req
+    }
+
+    #[test]
+    fn picks_the_forwarded_entry_past_the_trusted_proxies() {
+        let resolve = |index, req: &Request<()>| {
+            KeyResolver::XForwardedFor { max_trusted_index: index }.resolve(req)
+        };
+        let req = forwarded(&["1.1.1.1, 2.2.2.2", "3.3.3.3"]);
+        assert_eq!(resolve(1, &req).as_deref(), Some("3.3.3.3"));
+        assert_eq!(resolve(2, &req).as_deref(), Some("2.2.2.2"));
+        assert_eq!(resolve(3, &req).as_deref(), Some("1.1.1.1"));
+        assert_eq!(resolve(9, &req).as_deref(), Some("1.1.1.1"));
+
+        let req = forwarded(&[" 1.1.1.1 ,, 2.2.2.2 ,"]);
+        assert_eq!(resolve(1, &req).as_deref(), Some("2.2.2.2"));
+        assert_eq!(resolve(1, &forwarded(&[])).as_deref(), Some("10.0.0.1"));
+        assert_eq!(resolve(1, &forwarded(&[" , "])).as_deref(), Some("10.0.0.1"));
+    }
+
+    #[test]
+    fn resolves_headers_principals_and_routes() {
+        let mut req = forwarded(&[]);
+        let header = KeyResolver::header("x-api-key").unwrap();
+        assert_eq!(header.resolve(&req), None);
+        req.headers_mut().insert("x-api-key", "".parse().unwrap());
+        assert_eq!(header.resolve(&req), None);
+        req.headers_mut().insert("x-api-key", "k1".parse().unwrap());
+        assert_eq!(header.resolve(&req).as_deref(), Some("k1"));
+
+        assert_eq!(KeyResolver::Principal.resolve(&req), None);
+        req.extensions_mut().insert(Principal("alice".to_string()));
+        assert_eq!(KeyResolver::Principal.resolve(&req).as_deref(), Some("alice"));
+        assert_eq!(KeyResolver::RemoteAddr.resolve(&req).as_deref(), Some("10.0.0.1"));
+        assert_eq!(KeyResolver::RouteId.resolve(&req).as_deref(), Some(""));
+    }
+
+    const TWICE_LIMITED: &str = r#"
+default_filters:
+  - type: RequestRateLimiter
+    replenish_rate: 1
+    burst_capacity: 1
+routes:
+  - id: twice-limited
+    destination: http://localhost:9000
+    predicates: []
+    filters:
+      - type: RequestRateLimiter
+        replenish_rate: 1
+        burst_capacity: 1
+"#;
+
+    #[tokio::test]
+    async fn charges_each_limiter_of_a_route_once() {
+        let routes = build_routes(serde_yaml::from_str(TWICE_LIMITED).unwrap()).unwrap();
+        let limiters: Vec<&RequestRateLimiter> = routes[0]
+            .filters
+            .iter()
+            .filter_map(|filter| match filter {
+                Filter::RequestRateLimiter(limiter) => Some(limiter),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(limiters.len(), 2);
+
+        let mut req = forwarded(&[]);
+        req.extensions_mut().insert(RouteId("twice-limited".to_string()));
+        for limiter in &limiters {
+            assert!(limiter.check(&req).await.is_ok());
+        }
+        for limiter in &limiters {
+            let response = limiter.check(&req).await.unwrap_err();
+            assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use async_trait::async_trait;
+use hyper::{body::Incoming, Request};
+use regex::Regex;
+
+use super::{filter_failed, set_request_path, Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+
+/// Rewrites the request path with a regex replacement; the replacement may
+/// reference groups as `$1`, `${name}` or Spring's YAML-escaped `$\{name}`.
+#[derive(Clone, Debug)]
+pub struct RewritePath {
+    pub regexp: Regex,
+    pub replacement: String,
+}
+
+impl RewritePath {
+    pub fn new(regexp: &str, replacement: &str) -> Result<Self, GatewayError> {
+        let regexp = Regex::new(regexp).map_err(|err| {
+            GatewayError::InvalidConfig(format!("invalid RewritePath regex '{}': {}", regexp, err))
+        })?;
+        Ok(Self { regexp, replacement: replacement.replace("$\\{", "${") })
+    }
+
+    fn rewrite<B>(&self, req: &mut Request<B>) -> Result<(), &'static str> {
+        let rewritten = self.regexp.replace_all(req.uri().path(), self.replacement.as_str());
+        let path = if rewritten.starts_with('/') {
+            rewritten.into_owned()
+        } else {
+            format!("/{}", rewritten)
+        };
+        set_request_path(req, &path)
+    }
+}
+
+#[async_trait]
+impl Filterable for RewritePath {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.rewrite(&mut req).map_err(filter_failed)?;
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewritten(regexp: &str, replacement: &str, uri: &str) -> String {
+        let mut req = Request::builder().uri(uri).body(()).unwrap();
+        RewritePath::new(regexp, replacement).unwrap().rewrite(&mut req).unwrap();
+        req.uri().to_string()
+    }
+
+    #[test]
+    fn replaces_with_numbered_and_named_groups() {
+        let segment = "/red/(?P<segment>.*)";
+        assert_eq!(rewritten(segment, "/$\\{segment}", "/red/blue?x=1"), "/blue?x=1");
+        assert_eq!(rewritten(segment, "/${segment}", "/red/a/b"), "/a/b");
+        assert_eq!(rewritten("^/v(\\d+)/", "/api/$1/", "/v2/items"), "/api/2/items");
+    }
+
+    #[test]
+    fn keeps_the_path_absolute() {
+        assert_eq!(rewritten("^/api", "", "/api/items"), "/items");
+        assert_eq!(rewritten("^/api/?", "", "/api"), "/");
+        assert_eq!(rewritten("^/", "", "/items"), "/items");
+        assert_eq!(rewritten("^/nothing", "/else", "/items"), "/items");
+    }
+
+    #[test]
+    fn fails_on_invalid_results_and_regexes() {
+        let mut req = Request::builder().uri("/items").body(()).unwrap();
+        let filter = RewritePath::new("items", "a b").unwrap();
+        assert!(filter.rewrite(&mut req).is_err());
+        assert!(RewritePath::new("(", "/").is_err());
+    }
+}
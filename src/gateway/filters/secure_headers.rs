@@ -0,0 +1,130 @@
+use async_trait::async_trait;
+use http::header::{
+    CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS,
+    X_FRAME_OPTIONS,
+};
+use http::{HeaderName, HeaderValue, Response};
+use hyper::{body::Incoming, Request};
+
+use super::{Filterable, FilteredResult};
+use crate::gateway::bodies::BoxBody;
+use crate::gateway::errors::GatewayError;
+
+pub const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+
+/// Headers and values used unless a route overrides or disables them.
+const DEFAULT_SECURE_HEADERS: [(HeaderName, &str); 6] = [
+    (STRICT_TRANSPORT_SECURITY, "max-age=31536000; includeSubDomains"),
+    (X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    (X_FRAME_OPTIONS, "DENY"),
+    (REFERRER_POLICY, "no-referrer"),
+    (
+        CONTENT_SECURITY_POLICY,
+        "default-src 'self' https:; font-src 'self' https: data:; img-src 'self' https: data:; \
+         object-src 'none'; script-src https:; style-src 'self' https: 'unsafe-inline'",
+    ),
+    (PERMISSIONS_POLICY, "camera=(), geolocation=(), microphone=()"),
+];
+
+/// Adds browser security headers to responses, like Spring's filter of the
+/// same name. Values the upstream set itself are left alone.
+#[derive(Clone, Debug)]
+pub struct SecureHeaders {
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl SecureHeaders {
+    /// `overrides` replace default values by header name; `disabled` names
+    /// headers that are not sent at all.
+    pub fn new(
+        overrides: &[(HeaderName, Option<String>)],
+        disabled: &[String],
+    ) -> Result<Self, GatewayError> {
+        for name in disabled {
+            if !DEFAULT_SECURE_HEADERS
+                .iter()
+                .any(|(header, _)| header.as_str().eq_ignore_ascii_case(name))
+            {
+                return Err(GatewayError::InvalidConfig(format!(
+                    "SecureHeaders cannot disable unknown header '{}'",
+                    name
+                )));
+            }
+        }
+        let mut headers = Vec::new();
+        for (name, default) in DEFAULT_SECURE_HEADERS {
+            if disabled.iter().any(|disabled| name.as_str().eq_ignore_ascii_case(disabled)) {
+                continue;
+            }
+            let configured = overrides.iter().find(|(header, _)| *header == name);
+            let value = configured.and_then(|(_, value)| value.as_deref()).unwrap_or(default);
+            let value = HeaderValue::from_str(value).map_err(|_| {
+                GatewayError::InvalidConfig(format!("invalid {} value '{}'", name, value))
+            })?;
+            headers.push((name, value));
+        }
+        Ok(Self { headers })
+    }
+}
+
+#[async_trait]
+impl Filterable for SecureHeaders {
+    async fn apply(&self, req: Request<Incoming>) -> FilteredResult {
+        Ok(req)
+    }
+
+    fn apply_response(&self, _req: &Request<()>, response: &mut Response<BoxBody>) {
+        for (name, value) in &self.headers {
+            if !response.headers().contains_key(name) {
+                response.headers_mut().insert(name.clone(), value.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::bodies::single_chunk_response_body;
+
+    fn decorate(filter: &SecureHeaders, response: &mut Response<BoxBody>) {
+        filter.apply_response(&Request::new(()), response);
+    }
+
+    #[test]
+    fn adds_defaults_without_replacing_upstream_values() {
+        let filter = SecureHeaders::new(&[], &[]).unwrap();
+        let mut response = Response::new(single_chunk_response_body(""));
+        response.headers_mut().insert(X_FRAME_OPTIONS, "SAMEORIGIN".parse().unwrap());
+        decorate(&filter, &mut response);
+
+        let headers = response.headers();
+        assert_eq!(headers.len(), DEFAULT_SECURE_HEADERS.len());
+        assert_eq!(headers[X_FRAME_OPTIONS], "SAMEORIGIN");
+        assert_eq!(headers[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(headers[PERMISSIONS_POLICY], "camera=(), geolocation=(), microphone=()");
+    }
+
+    #[test]
+    fn applies_overrides_and_disabled_headers() {
+        let overrides =
+            [(REFERRER_POLICY, Some("same-origin".to_string())), (X_FRAME_OPTIONS, None)];
+        let disabled = ["Strict-Transport-Security".to_string()];
+        let filter = SecureHeaders::new(&overrides, &disabled).unwrap();
+        let mut response = Response::new(single_chunk_response_body(""));
+        decorate(&filter, &mut response);
+
+        let headers = response.headers();
+        assert_eq!(headers[REFERRER_POLICY], "same-origin");
+        assert_eq!(headers[X_FRAME_OPTIONS], "DENY");
+        assert!(!headers.contains_key(STRICT_TRANSPORT_SECURITY));
+        assert_eq!(headers.len(), DEFAULT_SECURE_HEADERS.len() - 1);
+    }
+
+    #[test]
+    fn rejects_unknown_headers_and_invalid_values() {
+        assert!(SecureHeaders::new(&[], &["X-Powered-By".to_string()]).is_err());
+        let overrides = [(REFERRER_POLICY, Some("no\nreferrer".to_string()))];
+        assert!(SecureHeaders::new(&overrides, &[]).is_err());
+    }
+}
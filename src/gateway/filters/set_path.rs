@@ -0,0 +1,87 @@
+use async_trait::async_trait;
+use hyper::{body::Incoming, Request};
+
+use super::{filter_failed, render_template, set_request_path, Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::template::Template;
+
+/// Replaces the request path with a template such as `/{segment}`, filled
+/// from the variables captured by the route's `Path` (with `pattern: true`)
+/// and `Host` predicates.
+#[derive(Clone, Debug)]
+pub struct SetPath {
+    pub template: Template,
+}
+
+impl SetPath {
+    pub fn new(template: &str) -> Result<Self, GatewayError> {
+        if !template.starts_with('/') {
+            return Err(GatewayError::InvalidConfig(format!(
+                "SetPath template '{}' must start with '/'",
+                template
+            )));
+        }
+        Ok(Self { template: Template::parse(template)? })
+    }
+
+    fn rewrite<B>(&self, req: &mut Request<B>) -> Result<(), String> {
+        let path = render_template(&self.template, req)?;
+        Ok(set_request_path(req, &path)?)
+    }
+}
+
+#[async_trait]
+impl Filterable for SetPath {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.rewrite(&mut req).map_err(|e| filter_failed(&e))?;
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::predicates::UriTemplateVariables;
+
+    fn request(uri: &str, variables: &[(&str, &str)]) -> Request<()> {
+        let mut req = Request::builder().uri(uri).body(()).unwrap();
+        let variables = variables.iter().map(|(k, v)| (k.to_string(), v.to_string()));
+        req.extensions_mut().insert(UriTemplateVariables(variables.collect()));
+        req
+    }
+
+    #[test]
+    fn fills_the_template_from_captured_variables() {
+        let filter = SetPath::new("/v2/{segment}").unwrap();
+        let mut req = request("/red/blue?x=1", &[("segment", "blue")]);
+        filter.rewrite(&mut req).unwrap();
+        assert_eq!(req.uri().to_string(), "/v2/blue?x=1");
+
+        let mut req = request("/red/", &[("segment", "")]);
+        filter.rewrite(&mut req).unwrap();
+        assert_eq!(req.uri().to_string(), "/v2/");
+
+        let root = SetPath::new("/").unwrap();
+        let mut req = request("/anything/else", &[]);
+        root.rewrite(&mut req).unwrap();
+        assert_eq!(req.uri().to_string(), "/");
+    }
+
+    #[test]
+    fn fails_on_missing_variables_and_invalid_paths() {
+        let filter = SetPath::new("/{segment}").unwrap();
+        assert_eq!(
+            filter.rewrite(&mut request("/red", &[])),
+            Err("unknown template variable 'segment'".to_string())
+        );
+        let mut req = request("/red", &[("segment", "a b")]);
+        assert!(filter.rewrite(&mut req).is_err());
+        assert_eq!(req.uri(), "/red");
+    }
+
+    #[test]
+    fn rejects_relative_templates() {
+        assert!(SetPath::new("{segment}").is_err());
+        assert!(SetPath::new("/{segment").is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+use http::HeaderName;
+use hyper::{body::Incoming, Request};
+
+use super::{filter_failed, header_name, header_value_template, render_header_value};
+use super::{Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::template::Template;
+
+/// Replaces every value of a header with a single new one.
+#[derive(Clone, Debug)]
+pub struct SetRequestHeader {
+    pub name: HeaderName,
+    pub value: Template,
+}
+
+impl SetRequestHeader {
+    pub fn new(name: &str, value: &str) -> Result<Self, GatewayError> {
+        Ok(Self { name: header_name(name)?, value: header_value_template(value)? })
+    }
+
+    pub(super) fn update<B>(&self, req: &mut Request<B>) -> Result<(), String> {
+        let value = render_header_value(&self.value, req)?;
+        req.headers_mut().insert(self.name.clone(), value);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Filterable for SetRequestHeader {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.update(&mut req).map_err(|e| filter_failed(&e))?;
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_every_value_with_one() {
+        let mut req =
+            Request::builder().header("x-tag", "a").header("x-tag", "b").body(()).unwrap();
+        SetRequestHeader::new("x-tag", "gateway").unwrap().update(&mut req).unwrap();
+        let values: Vec<_> = req.headers().get_all("x-tag").iter().collect();
+        assert_eq!(values, ["gateway"]);
+
+        let mut req = Request::new(());
+        SetRequestHeader::new("x-tag", "gateway").unwrap().update(&mut req).unwrap();
+        assert_eq!(req.headers()["x-tag"], "gateway");
+    }
+}
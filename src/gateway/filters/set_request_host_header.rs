@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use http::header::HOST;
+use hyper::{body::Incoming, Request};
+
+use super::preserve_host_header::PreservedHost;
+use super::{filter_failed, header_value_template, render_header_value};
+use super::{Filterable, FilteredResult};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::template::Template;
+
+/// Sends the upstream a specific `Host` header instead of the client's, also
+/// after a `PreserveHostHeader`.
+#[derive(Clone, Debug)]
+pub struct SetRequestHostHeader {
+    pub host: Template,
+}
+
+impl SetRequestHostHeader {
+    pub fn new(host: &str) -> Result<Self, GatewayError> {
+        Ok(Self { host: header_value_template(host)? })
+    }
+
+    pub(super) fn update<B>(&self, req: &mut Request<B>) -> Result<(), String> {
+        let host = render_header_value(&self.host, req)?;
+        req.headers_mut().insert(HOST, host);
+        req.extensions_mut().remove::<PreservedHost>();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Filterable for SetRequestHostHeader {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.update(&mut req).map_err(|e| filter_failed(&e))?;
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::predicates::UriTemplateVariables;
+
+    #[test]
+    fn sets_the_host_from_a_template() {
+        let mut req = Request::builder().header(HOST, "shop.example.org").body(()).unwrap();
+        let variables = [("sub".to_string(), "shop".to_string())].into_iter().collect();
+        req.extensions_mut().insert(UriTemplateVariables(variables));
+        SetRequestHostHeader::new("{sub}.internal:8080").unwrap().update(&mut req).unwrap();
+        assert_eq!(req.headers().get_all(HOST).iter().collect::<Vec<_>>(), ["shop.internal:8080"]);
+    }
+}
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use hyper::{body::Incoming, Request};
+
+use super::{filter_failed, set_request_path, Filterable, FilteredResult};
+
+/// Removes the first `parts` segments from the request path.
+#[derive(Clone, Debug)]
+pub struct StripPrefix {
+    pub parts: usize,
+}
+
+impl StripPrefix {
+    pub fn new(parts: usize) -> Self {
+        Self { parts }
+    }
+
+    fn rewrite<B>(&self, req: &mut Request<B>) -> Result<(), &'static str> {
+        // The path starts with '/', so the first split piece is always empty
+        let remaining: Vec<&str> = req.uri().path().split('/').skip(1 + self.parts).collect();
+        let path = format!("/{}", remaining.join("/"));
+        set_request_path(req, &path)
+    }
+}
+
+#[async_trait]
+impl Filterable for StripPrefix {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.rewrite(&mut req).map_err(filter_failed)?;
+        Ok(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stripped(parts: usize, uri: &str) -> String {
+        let mut req = Request::builder().uri(uri).body(()).unwrap();
+        StripPrefix::new(parts).rewrite(&mut req).unwrap();
+        req.uri().to_string()
+    }
+
+    #[test]
+    fn strips_leading_segments_and_keeps_the_query() {
+        assert_eq!(stripped(2, "/api/v1/items/42?page=2"), "/items/42?page=2");
+        assert_eq!(stripped(1, "/api/items/"), "/items/");
+        assert_eq!(stripped(0, "/api/items"), "/api/items");
+    }
+
+    #[test]
+    fn leaves_the_root_when_the_path_runs_out() {
+        assert_eq!(stripped(2, "/api/v1"), "/");
+        assert_eq!(stripped(2, "/api/v1/"), "/");
+        assert_eq!(stripped(3, "/api"), "/");
+        assert_eq!(stripped(1, "/"), "/");
+        assert_eq!(stripped(1, "//items"), "/items");
+    }
+}
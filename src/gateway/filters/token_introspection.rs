@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use http::header::{AUTHORIZATION, CONTENT_TYPE};
+use http::{HeaderName, HeaderValue, Request, Response, StatusCode};
+use http_body_util::Full;
+use hyper::body::Incoming;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{
+    bearer_challenge, bearer_token, header_name, request_id, Filterable, FilteredResult, Principal,
+};
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::http_client;
+
+const MAX_CACHED_TOKENS: usize = 100_000;
+
+/// Validates opaque bearer tokens with an RFC 7662 introspection endpoint.
+/// Answers are cached by token digest: active ones until the token expires
+/// (at most `cache_ttl`), inactive ones for `negative_ttl`.
+#[derive(Clone, Debug)]
+pub struct TokenIntrospection {
+    pub endpoint: String,
+    /// `Authorization` value for the endpoint, from the client credentials.
+    pub credentials: HeaderValue,
+    pub required_scopes: Vec<String>,
+    pub cache_ttl: Duration,
+    pub negative_ttl: Duration,
+    pub timeout: Duration,
+    pub headers: IntrospectionHeaders,
+    cache: Arc<Mutex<HashMap<[u8; 32], CachedAnswer>>>,
+}
+
+/// Upstream headers for the token's attributes; client values are dropped.
+#[derive(Clone, Debug)]
+pub struct IntrospectionHeaders {
+    pub subject: HeaderName,
+    pub scope: HeaderName,
+    pub client_id: HeaderName,
+}
+
+impl IntrospectionHeaders {
+    pub fn new(subject: &str, scope: &str, client_id: &str) -> Result<Self, GatewayError> {
+        Ok(Self {
+            subject: header_name(subject)?,
+            scope: header_name(scope)?,
+            client_id: header_name(client_id)?,
+        })
+    }
+}
+
+/// The parts of an introspection answer the gateway uses.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Introspection {
+    pub active: bool,
+    #[serde(default)]
+    pub scope: Option<String>,
+    #[serde(default)]
+    pub sub: Option<String>,
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub exp: Option<u64>,
+}
+
+#[derive(Debug)]
+struct CachedAnswer {
+    introspection: Arc<Introspection>,
+    expires: Instant,
+}
+
+impl TokenIntrospection {
+    pub fn new(
+        endpoint: String,
+        (client_id, client_secret): (&str, &str),
+        required_scopes: Vec<String>,
+        cache_ttl: Duration,
+        negative_ttl: Duration,
+        timeout: Duration,
+        headers: IntrospectionHeaders,
+    ) -> Result<Self, GatewayError> {
+        if !endpoint.starts_with("https://") && !endpoint.starts_with("http://") {
+            return Err(GatewayError::InvalidConfig(format!(
+                "invalid introspection endpoint '{}'",
+                endpoint
+            )));
+        }
+        // RFC 6749 section 2.3.1: form-encode, then Basic-encode
+        let encode =
+            |value: &str| form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>();
+        let basic = STANDARD.encode(format!("{}:{}", encode(client_id), encode(client_secret)));
+        let credentials = HeaderValue::from_str(&format!("Basic {}", basic))
+            .map_err(|_| GatewayError::InvalidConfig("invalid introspection credentials".into()))?;
+        Ok(Self {
+            endpoint,
+            credentials,
+            required_scopes,
+            cache_ttl,
+            negative_ttl,
+            timeout,
+            headers,
+            cache: Arc::default(),
+        })
+    }
+
+    async fn introspect(&self, token: &str, now: Instant) -> Result<Arc<Introspection>, String> {
+        let digest: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+        if let Some(cached) = self.cache.lock().unwrap().get(&digest) {
+            if cached.expires > now {
+                return Ok(cached.introspection.clone());
+            }
+        }
+
+        let body = form_urlencoded::Serializer::new(String::new())
+            .append_pair("token", token)
+            .append_pair("token_type_hint", "access_token")
+            .finish();
+        let request = Request::post(&self.endpoint)
+            .header(AUTHORIZATION, self.credentials.clone())
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Full::new(Bytes::from(body)))
+            .map_err(|err| err.to_string())?;
+        let answer = http_client::send(request, self.timeout).await?;
+        let introspection: Introspection =
+            serde_json::from_slice(&answer).map_err(|err| format!("bad answer: {}", err))?;
+
+        let ttl = if introspection.active {
+            introspection.exp.map_or(self.cache_ttl, |exp| seconds_until(exp).min(self.cache_ttl))
+        } else {
+            self.negative_ttl
+        };
+        let introspection = Arc::new(introspection);
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_TOKENS {
+            cache.retain(|_, cached| cached.expires > now);
+            if cache.len() >= MAX_CACHED_TOKENS {
+                cache.clear();
+            }
+        }
+        let expires = now + ttl;
+        cache.insert(digest, CachedAnswer { introspection: introspection.clone(), expires });
+        Ok(introspection)
+    }
+}
+
+fn seconds_until(exp: u64) -> Duration {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    Duration::from_secs(exp).saturating_sub(now)
+}
+
+#[async_trait]
+impl Filterable for TokenIntrospection {
+    async fn apply(&self, mut req: Request<Incoming>) -> FilteredResult {
+        self.check(&mut req, Instant::now()).await.map(|()| req)
+    }
+}
+
+impl TokenIntrospection {
+    /// `Err` holds the response turning the request away.
+    async fn check<B: Send>(
+        &self,
+        req: &mut Request<B>,
+        now: Instant,
+    ) -> Result<(), Response<BoxBody>> {
+        let Some(token) = bearer_token(req) else {
+            return Err(bearer_challenge(StatusCode::UNAUTHORIZED, None));
+        };
+        let introspection = match self.introspect(token, now).await {
+            Ok(introspection) => introspection,
+            Err(err) => {
+                eprintln!(
+                    "[{}] Token introspection at {} failed: {err}",
+                    request_id(req),
+                    self.endpoint
+                );
+                let response = Response::builder()
+                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                    .body(single_chunk_response_body("Token introspection unavailable"))
+                    .unwrap();
+                return Err(response);
+            }
+        };
+        // Cached answers outlive tokens without `exp` only up to the cache TTL
+        let expired = introspection.exp.is_some_and(|exp| seconds_until(exp).is_zero());
+        if !introspection.active || expired {
+            return Err(bearer_challenge(StatusCode::UNAUTHORIZED, Some("token is not active")));
+        }
+        let scopes: Vec<&str> =
+            introspection.scope.as_deref().unwrap_or_default().split_whitespace().collect();
+        if !self.required_scopes.iter().all(|required| scopes.contains(&required.as_str())) {
+            return Err(bearer_challenge(StatusCode::FORBIDDEN, Some("missing required scope")));
+        }
+
+        let attributes = [
+            (&self.headers.subject, &introspection.sub),
+            (&self.headers.scope, &introspection.scope),
+            (&self.headers.client_id, &introspection.client_id),
+        ];
+        for (header, value) in attributes {
+            match value.as_deref().and_then(|value| HeaderValue::from_str(value).ok()) {
+                Some(value) => req.headers_mut().insert(header.clone(), value),
+                None => req.headers_mut().remove(header),
+            };
+        }
+        let principal = introspection.sub.clone().or_else(|| introspection.client_id.clone());
+        if let Some(principal) = principal {
+            req.extensions_mut().insert(Principal(principal));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    use http_body_util::BodyExt;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Authorization header and form body of each introspection request.
+    type Calls = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Answers as an introspection endpoint would: `writer` tokens are active
+    /// with the write scope, `reader` tokens with read only and `short` ones
+    /// expire two seconds from now. Anything else is inactive.
+    async fn introspection_server() -> (SocketAddr, Calls) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let calls = Calls::default();
+        let recorded = calls.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let calls = recorded.clone();
+                let service = service_fn(move |req: Request<Incoming>| {
+                    let calls = calls.clone();
+                    async move {
+                        let authorization = req.headers()[AUTHORIZATION].to_str().unwrap().into();
+                        let body = req.into_body().collect().await.unwrap().to_bytes();
+                        let body = String::from_utf8(body.to_vec()).unwrap();
+                        let answer = answer(&body);
+                        calls.lock().unwrap().push((authorization, body));
+                        Ok::<_, Infallible>(Response::new(Full::new(Bytes::from(answer))))
+                    }
+                });
+                tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), service));
+            }
+        });
+        (address, calls)
+    }
+
+    fn answer(body: &str) -> String {
+        let (_, token) =
+            form_urlencoded::parse(body.as_bytes()).find(|(name, _)| name == "token").unwrap();
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let scope = match token.as_ref() {
+            "writer" | "short" => "read write",
+            "reader" => "read",
+            _ => return r#"{"active":false}"#.to_string(),
+        };
+        let exp = if token == "short" { now + 2 } else { now + 3600 };
+        format!(
+            r#"{{"active":true,"scope":"{}","sub":"alice","client_id":"app","exp":{}}}"#,
+            scope, exp
+        )
+    }
+
+    fn filter(address: SocketAddr, required_scopes: &[&str]) -> TokenIntrospection {
+        TokenIntrospection::new(
+            format!("http://{}/introspect", address),
+            ("gateway", "s3cret"),
+            required_scopes.iter().map(|scope| scope.to_string()).collect(),
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+            Duration::from_secs(1),
+            IntrospectionHeaders::new("x-user", "x-scope", "x-client-id").unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn request(token: &str) -> Request<()> {
+        Request::get("/")
+            .header(AUTHORIZATION, format!("Bearer {}", token))
+            .header("x-user", "mallory")
+            .body(())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn passes_active_tokens_with_their_attributes() {
+        let now = Instant::now();
+        let (address, calls) = introspection_server().await;
+        let filter = filter(address, &["write"]);
+
+        let mut req = request("writer");
+        assert!(filter.check(&mut req, now).await.is_ok());
+        assert_eq!(req.headers()["x-user"], "alice");
+        assert_eq!(req.headers()["x-scope"], "read write");
+        assert_eq!(req.headers()["x-client-id"], "app");
+        assert_eq!(req.extensions().get::<Principal>().unwrap().0, "alice");
+
+        let calls = calls.lock().unwrap();
+        let (authorization, body) = &calls[0];
+        assert_eq!(authorization, &format!("Basic {}", STANDARD.encode("gateway:s3cret")));
+        assert_eq!(body, "token=writer&token_type_hint=access_token");
+    }
+
+    #[tokio::test]
+    async fn rejects_inactive_tokens_and_missing_scopes() {
+        let now = Instant::now();
+        let (address, calls) = introspection_server().await;
+        let filter = filter(address, &["write"]);
+
+        for _ in 0..2 {
+            let rejection = filter.check(&mut request("revoked"), now).await.unwrap_err();
+            assert_eq!(rejection.status(), StatusCode::UNAUTHORIZED);
+        }
+        let rejection = filter.check(&mut request("reader"), now).await.unwrap_err();
+        assert_eq!(rejection.status(), StatusCode::FORBIDDEN);
+        let mut anonymous = Request::get("/").body(()).unwrap();
+        let rejection = filter.check(&mut anonymous, now).await.unwrap_err();
+        assert_eq!(rejection.status(), StatusCode::UNAUTHORIZED);
+        // The second inactive answer came from the cache
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn caches_answers_until_the_token_expires() {
+        let (address, calls) = introspection_server().await;
+        let filter = filter(address, &[]);
+        let start = Instant::now();
+
+        assert!(filter.check(&mut request("short"), start).await.is_ok());
+        assert!(filter.check(&mut request("short"), start).await.is_ok());
+        assert_eq!(calls.lock().unwrap().len(), 1);
+
+        // The answer was cached for at most the two seconds the token had left
+        let later = start + Duration::from_millis(2100);
+        assert!(filter.check(&mut request("short"), later).await.is_ok());
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fails_when_the_endpoint_is_unreachable() {
+        let now = Instant::now();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let rejection = filter(address, &[]).check(&mut request("writer"), now).await.unwrap_err();
+        assert_eq!(rejection.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::Request;
+use http_body_util::{BodyExt, Full, Limited};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::{connect::HttpConnector, Client};
+use hyper_util::rt::TokioExecutor;
+
+/// Largest response body the gateway reads for its own calls.
+const MAX_BODY: usize = 1 << 20;
+
+/// Client for calls the gateway makes on its own behalf, such as fetching
+/// signing keys. Unlike `forward_request` it pools connections and speaks TLS.
+static CLIENT: LazyLock<Client<HttpsConnector<HttpConnector>, Full<Bytes>>> = LazyLock::new(|| {
+    let connector =
+        HttpsConnectorBuilder::new().with_webpki_roots().https_or_http().enable_http1().build();
+    Client::builder(TokioExecutor::new()).build(connector)
+});
+
+/// Sends a request and returns the body of a 2xx response.
+pub async fn send(request: Request<Full<Bytes>>, timeout: Duration) -> Result<Bytes, String> {
+    let uri = request.uri().clone();
+    let exchange = async {
+        let response = CLIENT.request(request).await.map_err(|err| err.to_string())?;
+        let status = response.status();
+        let body = Limited::new(response.into_body(), MAX_BODY);
+        let body = body.collect().await.map_err(|err| err.to_string())?.to_bytes();
+        Ok::<_, String>((status, body))
+    };
+    let (status, body) = tokio::time::timeout(timeout, exchange)
+        .await
+        .map_err(|_| format!("{} timed out", uri))?
+        .map_err(|err| format!("{}: {}", uri, err))?;
+    if !status.is_success() {
+        return Err(format!("{} answered {}", uri, status));
+    }
+    Ok(body)
+}
+
+/// GETs a URL and returns the body of a 2xx response.
+pub async fn get(url: &str, timeout: Duration) -> Result<Bytes, String> {
+    let request = Request::get(url)
+        .body(Full::default())
+        .map_err(|err| format!("invalid URL '{}': {}", url, err))?;
+    send(request, timeout).await
+}
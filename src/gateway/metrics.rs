@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::{Method, StatusCode};
+
+use crate::gateway::filters::concurrency_limit::limiter_stats;
+
+/// Process-wide metrics, kept across config reloads.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// Upper bounds in seconds of the request latency histogram.
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Counters and gauges rendered in the Prometheus text format. Labels are
+/// route ids, methods and status classes, so their number stays bounded.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    requests: Mutex<HashMap<(String, &'static str, &'static str), Latencies>>,
+    upstream_errors: Mutex<HashMap<(String, &'static str), u64>>,
+    filter_responses: Mutex<HashMap<(String, &'static str, &'static str), u64>>,
+    upstream_connections: Mutex<HashMap<String, i64>>,
+    active_connections: AtomicI64,
+    reloads_succeeded: AtomicU64,
+    reloads_failed: AtomicU64,
+    /// Seconds since the epoch; zero until the first successful load.
+    last_load: AtomicU64,
+}
+
+#[derive(Debug, Default)]
+struct Latencies {
+    /// Non-cumulative counts per bucket, the last one for `+Inf`.
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    count: u64,
+    sum: f64,
+}
+
+/// Counts an open connection for as long as it is alive.
+pub struct ConnectionGuard<'a> {
+    metrics: &'a Metrics,
+    upstream: Option<String>,
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        match self.upstream.take() {
+            None => {
+                self.metrics.active_connections.fetch_sub(1, Ordering::Relaxed);
+            }
+            Some(upstream) => {
+                let mut connections = self.metrics.upstream_connections.lock().unwrap();
+                if let Some(open) = connections.get_mut(&upstream) {
+                    *open -= 1;
+                }
+            }
+        }
+    }
+}
+
+impl Metrics {
+    /// A request answered by the gateway, timed until the response headers.
+    pub fn request(
+        &self,
+        route: Option<&str>,
+        method: &Method,
+        status: StatusCode,
+        took: Duration,
+    ) {
+        let key = (route_label(route), method_label(method), status_class(status));
+        let mut requests = self.requests.lock().unwrap();
+        let latencies = requests.entry(key).or_default();
+        let seconds = took.as_secs_f64();
+        let bucket = LATENCY_BUCKETS.iter().position(|&bound| seconds <= bound);
+        latencies.buckets[bucket.unwrap_or(LATENCY_BUCKETS.len())] += 1;
+        latencies.count += 1;
+        latencies.sum += seconds;
+    }
+
+    /// A `502` from `forward_request`, by reason.
+    pub fn upstream_error(&self, route: Option<&str>, reason: &'static str) {
+        *self.upstream_errors.lock().unwrap().entry((route_label(route), reason)).or_default() += 1;
+    }
+
+    /// A request a filter answered itself instead of passing it on. Redirects
+    /// are where the request was meant to go, not rejections, so they only
+    /// show in the request metrics.
+    pub fn filter_response(&self, route: &str, filter: &'static str, status: StatusCode) {
+        if status.is_redirection() {
+            return;
+        }
+        let key = (route.to_string(), filter, status_class(status));
+        *self.filter_responses.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    pub fn client_connection(&self) -> ConnectionGuard<'_> {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard { metrics: self, upstream: None }
+    }
+
+    pub fn upstream_connection(&self, upstream: &str) -> ConnectionGuard<'_> {
+        *self.upstream_connections.lock().unwrap().entry(upstream.to_string()).or_default() += 1;
+        ConnectionGuard { metrics: self, upstream: Some(upstream.to_string()) }
+    }
+
+    /// The config was loaded at startup or by the watcher.
+    pub fn config_loaded(&self) {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        self.last_load.store(now.as_secs(), Ordering::Relaxed);
+    }
+
+    pub fn config_reloaded(&self, succeeded: bool) {
+        if succeeded {
+            self.reloads_succeeded.fetch_add(1, Ordering::Relaxed);
+            self.config_loaded();
+        } else {
+            self.reloads_failed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The Prometheus text exposition format, version 0.0.4.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "gateway_requests_total", "counter", "Requests answered by the gateway.");
+        let requests = self.requests.lock().unwrap();
+        let mut keys: Vec<_> = requests.keys().collect();
+        keys.sort();
+        for key @ (route, method, class) in &keys {
+            let labels = labels(&[("route", route), ("method", method), ("status_class", class)]);
+            let _ = writeln!(out, "gateway_requests_total{{{}}} {}", labels, requests[key].count);
+        }
+        header(
+            &mut out,
+            "gateway_request_duration_seconds",
+            "histogram",
+            "Time until the response headers were ready.",
+        );
+        for key @ (route, method, class) in &keys {
+            let latencies = &requests[key];
+            let labels = labels(&[("route", route), ("method", method), ("status_class", class)]);
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(latencies.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "gateway_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "gateway_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, latencies.count
+            );
+            let _ = writeln!(
+                out,
+                "gateway_request_duration_seconds_sum{{{}}} {}",
+                labels, latencies.sum
+            );
+            let _ = writeln!(
+                out,
+                "gateway_request_duration_seconds_count{{{}}} {}",
+                labels, latencies.count
+            );
+        }
+        drop(requests);
+
+        header(
+            &mut out,
+            "gateway_upstream_errors_total",
+            "counter",
+            "Requests answered with 502 because the upstream could not be reached.",
+        );
+        for ((route, reason), count) in sorted(&self.upstream_errors.lock().unwrap()) {
+            let labels = labels(&[("route", &route), ("reason", reason)]);
+            let _ = writeln!(out, "gateway_upstream_errors_total{{{}}} {}", labels, count);
+        }
+
+        header(
+            &mut out,
+            "gateway_filter_responses_total",
+            "counter",
+            "Requests a filter rejected or failed instead of passing them on.",
+        );
+        for ((route, filter, class), count) in sorted(&self.filter_responses.lock().unwrap()) {
+            let labels = labels(&[("route", &route), ("filter", filter), ("status_class", class)]);
+            let _ = writeln!(out, "gateway_filter_responses_total{{{}}} {}", labels, count);
+        }
+
+        header(&mut out, "gateway_active_connections", "gauge", "Open client connections.");
+        let active = self.active_connections.load(Ordering::Relaxed);
+        let _ = writeln!(out, "gateway_active_connections {}", active);
+        header(&mut out, "gateway_upstream_connections", "gauge", "Open upstream connections.");
+        for (upstream, open) in sorted(&self.upstream_connections.lock().unwrap()) {
+            let labels = labels(&[("upstream", &upstream)]);
+            let _ = writeln!(out, "gateway_upstream_connections{{{}}} {}", labels, open);
+        }
+
+        let limiters = limiter_stats();
+        let gauges = [
+            ("gateway_concurrency_limit", "gauge", "Current limit of a ConcurrencyLimit group."),
+            ("gateway_concurrency_in_flight", "gauge", "Requests holding a concurrency slot."),
+            ("gateway_concurrency_queued", "gauge", "Requests waiting for a concurrency slot."),
+            (
+                "gateway_concurrency_rejected_total",
+                "counter",
+                "Requests turned away by a ConcurrencyLimit group.",
+            ),
+        ];
+        for (position, (name, kind, help)) in gauges.into_iter().enumerate() {
+            header(&mut out, name, kind, help);
+            for (group, stats) in &limiters {
+                let value = [stats.limit, stats.in_flight, stats.queued, stats.rejected][position];
+                let _ = writeln!(out, "{}{{{}}} {}", name, labels(&[("group", group)]), value);
+            }
+        }
+
+        header(&mut out, "gateway_config_reloads_total", "counter", "Config reloads by result.");
+        let reloads = [
+            ("success", self.reloads_succeeded.load(Ordering::Relaxed)),
+            ("failure", self.reloads_failed.load(Ordering::Relaxed)),
+        ];
+        for (result, count) in reloads {
+            let _ =
+                writeln!(out, "gateway_config_reloads_total{{result=\"{}\"}} {}", result, count);
+        }
+        header(
+            &mut out,
+            "gateway_config_last_reload_success_timestamp_seconds",
+            "gauge",
+            "When the config in use was loaded.",
+        );
+        let last_load = self.last_load.load(Ordering::Relaxed);
+        let _ = writeln!(out, "gateway_config_last_reload_success_timestamp_seconds {}", last_load);
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let escape =
+        |value: &str| value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn sorted<K: Ord + Clone, V: Copy>(map: &HashMap<K, V>) -> Vec<(K, V)> {
+    let mut entries: Vec<_> = map.iter().map(|(key, value)| (key.clone(), *value)).collect();
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+    entries
+}
+
+/// Requests that matched no route are counted under `unmatched`.
+fn route_label(route: Option<&str>) -> String {
+    route.unwrap_or("unmatched").to_string()
+}
+
+/// Unusual methods are lumped together so clients cannot add label values.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        Method::OPTIONS => "OPTIONS",
+        _ => "other",
+    }
+}
+
+fn status_class(status: StatusCode) -> &'static str {
+    match status.as_u16() / 100 {
+        1 => "1xx",
+        2 => "2xx",
+        3 => "3xx",
+        4 => "4xx",
+        _ => "5xx",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(rendered: &str, metric: &str) -> Vec<String> {
+        let prefix = format!("{}{{", metric);
+        rendered.lines().filter(|line| line.starts_with(&prefix)).map(str::to_string).collect()
+    }
+
+    #[test]
+    fn renders_request_counts_and_cumulative_histograms() {
+        let metrics = Metrics::default();
+        let ok = StatusCode::OK;
+        metrics.request(Some("api"), &Method::GET, ok, Duration::from_millis(3));
+        metrics.request(Some("api"), &Method::GET, StatusCode::CREATED, Duration::from_millis(40));
+        metrics.request(Some("api"), &Method::GET, ok, Duration::from_secs(30));
+        metrics.request(
+            None,
+            &Method::from_bytes(b"PURGE").unwrap(),
+            StatusCode::NOT_FOUND,
+            Duration::ZERO,
+        );
+        let rendered = metrics.render();
+
+        assert_eq!(
+            lines(&rendered, "gateway_requests_total"),
+            [
+                r#"gateway_requests_total{route="api",method="GET",status_class="2xx"} 3"#,
+                r#"gateway_requests_total{route="unmatched",method="other",status_class="4xx"} 1"#,
+            ]
+        );
+        let api = r#"route="api",method="GET",status_class="2xx""#;
+        let buckets = lines(&rendered, "gateway_request_duration_seconds_bucket");
+        assert_eq!(buckets.len(), 2 * (LATENCY_BUCKETS.len() + 1));
+        for (bound, count) in [("0.005", 1), ("0.025", 1), ("0.05", 2), ("10", 2), ("+Inf", 3)] {
+            let line = format!(
+                "gateway_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                api, bound, count
+            );
+            assert!(buckets.contains(&line), "missing {}", line);
+        }
+        let sum = format!("gateway_request_duration_seconds_sum{{{}}} 30.043", api);
+        assert!(lines(&rendered, "gateway_request_duration_seconds_sum").contains(&sum));
+        assert!(rendered.contains("# TYPE gateway_request_duration_seconds histogram\n"));
+    }
+
+    #[test]
+    fn renders_errors_connections_and_reloads() {
+        let metrics = Metrics::default();
+        metrics.upstream_error(Some("api"), "timeout");
+        metrics.filter_response("api", "JwtAuth", StatusCode::UNAUTHORIZED);
+        metrics.filter_response("api", "JwtAuth", StatusCode::FORBIDDEN);
+        metrics.filter_response("api", "RedirectTo", StatusCode::FOUND);
+        metrics.config_reloaded(true);
+        metrics.config_reloaded(false);
+        let client = metrics.client_connection();
+        let upstream = metrics.upstream_connection("10.0.0.1:80");
+        let _second = metrics.upstream_connection("10.0.0.1:80");
+        drop(upstream);
+        let rendered = metrics.render();
+        drop(client);
+
+        let expected = [
+            r#"gateway_upstream_errors_total{route="api",reason="timeout"} 1"#,
+            r#"gateway_filter_responses_total{route="api",filter="JwtAuth",status_class="4xx"} 2"#,
+            "gateway_active_connections 1",
+            r#"gateway_upstream_connections{upstream="10.0.0.1:80"} 1"#,
+            r#"gateway_config_reloads_total{result="success"} 1"#,
+            r#"gateway_config_reloads_total{result="failure"} 1"#,
+        ];
+        for line in expected {
+            assert!(rendered.lines().any(|rendered| rendered == line), "missing {}", line);
+        }
+        assert!(!rendered.contains("RedirectTo"));
+        assert!(!rendered.contains("gateway_config_last_reload_success_timestamp_seconds 0\n"));
+        assert!(metrics.render().contains("gateway_active_connections 0\n"));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let escaped = labels(&[("route", "a\"b\\c\nd")]);
+        assert_eq!(escaped, r#"route="a\"b\\c\nd""#);
+    }
+}
@@ -6,12 +6,27 @@ pub mod cookie;
 pub mod host;
 pub mod remote_addr;
 pub mod x_forwarded_remote_addr;
+pub mod weight;
+pub mod all_of;
+pub mod any_of;
+pub mod not;
+pub mod value_match;
 
+use std::collections::HashMap;
 use std::fmt::Debug;
 use hyper::Request;
 
+/// Variables captured by the matched route's templates, such as `{sub}` in a
+/// host pattern. Stored in the request extensions once a route matches.
+#[derive(Clone, Debug, Default)]
+pub struct UriTemplateVariables(pub HashMap<String, String>);
+
 pub trait Evaluable<T>: Debug + Send + Sync {
     fn evaluate(&self, request: &Request<T>) -> bool;
+
+    /// Adds the template variables this predicate extracts from a request it
+    /// matches. Most predicates have none.
+    fn capture(&self, _request: &Request<T>, _variables: &mut UriTemplateVariables) {}
 }
 
 #[derive(Clone, Debug)]
@@ -24,6 +39,10 @@ pub enum Predicate {
     Host(HostPredicate),
     RemoteAddr(RemoteAddrPredicate),
     XForwardedRemoteAddr(XForwardedRemoteAddrPredicate),
+    Weight(WeightPredicate),
+    AllOf(AllOfPredicate),
+    AnyOf(AnyOfPredicate),
+    Not(NotPredicate),
 }
 
 impl <T> Evaluable<T> for Predicate {
@@ -37,6 +56,41 @@ impl <T> Evaluable<T> for Predicate {
             Predicate::Host(p) => p.evaluate(request),
             Predicate::RemoteAddr(p) => p.evaluate(request),
             Predicate::XForwardedRemoteAddr(p) => p.evaluate(request),
+            Predicate::Weight(p) => p.evaluate(request),
+            Predicate::AllOf(p) => p.evaluate(request),
+            Predicate::AnyOf(p) => p.evaluate(request),
+            Predicate::Not(p) => p.evaluate(request),
+        }
+    }
+
+    fn capture(&self, request: &Request<T>, variables: &mut UriTemplateVariables) {
+        match self {
+            Predicate::Path(p) => p.capture(request, variables),
+            Predicate::Host(p) => p.capture(request, variables),
+            Predicate::AllOf(p) => p.capture(request, variables),
+            Predicate::AnyOf(p) => p.capture(request, variables),
+            _ => {}
+        }
+    }
+}
+
+impl Predicate {
+    /// Predicates nested directly inside a combinator, empty for leaf predicates.
+    pub fn children(&self) -> &[Predicate] {
+        match self {
+            Predicate::AllOf(p) => &p.predicates,
+            Predicate::AnyOf(p) => &p.predicates,
+            Predicate::Not(p) => std::slice::from_ref(p.predicate.as_ref()),
+            _ => &[],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> &mut [Predicate] {
+        match self {
+            Predicate::AllOf(p) => &mut p.predicates,
+            Predicate::AnyOf(p) => &mut p.predicates,
+            Predicate::Not(p) => std::slice::from_mut(p.predicate.as_mut()),
+            _ => &mut [],
         }
     }
 }
@@ -49,3 +103,8 @@ pub use cookie::CookiePredicate;
 pub use host::HostPredicate;
 pub use remote_addr::RemoteAddrPredicate;
 pub use x_forwarded_remote_addr::XForwardedRemoteAddrPredicate;
+pub use weight::{StickyKey, WeightPredicate};
+pub use all_of::AllOfPredicate;
+pub use any_of::AnyOfPredicate;
+pub use not::NotPredicate;
+pub use value_match::{Repeated, ValueMatch};
@@ -0,0 +1,48 @@
+use hyper::Request;
+
+use super::{Evaluable, Predicate, UriTemplateVariables};
+
+/// Matches when every nested predicate matches.
+#[derive(Clone, Debug)]
+pub struct AllOfPredicate {
+    pub predicates: Vec<Predicate>,
+}
+
+impl<T> Evaluable<T> for AllOfPredicate {
+    fn evaluate(&self, request: &Request<T>) -> bool {
+        self.predicates.iter().all(|predicate| predicate.evaluate(request))
+    }
+    fn capture(&self, request: &Request<T>, variables: &mut UriTemplateVariables) {
+        for predicate in &self.predicates {
+            predicate.capture(request, variables);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::predicates::method::MethodPredicate;
+    use crate::gateway::predicates::path::PathPredicate;
+
+    fn request(method: &str, path: &str) -> Request<()> {
+        Request::builder().method(method).uri(path).body(()).unwrap()
+    }
+
+    #[test]
+    fn matches_only_when_every_predicate_matches() {
+        let predicate = AllOfPredicate {
+            predicates: vec![
+                Predicate::Method(MethodPredicate { method: "GET".to_string() }),
+                Predicate::Path(PathPredicate::new("/items/{id}", true).unwrap()),
+            ],
+        };
+        assert!(predicate.evaluate(&request("GET", "/items/42")));
+        assert!(!predicate.evaluate(&request("POST", "/items/42")));
+        assert!(!predicate.evaluate(&request("GET", "/orders/42")));
+
+        let mut variables = UriTemplateVariables::default();
+        predicate.capture(&request("GET", "/items/42"), &mut variables);
+        assert_eq!(variables.0["id"], "42");
+    }
+}
@@ -0,0 +1,59 @@
+use hyper::Request;
+
+use super::{Evaluable, Predicate, UriTemplateVariables};
+
+/// Matches when at least one nested predicate matches.
+#[derive(Clone, Debug)]
+pub struct AnyOfPredicate {
+    pub predicates: Vec<Predicate>,
+}
+
+impl<T> Evaluable<T> for AnyOfPredicate {
+    fn evaluate(&self, request: &Request<T>) -> bool {
+        self.predicates.iter().any(|predicate| predicate.evaluate(request))
+    }
+    /// Only the first matching alternative contributes variables.
+    fn capture(&self, request: &Request<T>, variables: &mut UriTemplateVariables) {
+        if let Some(predicate) = self.predicates.iter().find(|p| p.evaluate(request)) {
+            predicate.capture(request, variables);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::predicates::path::PathPredicate;
+
+    fn path(pattern: &str) -> Predicate {
+        Predicate::Path(PathPredicate::new(pattern, true).unwrap())
+    }
+
+    fn request(path: &str) -> Request<()> {
+        Request::builder().uri(path).body(()).unwrap()
+    }
+
+    #[test]
+    fn matches_when_any_predicate_matches() {
+        let predicate = AnyOfPredicate { predicates: vec![path("/items/**"), path("/orders/**")] };
+        assert!(predicate.evaluate(&request("/items/1")));
+        assert!(predicate.evaluate(&request("/orders/1")));
+        assert!(!predicate.evaluate(&request("/users/1")));
+    }
+
+    #[test]
+    fn captures_from_the_first_matching_alternative_only() {
+        let predicate = AnyOfPredicate {
+            predicates: vec![path("/items/{first}"), path("/{second}/{id}"), path("/{third}/**")],
+        };
+        let mut variables = UriTemplateVariables::default();
+        predicate.capture(&request("/orders/1"), &mut variables);
+        assert_eq!(variables.0.len(), 2);
+        assert_eq!(variables.0["second"], "orders");
+        assert_eq!(variables.0["id"], "1");
+
+        let mut variables = UriTemplateVariables::default();
+        predicate.capture(&request("/"), &mut variables);
+        assert!(variables.0.is_empty());
+    }
+}
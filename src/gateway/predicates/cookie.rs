@@ -1,49 +1,97 @@
-use std::collections::HashMap;
-use regex::Regex;
+use http::HeaderMap;
+use hyper::Request;
 
+use super::value_match::{Repeated, ValueMatch};
 use super::Evaluable;
-use hyper::Request;
 
 #[derive(Clone, Debug)]
 pub struct CookiePredicate {
     pub name: String,
-    pub value: Regex, // Precompiled regex
+    pub matcher: ValueMatch,
+    pub repeated: Repeated,
 }
 
 impl CookiePredicate {
-    pub fn new(name: String, value: String) -> Self {
-        Self {
-            name,
-            value: Regex::new(&value).unwrap(), // Compile regex once
-        }
+    pub fn new(name: String, matcher: ValueMatch, repeated: Repeated) -> Self {
+        Self { name, matcher, repeated }
     }
 }
 
-impl <T> Evaluable<T> for CookiePredicate {
-
+impl<T> Evaluable<T> for CookiePredicate {
     fn evaluate(&self, request: &Request<T>) -> bool {
-        if let Some(cookie_header) = request.headers().get("cookie") {
-            if let Ok(cookie_str) = cookie_header.to_str() {
-                let cookies = parse_cookies(cookie_str);
-                if let Some(cookie_value) = cookies.get(self.name.as_str()) {
-                    return self.value.is_match(cookie_value);
-                }
-            }
-        }
-        false
+        let values = cookie_values(request.headers(), &self.name).map(Some);
+        self.matcher.matches(values, self.repeated)
     }
 }
 
-fn parse_cookies(cookie_str: &str) -> HashMap<&str, &str> {
-    cookie_str
-        .split(';')
-        .filter_map(|c| {
-            let mut iter = c.split('=');
-            if let (Some(name), Some(value)) = (iter.next(), iter.next()) {
-                Some((name.trim(), value.trim()))
-            } else {
-                None
+/// Every value sent for a cookie, across all `Cookie` headers, per RFC 6265:
+/// pairs split on `;`, name and value split on the first `=`, and surrounding
+/// double quotes removed from the value.
+pub(super) fn cookie_values<'a>(
+    headers: &'a HeaderMap,
+    name: &'a str,
+) -> impl Iterator<Item = &'a str> + 'a {
+    headers
+        .get_all(http::header::COOKIE)
+        .iter()
+        .filter_map(|header| header.to_str().ok())
+        .flat_map(|header| header.split(';'))
+        .filter_map(move |pair| {
+            let (key, value) = pair.split_once('=')?;
+            if key.trim() != name {
+                return None;
             }
+            let value = value.trim();
+            Some(value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value))
         })
-        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(headers: &[&str]) -> Request<()> {
+        let mut request = Request::builder();
+        for header in headers {
+            request = request.header("cookie", *header);
+        }
+        request.body(()).unwrap()
+    }
+
+    fn values<'a>(request: &'a Request<()>, name: &'a str) -> Vec<&'a str> {
+        cookie_values(request.headers(), name).collect()
+    }
+
+    #[test]
+    fn splits_pairs_on_the_first_equals_sign() {
+        let request = request(&["theme=dark; token=a=b==;session = 42 ;flag"]);
+        assert_eq!(values(&request, "theme"), ["dark"]);
+        assert_eq!(values(&request, "token"), ["a=b=="]);
+        assert_eq!(values(&request, "session"), ["42"]);
+        assert!(values(&request, "flag").is_empty());
+        assert!(values(&request, "Theme").is_empty());
+    }
+
+    #[test]
+    fn strips_quotes_around_values() {
+        let request = request(&[r#"quoted="a b"; half="open; empty="""#]);
+        assert_eq!(values(&request, "quoted"), ["a b"]);
+        assert_eq!(values(&request, "half"), ["\"open"]);
+        assert_eq!(values(&request, "empty"), [""]);
+    }
+
+    #[test]
+    fn collects_repeated_cookies_across_headers() {
+        let request = request(&["id=1; id=2", "id=3"]);
+        assert_eq!(values(&request, "id"), ["1", "2", "3"]);
+
+        let exact =
+            |value: &str| ValueMatch::Exact { value: value.to_string(), ignore_case: false };
+        let any = CookiePredicate::new("id".to_string(), exact("3"), Repeated::Any);
+        let all = CookiePredicate::new("id".to_string(), exact("3"), Repeated::All);
+        assert!(any.evaluate(&request));
+        assert!(!all.evaluate(&request));
+        let absent = CookiePredicate::new("sid".to_string(), ValueMatch::Absent, Repeated::Any);
+        assert!(absent.evaluate(&request));
+    }
 }
@@ -1,18 +1,116 @@
-use regex::Regex;
+use http::HeaderName;
 
 use hyper::Request;
 
+use super::value_match::{Repeated, ValueMatch};
 use super::Evaluable;
+use crate::gateway::errors::GatewayError;
 
+/// Matches a request header by presence, absence or value.
+///
+/// A repeated header is checked value by value according to `repeated`, and
+/// values that are not valid UTF-8 never match.
 #[derive(Clone, Debug)]
 pub struct HeaderPredicate {
-    pub header: String,
-    pub value: String,
+    pub header: HeaderName,
+    pub matcher: ValueMatch,
+    pub repeated: Repeated,
 }
 
-impl <T> Evaluable<T> for HeaderPredicate {
+impl HeaderPredicate {
+    pub fn new(
+        header: &str,
+        matcher: ValueMatch,
+        repeated: Repeated,
+    ) -> Result<Self, GatewayError> {
+        let header = header.parse::<HeaderName>().map_err(|_| {
+            GatewayError::InvalidConfig(format!("invalid header name '{}'", header))
+        })?;
+        Ok(Self { header, matcher, repeated })
+    }
+}
+
+impl<T> Evaluable<T> for HeaderPredicate {
     fn evaluate(&self, request: &Request<T>) -> bool {
-        let re = Regex::new(&self.value).unwrap();
-        request.headers().get(&self.header).map_or(false, |v| re.is_match(v.to_str().unwrap()))
+        let values = request.headers().get_all(&self.header).iter().map(|v| v.to_str().ok());
+        self.matcher.matches(values, self.repeated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::HeaderValue;
+    use regex::Regex;
+
+    fn request(values: &[&[u8]]) -> Request<()> {
+        let mut request = Request::new(());
+        for value in values {
+            request.headers_mut().append("x-version", HeaderValue::from_bytes(value).unwrap());
+        }
+        request
+    }
+
+    fn predicate(matcher: ValueMatch, repeated: Repeated) -> HeaderPredicate {
+        HeaderPredicate::new("X-Version", matcher, repeated).unwrap()
+    }
+
+    fn exact(value: &str, ignore_case: bool) -> ValueMatch {
+        ValueMatch::Exact { value: value.to_string(), ignore_case }
+    }
+
+    #[test]
+    fn checks_presence_and_absence() {
+        let present = predicate(ValueMatch::Present, Repeated::Any);
+        let absent = predicate(ValueMatch::Absent, Repeated::Any);
+        assert!(present.evaluate(&request(&[b""])));
+        assert!(!absent.evaluate(&request(&[b""])));
+        assert!(!present.evaluate(&request(&[])));
+        assert!(absent.evaluate(&request(&[])));
+    }
+
+    #[test]
+    fn compares_exact_prefix_and_regex_values() {
+        assert!(predicate(exact("v2", false), Repeated::Any).evaluate(&request(&[b"v2"])));
+        assert!(!predicate(exact("v2", false), Repeated::Any).evaluate(&request(&[b"V2"])));
+        assert!(predicate(exact("v2", true), Repeated::Any).evaluate(&request(&[b"V2"])));
+        assert!(!predicate(exact("v2", false), Repeated::Any).evaluate(&request(&[])));
+
+        let prefix = |ignore_case| ValueMatch::Prefix { value: "beta".to_string(), ignore_case };
+        assert!(predicate(prefix(false), Repeated::Any).evaluate(&request(&[b"beta-7"])));
+        assert!(!predicate(prefix(false), Repeated::Any).evaluate(&request(&[b"BETA-7"])));
+        assert!(predicate(prefix(true), Repeated::Any).evaluate(&request(&[b"BETA-7"])));
+        assert!(!predicate(prefix(false), Repeated::Any).evaluate(&request(&[b"bet"])));
+
+        let regex = ValueMatch::Regex(Regex::new("^v[0-9]+$").unwrap());
+        assert!(predicate(regex.clone(), Repeated::Any).evaluate(&request(&[b"v12"])));
+        assert!(!predicate(regex, Repeated::Any).evaluate(&request(&[b"v12-rc"])));
+    }
+
+    #[test]
+    fn checks_repeated_values_by_any_or_all() {
+        let both = request(&[b"v1", b"v2"]);
+        assert!(predicate(exact("v2", false), Repeated::Any).evaluate(&both));
+        assert!(!predicate(exact("v2", false), Repeated::All).evaluate(&both));
+        assert!(predicate(exact("v2", false), Repeated::All).evaluate(&request(&[b"v2", b"v2"])));
+    }
+
+    #[test]
+    fn never_matches_values_that_are_not_utf8() {
+        let binary = request(&[b"v2\xff"]);
+        assert!(predicate(ValueMatch::Present, Repeated::Any).evaluate(&binary));
+        let any = ValueMatch::Regex(Regex::new("").unwrap());
+        assert!(!predicate(any.clone(), Repeated::Any).evaluate(&binary));
+        let prefix = ValueMatch::Prefix { value: "v2".to_string(), ignore_case: false };
+        assert!(!predicate(prefix, Repeated::Any).evaluate(&binary));
+
+        let mixed = request(&[b"v2", b"\xff"]);
+        assert!(predicate(exact("v2", false), Repeated::Any).evaluate(&mixed));
+        assert!(!predicate(any, Repeated::All).evaluate(&mixed));
+    }
+
+    #[test]
+    fn rejects_invalid_header_names() {
+        assert!(HeaderPredicate::new("bad header", ValueMatch::Present, Repeated::Any).is_err());
     }
 }
@@ -2,18 +2,216 @@ use regex::Regex;
 
 use hyper::Request;
 
-use super::Evaluable;
+use super::{Evaluable, UriTemplateVariables};
+use crate::gateway::errors::GatewayError;
+
+/// Spring-style host template such as `{sub}.example.org` or `**.example.org`.
+///
+/// Labels are matched case-insensitively: `*` matches within a label, `**`
+/// matches any number of whole labels and `{name}` captures one label.
+#[derive(Clone, Debug)]
+pub struct HostPattern {
+    pub pattern: String,
+    regex: Regex,
+}
+
+impl HostPattern {
+    pub fn parse(pattern: &str) -> Result<Self, GatewayError> {
+        let invalid = |reason: &str| {
+            GatewayError::InvalidConfig(format!("invalid host pattern '{}': {}", pattern, reason))
+        };
+        if pattern.is_empty() {
+            return Err(invalid("empty pattern"));
+        }
+
+        let mut source = String::from("(?i)^");
+        let labels: Vec<&str> = pattern.split('.').collect();
+        for (position, label) in labels.iter().enumerate() {
+            let last = position + 1 == labels.len();
+            if *label == "**" {
+                // Zero or more labels, each carrying the dot that joins it
+                match (position, last) {
+                    (0, true) => source.push_str(".+"),
+                    (0, false) => source.push_str(r"(?:[^.]+\.)*"),
+                    (_, true) => source.push_str(r"(?:\.[^.]+)*"),
+                    (_, false) => source.push_str(r"(?:\.[^.]+)*\."),
+                }
+                continue;
+            }
+            let mut rest = *label;
+            while let Some(c) = rest.chars().next() {
+                if c == '{' {
+                    let end = rest.find('}').ok_or_else(|| invalid("unclosed '{'"))?;
+                    let name = &rest[1..end];
+                    if name.is_empty()
+                        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        return Err(invalid("variable names must be alphanumeric"));
+                    }
+                    source.push_str(&format!("(?P<{}>[^.]+)", name));
+                    rest = &rest[end + 1..];
+                } else {
+                    match c {
+                        '*' => source.push_str("[^.]*"),
+                        '?' => source.push_str("[^.]"),
+                        _ => source.push_str(&regex::escape(&c.to_string())),
+                    }
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+            if !last && labels[position + 1] != "**" {
+                source.push_str(r"\.");
+            }
+        }
+        source.push('$');
+
+        let regex = Regex::new(&source).map_err(|err| invalid(&err.to_string()))?;
+        Ok(Self { pattern: pattern.to_string(), regex })
+    }
+
+    /// The lowercased host when the pattern has no wildcards or variables.
+    pub fn literal(&self) -> Option<String> {
+        let literal = !self.pattern.contains(['*', '?', '{']);
+        literal.then(|| self.pattern.to_ascii_lowercase())
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct HostPredicate {
-    pub patterns: Vec<String>,
+    pub patterns: Vec<HostPattern>,
+}
+
+impl HostPredicate {
+    pub fn new(patterns: &[String]) -> Result<Self, GatewayError> {
+        let patterns = patterns.iter().map(|pattern| HostPattern::parse(pattern));
+        Ok(Self { patterns: patterns.collect::<Result<_, _>>()? })
+    }
+
+    /// Lowercased hosts when every pattern is a plain host name, so the route
+    /// can be looked up by exact host instead of being tried for every request.
+    pub fn literal_hosts(&self) -> Option<Vec<String>> {
+        self.patterns.iter().map(HostPattern::literal).collect()
+    }
 }
 
 impl<T> Evaluable<T> for HostPredicate {
     fn evaluate(&self, request: &Request<T>) -> bool {
-        self.patterns.iter().any(|pattern| {
-            let re = Regex::new(&pattern.replace("**", ".*")).unwrap();
-            re.is_match(request.uri().host().unwrap())
-        })
+        let Some(host) = request_host(request) else { return false };
+        self.patterns.iter().any(|pattern| pattern.regex.is_match(host))
+    }
+
+    fn capture(&self, request: &Request<T>, variables: &mut UriTemplateVariables) {
+        let Some(host) = request_host(request) else { return };
+        let Some((pattern, captures)) =
+            self.patterns.iter().find_map(|pattern| Some((pattern, pattern.regex.captures(host)?)))
+        else {
+            return;
+        };
+        for name in pattern.regex.capture_names().flatten() {
+            if let Some(value) = captures.name(name) {
+                variables.0.insert(name.to_string(), value.as_str().to_string());
+            }
+        }
+    }
+}
+
+/// Host the client addressed, from the `Host` header or else the request
+/// URI authority (absolute-form and HTTP/2 `:authority`), without the port.
+pub fn request_host<T>(request: &Request<T>) -> Option<&str> {
+    let Some(header) = request.headers().get(hyper::header::HOST) else {
+        return request.uri().host();
+    };
+    let authority = header.to_str().ok()?;
+    let host = if authority.starts_with('[') {
+        // IPv6 literal, keep the brackets like `Uri::host` does
+        &authority[..=authority.find(']')?]
+    } else {
+        authority.split(':').next()?
+    };
+    Some(host)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(host: &str) -> Request<()> {
+        Request::builder().header("host", host).body(()).unwrap()
+    }
+
+    fn predicate(patterns: &[&str]) -> HostPredicate {
+        HostPredicate::new(&patterns.iter().map(|pattern| pattern.to_string()).collect::<Vec<_>>())
+            .unwrap()
+    }
+
+    #[test]
+    fn matches_host_templates_label_by_label() {
+        let single = predicate(&["*.example.org"]);
+        assert!(single.evaluate(&request("api.example.org")));
+        assert!(single.evaluate(&request("API.Example.ORG")));
+        assert!(!single.evaluate(&request("a.b.example.org")));
+        assert!(!single.evaluate(&request("example.org")));
+        assert!(!single.evaluate(&request("api.example.org.evil.com")));
+
+        let any = predicate(&["**.example.org"]);
+        assert!(any.evaluate(&request("example.org")));
+        assert!(any.evaluate(&request("a.b.example.org")));
+        assert!(!any.evaluate(&request("badexample.org")));
+
+        let middle = predicate(&["api.**.internal"]);
+        assert!(middle.evaluate(&request("api.internal")));
+        assert!(middle.evaluate(&request("api.eu.west.internal")));
+
+        let partial = predicate(&["web-?.example.org", "static.example.org"]);
+        assert!(partial.evaluate(&request("web-1.example.org")));
+        assert!(!partial.evaluate(&request("web-12.example.org")));
+        assert!(partial.evaluate(&request("static.example.org")));
+        assert!(!partial.evaluate(&request("staticXexample.org")));
+    }
+
+    #[test]
+    fn captures_variables_from_the_first_matching_pattern() {
+        let predicate = predicate(&["{sub}.api.example.org", "{tenant}.{region}.example.org"]);
+        let mut variables = UriTemplateVariables::default();
+        predicate.capture(&request("shop.eu.example.org:8443"), &mut variables);
+        assert_eq!(variables.0.len(), 2);
+        assert_eq!(variables.0["tenant"], "shop");
+        assert_eq!(variables.0["region"], "eu");
+
+        let mut variables = UriTemplateVariables::default();
+        predicate.capture(&request("v2.api.example.org"), &mut variables);
+        assert_eq!(variables.0.len(), 1);
+        assert_eq!(variables.0["sub"], "v2");
+    }
+
+    #[test]
+    fn rejects_malformed_patterns_and_spots_literal_hosts() {
+        for pattern in ["", "{sub.example.org", "{}.example.org", "{a-b}.example.org"] {
+            assert!(HostPattern::parse(pattern).is_err(), "{}", pattern);
+        }
+        assert_eq!(
+            predicate(&["Api.Example.org"]).literal_hosts(),
+            Some(vec!["api.example.org".to_string()])
+        );
+        assert_eq!(predicate(&["api.example.org", "*.example.org"]).literal_hosts(), None);
+    }
+
+    #[test]
+    fn strips_the_port_from_the_request_host() {
+        assert_eq!(request_host(&request("example.org:8080")), Some("example.org"));
+        assert_eq!(request_host(&request("example.org")), Some("example.org"));
+        assert_eq!(request_host(&request("[::1]:8080")), Some("[::1]"));
+        assert_eq!(request_host(&request("[::1]")), Some("[::1]"));
+        assert_eq!(request_host(&request("[::1")), None);
+
+        let absolute = Request::builder().uri("http://example.org:8080/path").body(()).unwrap();
+        assert_eq!(request_host(&absolute), Some("example.org"));
+        assert_eq!(request_host(&Request::new(())), None);
+
+        // The Host header wins over the request target
+        let mut both = request("example.net:80");
+        *both.uri_mut() = "http://example.org/".parse().unwrap();
+        assert_eq!(request_host(&both), Some("example.net"));
+        assert!(predicate(&["example.net"]).evaluate(&both));
     }
 }
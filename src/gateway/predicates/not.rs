@@ -0,0 +1,48 @@
+use hyper::Request;
+
+use super::{Evaluable, Predicate};
+
+/// Inverts the nested predicate.
+#[derive(Clone, Debug)]
+pub struct NotPredicate {
+    pub predicate: Box<Predicate>,
+}
+
+impl<T> Evaluable<T> for NotPredicate {
+    fn evaluate(&self, request: &Request<T>) -> bool {
+        !self.predicate.evaluate(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::predicates::any_of::AnyOfPredicate;
+    use crate::gateway::predicates::method::MethodPredicate;
+
+    fn method(method: &str) -> Predicate {
+        Predicate::Method(MethodPredicate { method: method.to_string() })
+    }
+
+    fn request(method: &str) -> Request<()> {
+        Request::builder().method(method).body(()).unwrap()
+    }
+
+    #[test]
+    fn inverts_the_nested_predicate() {
+        let predicate = NotPredicate { predicate: Box::new(method("GET")) };
+        assert!(!predicate.evaluate(&request("GET")));
+        assert!(predicate.evaluate(&request("POST")));
+
+        let neither = NotPredicate {
+            predicate: Box::new(Predicate::AnyOf(AnyOfPredicate {
+                predicates: vec![method("GET"), method("HEAD")],
+            })),
+        };
+        assert!(!neither.evaluate(&request("HEAD")));
+        assert!(neither.evaluate(&request("DELETE")));
+
+        let twice = NotPredicate { predicate: Box::new(Predicate::Not(predicate)) };
+        assert!(twice.evaluate(&request("GET")));
+    }
+}
@@ -1,13 +1,175 @@
-use crate::gateway::predicates::Evaluable;
+use crate::gateway::errors::GatewayError;
+use crate::gateway::predicates::{Evaluable, UriTemplateVariables};
 use hyper::Request;
 
+/// One `/`-separated piece of a path pattern.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    Literal(String),
+    /// `{name}`: exactly one non-empty segment.
+    Variable(String),
+    /// `*`: exactly one non-empty segment.
+    Wildcard,
+    /// `**` or `{*name}`: zero or more trailing segments.
+    CatchAll(Option<String>),
+}
+
+/// Spring-style path pattern such as `/red/{segment}` or `/static/**`.
+#[derive(Clone, Debug)]
+pub struct PathPattern {
+    segments: Vec<PathSegment>,
+}
+
+impl PathPattern {
+    pub fn parse(pattern: &str) -> Result<Self, GatewayError> {
+        let invalid = |reason: &str| {
+            GatewayError::InvalidConfig(format!("invalid path pattern '{}': {}", pattern, reason))
+        };
+        let rest = pattern.strip_prefix('/').ok_or_else(|| invalid("must start with '/'"))?;
+
+        let mut segments = Vec::new();
+        for raw in rest.split('/') {
+            if matches!(segments.last(), Some(PathSegment::CatchAll(_))) {
+                return Err(invalid("catch-all must be the last segment"));
+            }
+            let segment = match raw {
+                "*" => PathSegment::Wildcard,
+                "**" => PathSegment::CatchAll(None),
+                _ => match raw.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+                    Some(name) => match name.strip_prefix('*') {
+                        Some("") => return Err(invalid("empty variable name")),
+                        Some(name) => PathSegment::CatchAll(Some(name.to_string())),
+                        None if name.is_empty() => return Err(invalid("empty variable name")),
+                        None => PathSegment::Variable(name.to_string()),
+                    },
+                    None => PathSegment::Literal(raw.to_string()),
+                },
+            };
+            segments.push(segment);
+        }
+        Ok(Self { segments })
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    pub fn matches(&self, path: &str) -> bool {
+        self.walk(path, None)
+    }
+
+    /// Adds the `{name}` and `{*name}` values of a matching path to `variables`.
+    /// A catch-all keeps its leading `/`, as in Spring.
+    pub fn capture(&self, path: &str, variables: &mut UriTemplateVariables) -> bool {
+        self.walk(path, Some(variables))
+    }
+
+    fn walk(&self, path: &str, mut variables: Option<&mut UriTemplateVariables>) -> bool {
+        let Some(rest) = path.strip_prefix('/') else { return false };
+        let mut parts = rest.split('/');
+        for (position, segment) in self.segments.iter().enumerate() {
+            if let PathSegment::CatchAll(name) = segment {
+                if let (Some(name), Some(variables)) = (name, variables) {
+                    let tail = rest.splitn(position + 1, '/').nth(position).unwrap_or("");
+                    let tail = if tail.is_empty() { String::new() } else { format!("/{}", tail) };
+                    variables.0.insert(name.clone(), tail);
+                }
+                return true;
+            }
+            let Some(part) = parts.next() else { return false };
+            let matched = match segment {
+                PathSegment::Literal(literal) => literal == part,
+                _ => !part.is_empty(),
+            };
+            if !matched {
+                return false;
+            }
+            if let (PathSegment::Variable(name), Some(variables)) = (segment, variables.as_mut()) {
+                variables.0.insert(name.clone(), part.to_string());
+            }
+        }
+        parts.next().is_none()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct PathPredicate {
-    pub path: String,
+    path: String,
+    /// Only set for `pattern: true`, so existing routes whose paths happen
+    /// to contain `*` or `{` keep matching exactly.
+    pattern: Option<PathPattern>,
+}
+
+impl PathPredicate {
+    pub fn new(path: &str, pattern: bool) -> Result<Self, GatewayError> {
+        let pattern = if pattern { Some(PathPattern::parse(path)?) } else { None };
+        Ok(Self { path: path.to_string(), pattern })
+    }
+
+    /// The segments the route index files this predicate under. A literal
+    /// path is all literal segments; one without a leading `/` has none.
+    pub fn segments(&self) -> Option<Vec<PathSegment>> {
+        match &self.pattern {
+            Some(pattern) => Some(pattern.segments().to_vec()),
+            None => self.path.strip_prefix('/').map(|rest| {
+                rest.split('/').map(|part| PathSegment::Literal(part.to_string())).collect()
+            }),
+        }
+    }
 }
 
 impl<T> Evaluable<T> for PathPredicate {
     fn evaluate(&self, request: &Request<T>) -> bool {
-        request.uri().path() == self.path
+        match &self.pattern {
+            Some(pattern) => pattern.matches(request.uri().path()),
+            None => request.uri().path() == self.path,
+        }
+    }
+
+    fn capture(&self, request: &Request<T>, variables: &mut UriTemplateVariables) {
+        if let Some(pattern) = &self.pattern {
+            pattern.capture(request.uri().path(), variables);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(path: &str) -> Request<()> {
+        Request::builder().uri(path).body(()).unwrap()
+    }
+
+    #[test]
+    fn literal_paths_match_exactly() {
+        let predicate = PathPredicate::new("/static/**", false).unwrap();
+        assert!(predicate.evaluate(&request("/static/**")));
+        assert!(!predicate.evaluate(&request("/static/app.js")));
+
+        let predicate = PathPredicate::new("/items/{id}", false).unwrap();
+        assert!(!predicate.evaluate(&request("/items/42")));
+        assert!(!predicate.evaluate(&request("/items/")));
+    }
+
+    #[test]
+    fn patterns_match_and_capture() {
+        let predicate = PathPredicate::new("/items/{id}/**", true).unwrap();
+        assert!(predicate.evaluate(&request("/items/42")));
+        assert!(predicate.evaluate(&request("/items/42/a/b")));
+        assert!(!predicate.evaluate(&request("/items//a")));
+
+        let mut variables = UriTemplateVariables::default();
+        PathPredicate::new("/files/{*rest}", true)
+            .unwrap()
+            .capture(&request("/files/a/b"), &mut variables);
+        assert_eq!(variables.0["rest"], "/a/b");
+    }
+
+    #[test]
+    fn patterns_are_validated_only_when_enabled() {
+        assert!(PathPredicate::new("no-slash", false).is_ok());
+        assert!(PathPredicate::new("no-slash", true).is_err());
+        assert!(PathPredicate::new("/**/tail", true).is_err());
     }
 }
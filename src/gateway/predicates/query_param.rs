@@ -1,47 +1,78 @@
-use std::collections::HashMap;
-use regex::Regex;
+use std::borrow::Cow;
 
 use hyper::Request;
 
+use super::value_match::{Repeated, ValueMatch};
 use super::Evaluable;
 
+/// Matches a query parameter after `application/x-www-form-urlencoded`
+/// decoding, so `+`, percent-escapes and `=` inside values are handled.
 #[derive(Clone, Debug)]
 pub struct QueryParamPredicate {
     pub param: String,
-    pub value: Regex,
+    pub matcher: ValueMatch,
+    pub repeated: Repeated,
 }
 
 impl QueryParamPredicate {
-    pub fn new(param: String, value: String) -> Self {
-        Self {
-            param,
-            value: Regex::new(&value).unwrap(), // Compile regex once
-        }
+    pub fn new(param: String, matcher: ValueMatch, repeated: Repeated) -> Self {
+        Self { param, matcher, repeated }
     }
 }
 
 impl<T> Evaluable<T> for QueryParamPredicate {
     fn evaluate(&self, request: &Request<T>) -> bool {
-        if let Some(query_str) = request.uri().query() {
-            let query_params = parse_query_params(query_str);
-            if let Some(param_value) = query_params.get(self.param.as_str()) {
-                return self.value.is_match(param_value);
-            }
-        }
-        false
+        let query = request.uri().query().unwrap_or("");
+        let values: Vec<Cow<'_, str>> = form_urlencoded::parse(query.as_bytes())
+            .filter(|(key, _)| *key == self.param)
+            .map(|(_, value)| value)
+            .collect();
+        self.matcher.matches(values.iter().map(|value| Some(value.as_ref())), self.repeated)
     }
 }
 
-fn parse_query_params(query: &str) -> HashMap<&str, &str> {
-    query
-        .split('&')
-        .filter_map(|pair| {
-            let mut iter = pair.split('=');
-            if let (Some(key), Some(value)) = (iter.next(), iter.next()) {
-                Some((key.trim(), value.trim()))
-            } else {
-                None
-            }
-        })
-        .collect()
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(query: &str) -> Request<()> {
+        Request::builder().uri(format!("/search{}", query)).body(()).unwrap()
+    }
+
+    fn predicate(matcher: ValueMatch, repeated: Repeated) -> QueryParamPredicate {
+        QueryParamPredicate::new("q".to_string(), matcher, repeated)
+    }
+
+    fn exact(value: &str) -> ValueMatch {
+        ValueMatch::Exact { value: value.to_string(), ignore_case: false }
+    }
+
+    #[test]
+    fn decodes_values_before_matching() {
+        let spaced = predicate(exact("red shoes"), Repeated::Any);
+        assert!(spaced.evaluate(&request("?q=red+shoes")));
+        assert!(spaced.evaluate(&request("?q=red%20shoes")));
+        assert!(predicate(exact("a=b"), Repeated::Any).evaluate(&request("?q=a=b")));
+        assert!(predicate(exact("a&b"), Repeated::Any).evaluate(&request("?q=a%26b")));
+        assert!(predicate(exact("x"), Repeated::Any).evaluate(&request("?%71=x")));
+    }
+
+    #[test]
+    fn checks_presence_without_values() {
+        let present = predicate(ValueMatch::Present, Repeated::Any);
+        assert!(present.evaluate(&request("?q")));
+        assert!(present.evaluate(&request("?q=")));
+        assert!(!present.evaluate(&request("?query=1")));
+        assert!(!present.evaluate(&request("")));
+        assert!(predicate(ValueMatch::Absent, Repeated::Any).evaluate(&request("?p=q")));
+        assert!(predicate(exact(""), Repeated::Any).evaluate(&request("?q")));
+    }
+
+    #[test]
+    fn checks_repeated_keys_by_any_or_all() {
+        let repeated = request("?q=a&p=b&q=b");
+        assert!(predicate(exact("b"), Repeated::Any).evaluate(&repeated));
+        assert!(!predicate(exact("b"), Repeated::All).evaluate(&repeated));
+        assert!(predicate(exact("b"), Repeated::All).evaluate(&request("?q=b&q=b")));
+    }
+}
@@ -0,0 +1,61 @@
+use regex::Regex;
+
+/// How a predicate compares the values it finds under one name in a request.
+#[derive(Clone, Debug)]
+pub enum ValueMatch {
+    Present,
+    Absent,
+    Exact { value: String, ignore_case: bool },
+    Prefix { value: String, ignore_case: bool },
+    Regex(Regex),
+}
+
+/// Which of several values of a repeated header or parameter must match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeated {
+    Any,
+    All,
+}
+
+impl ValueMatch {
+    /// Checks every value found under one name. `None` stands for a value
+    /// that could not be decoded, which never matches.
+    pub fn matches<'a>(
+        &self,
+        values: impl Iterator<Item = Option<&'a str>>,
+        repeated: Repeated,
+    ) -> bool {
+        let mut values = values.peekable();
+        match self {
+            ValueMatch::Present => values.peek().is_some(),
+            ValueMatch::Absent => values.peek().is_none(),
+            _ if values.peek().is_none() => false,
+            _ => {
+                let mut results = values.map(|value| value.is_some_and(|v| self.matches_value(v)));
+                match repeated {
+                    Repeated::Any => results.any(|matched| matched),
+                    Repeated::All => results.all(|matched| matched),
+                }
+            }
+        }
+    }
+
+    fn matches_value(&self, candidate: &str) -> bool {
+        match self {
+            ValueMatch::Present => true,
+            ValueMatch::Absent => false,
+            ValueMatch::Exact { value, ignore_case: false } => candidate == value,
+            ValueMatch::Exact { value, ignore_case: true } => candidate.eq_ignore_ascii_case(value),
+            ValueMatch::Prefix { value, ignore_case } => {
+                candidate.get(..value.len()).is_some_and(|head| {
+                    if *ignore_case {
+                        head.eq_ignore_ascii_case(value)
+                    } else {
+                        head == value
+                    }
+                })
+            }
+            ValueMatch::Regex(regex) => regex.is_match(candidate),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use hyper::Request;
+
+use super::cookie::cookie_values;
+use super::{Evaluable, Predicate};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::route::Route;
+
+/// Random value drawn once per request and shared by every weight group.
+///
+/// The responder inserts it before matching, so all routes of a group roll
+/// the same number and at most one of them wins.
+#[derive(Clone, Copy, Debug)]
+pub struct WeightRoll(pub u64);
+
+impl WeightRoll {
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+}
+
+/// Request attribute that keeps a client on the same route of a group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StickyKey {
+    Cookie(String),
+    Header(String),
+}
+
+impl StickyKey {
+    fn extract<'a, T>(&'a self, request: &'a Request<T>) -> Option<&'a str> {
+        match self {
+            StickyKey::Header(name) => request.headers().get(name.as_str())?.to_str().ok(),
+            StickyKey::Cookie(name) => cookie_values(request.headers(), name).next(),
+        }
+    }
+}
+
+/// All routes sharing a group name, laid out as cumulative weight ranges.
+#[derive(Debug, Default)]
+pub struct WeightGroup {
+    /// Route id and the exclusive upper bound of its range.
+    members: Vec<(String, u64)>,
+    total: u64,
+    sticky: Option<StickyKey>,
+}
+
+impl WeightGroup {
+    fn select<T>(&self, name: &str, request: &Request<T>) -> Option<&str> {
+        if self.total == 0 {
+            return None;
+        }
+        let hash = match self.sticky.as_ref().and_then(|key| key.extract(request)) {
+            Some(value) => fnv1a(&[value.as_bytes(), name.as_bytes()]),
+            None => {
+                let roll = request.extensions().get::<WeightRoll>()?;
+                fnv1a(&[&roll.0.to_le_bytes(), name.as_bytes()])
+            }
+        };
+        let point = hash % self.total;
+        self.members.iter().find(|(_, upper)| point < *upper).map(|(id, _)| id.as_str())
+    }
+}
+
+/// Matches when this route is the one picked for its weight group.
+///
+/// Only meaningful after [`link_weight_groups`] has run over the route table;
+/// an unlinked predicate never matches.
+#[derive(Clone, Debug)]
+pub struct WeightPredicate {
+    pub group: String,
+    pub weight: u32,
+    pub sticky: Option<StickyKey>,
+    route_id: String,
+    members: Arc<WeightGroup>,
+}
+
+impl WeightPredicate {
+    pub fn new(group: String, weight: u32, sticky: Option<StickyKey>) -> Self {
+        Self { group, weight, sticky, route_id: String::new(), members: Arc::default() }
+    }
+}
+
+impl<T> Evaluable<T> for WeightPredicate {
+    fn evaluate(&self, request: &Request<T>) -> bool {
+        self.members.select(&self.group, request) == Some(self.route_id.as_str())
+    }
+}
+
+/// Connects every `Weight` predicate to the other routes of its group.
+///
+/// Has to run whenever the route table is built or replaced, since weights
+/// are only meaningful relative to the rest of the group.
+pub fn link_weight_groups(routes: &mut [Route]) -> Result<(), GatewayError> {
+    let mut groups: HashMap<String, WeightGroup> = HashMap::new();
+    for route in routes.iter() {
+        let mut pending: Vec<&Predicate> = route.predicates.iter().collect();
+        while let Some(predicate) = pending.pop() {
+            pending.extend(predicate.children());
+            let Predicate::Weight(weight) = predicate else { continue };
+            let group = groups.entry(weight.group.clone()).or_default();
+            if group.members.iter().any(|(id, _)| *id == route.id) {
+                return Err(GatewayError::InvalidConfig(format!(
+                    "route '{}' joins weight group '{}' more than once",
+                    route.id, weight.group
+                )));
+            }
+            group.total += u64::from(weight.weight);
+            group.members.push((route.id.clone(), group.total));
+            match (&group.sticky, &weight.sticky) {
+                (None, Some(key)) => group.sticky = Some(key.clone()),
+                (Some(existing), Some(key)) if existing != key => {
+                    return Err(GatewayError::InvalidConfig(format!(
+                        "weight group '{}' declares conflicting sticky keys",
+                        weight.group
+                    )));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let groups: HashMap<String, Arc<WeightGroup>> =
+        groups.into_iter().map(|(name, group)| (name, Arc::new(group))).collect();
+    for route in routes.iter_mut() {
+        let mut pending: Vec<&mut Predicate> = route.predicates.iter_mut().collect();
+        while let Some(predicate) = pending.pop() {
+            if let Predicate::Weight(weight) = predicate {
+                weight.route_id = route.id.clone();
+                weight.members = groups[&weight.group].clone();
+            } else {
+                pending.extend(predicate.children_mut());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// FNV-1a, so sticky assignments stay stable across restarts and replicas.
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::predicates::all_of::AllOfPredicate;
+    use crate::gateway::route::Destination;
+
+    fn route(id: &str, predicates: Vec<Predicate>) -> Route {
+        Route {
+            id: id.to_string(),
+            order: 0,
+            predicates,
+            filters: Vec::new(),
+            destination: Destination::Upstream("http://localhost".to_string()),
+        }
+    }
+
+    fn weighted(id: &str, weight: u32, sticky: Option<StickyKey>) -> Route {
+        route(
+            id,
+            vec![Predicate::Weight(WeightPredicate::new("canary".to_string(), weight, sticky))],
+        )
+    }
+
+    fn winners<T>(routes: &[Route], request: &Request<T>) -> Vec<String> {
+        routes.iter().filter(|route| route.matches(request)).map(|route| route.id.clone()).collect()
+    }
+
+    #[test]
+    fn exactly_one_route_wins_each_roll_in_proportion() {
+        let mut routes = vec![weighted("stable", 3, None), weighted("canary", 1, None)];
+        link_weight_groups(&mut routes).unwrap();
+
+        let mut canary = 0;
+        for roll in 0..4000u64 {
+            let mut request = Request::new(());
+            request.extensions_mut().insert(WeightRoll(roll.wrapping_mul(0x9e37_79b9_7f4a_7c15)));
+            let winners = winners(&routes, &request);
+            assert_eq!(winners.len(), 1);
+            canary += usize::from(winners[0] == "canary");
+        }
+        assert!((800..1200).contains(&canary), "canary won {} of 4000", canary);
+    }
+
+    #[test]
+    fn unrolled_requests_and_empty_groups_match_nothing() {
+        let mut routes = vec![weighted("stable", 3, None), weighted("off", 0, None)];
+        link_weight_groups(&mut routes).unwrap();
+        assert!(winners(&routes, &Request::new(())).is_empty());
+
+        let mut routes = vec![weighted("off", 0, None)];
+        link_weight_groups(&mut routes).unwrap();
+        let mut request = Request::new(());
+        request.extensions_mut().insert(WeightRoll(7));
+        assert!(winners(&routes, &request).is_empty());
+    }
+
+    #[test]
+    fn sticky_keys_pin_a_client_regardless_of_the_roll() {
+        let sticky = Some(StickyKey::Cookie("session".to_string()));
+        let mut routes = vec![weighted("stable", 1, sticky.clone()), weighted("canary", 1, None)];
+        link_weight_groups(&mut routes).unwrap();
+
+        let mut seen = Vec::new();
+        for session in 0..32 {
+            let cookie = format!("theme=dark; session=client-{}", session);
+            let mut first = None;
+            for roll in 0..8 {
+                let mut request = Request::builder().header("cookie", &cookie).body(()).unwrap();
+                request.extensions_mut().insert(WeightRoll(roll));
+                let winners = winners(&routes, &request);
+                assert_eq!(winners.len(), 1);
+                assert_eq!(*first.get_or_insert(winners[0].clone()), winners[0]);
+            }
+            seen.extend(first);
+        }
+        assert!(seen.iter().any(|id| id == "stable") && seen.iter().any(|id| id == "canary"));
+
+        let header = StickyKey::Header("x-user".to_string());
+        let request = Request::builder().header("x-user", "alice").body(()).unwrap();
+        assert_eq!(header.extract(&request), Some("alice"));
+        assert_eq!(header.extract(&Request::new(())), None);
+    }
+
+    #[test]
+    fn rejects_conflicting_sticky_keys_and_repeated_members() {
+        let mut routes = vec![
+            weighted("stable", 1, Some(StickyKey::Cookie("session".to_string()))),
+            weighted("canary", 1, Some(StickyKey::Header("x-user".to_string()))),
+        ];
+        assert!(link_weight_groups(&mut routes).is_err());
+
+        let weight = || Predicate::Weight(WeightPredicate::new("canary".to_string(), 1, None));
+        let nested = Predicate::AllOf(AllOfPredicate { predicates: vec![weight()] });
+        let mut routes = vec![route("twice", vec![weight(), nested])];
+        assert!(link_weight_groups(&mut routes).is_err());
+    }
+}
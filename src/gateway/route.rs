@@ -1,19 +1,80 @@
-use crate::gateway::filters::Filter;
+use bytes::Bytes;
+use http::{HeaderMap, Response, StatusCode};
+
+use crate::gateway::bodies::{single_chunk_response_body, BoxBody};
+use crate::gateway::filters::{Cors, Filter, RequestId};
 use crate::gateway::Predicate;
-use crate::gateway::predicates::Evaluable;
+use crate::gateway::predicates::{Evaluable, UriTemplateVariables};
 
 use hyper::Request;
 
+/// Id of the route that matched a request, stored in its extensions.
+#[derive(Clone, Debug)]
+pub struct RouteId(pub String);
+
 #[derive(Clone, Debug)]
 pub struct Route {
     pub id: String,
+    pub order: i32,
     pub predicates: Vec<Predicate>,
     pub filters: Vec<Filter>,
-    pub destination: String,
+    pub destination: Destination,
+}
+
+/// Where a matched, filtered request is answered.
+#[derive(Clone, Debug)]
+pub enum Destination {
+    /// Forwarded to this upstream URI.
+    Upstream(String),
+    /// Answered by the gateway itself.
+    Static(StaticResponse),
+}
+
+/// Fixed status, headers and body, e.g. for maintenance pages or `robots.txt`.
+#[derive(Clone, Debug)]
+pub struct StaticResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl StaticResponse {
+    pub fn respond(&self) -> Response<BoxBody> {
+        let mut response = Response::new(single_chunk_response_body(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        response
+    }
 }
 
 impl Route {
     pub fn matches<T>(&self, request: &Request<T>) -> bool {
         self.predicates.iter().all(|predicate| predicate.evaluate(request))
     }
-}
\ No newline at end of file
+
+    /// The route's CORS policy, which answers its preflights.
+    pub fn cors(&self) -> Option<&Cors> {
+        self.filters.iter().find_map(|filter| match filter {
+            Filter::Cors(cors) => Some(cors),
+            _ => None,
+        })
+    }
+
+    /// The route's `RequestId` filter, which runs ahead of the others.
+    pub fn request_id(&self) -> Option<&RequestId> {
+        self.filters.iter().find_map(|filter| match filter {
+            Filter::RequestId(request_id) => Some(request_id),
+            _ => None,
+        })
+    }
+
+    /// Template variables captured by this route's predicates from a request
+    /// it matches.
+    pub fn template_variables<T>(&self, request: &Request<T>) -> UriTemplateVariables {
+        let mut variables = UriTemplateVariables::default();
+        for predicate in &self.predicates {
+            predicate.capture(request, &mut variables);
+        }
+        variables
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::str::Split;
+
+use hyper::Request;
+
+use crate::gateway::access_log::AccessLog;
+use crate::gateway::config::Config;
+use crate::gateway::filters::RequestId;
+use crate::gateway::predicates::host::request_host;
+use crate::gateway::predicates::path::PathSegment;
+use crate::gateway::predicates::Predicate;
+use crate::gateway::route::Route;
+use crate::gateway::trace::Tracer;
+
+/// Routes in match order together with a precompiled index over them.
+///
+/// The index only narrows down candidates from their top-level `Path` and
+/// `Host` predicates; every candidate still runs its full predicate list, so
+/// lookups behave exactly like a scan in route order.
+#[derive(Debug, Default)]
+pub struct RouteTable {
+    routes: Vec<Route>,
+    index: RouteIndex,
+    /// Assigns ids to requests that match no route, from the `RequestId`
+    /// default filter.
+    pub request_id: Option<RequestId>,
+    pub access_log: Option<AccessLog>,
+    pub tracer: Option<Tracer>,
+    /// The config the table was built from, as the admin API shows it.
+    pub config: Config,
+}
+
+impl RouteTable {
+    /// Stable-sorts routes by `order` and builds the index.
+    pub fn new(mut routes: Vec<Route>) -> Self {
+        routes.sort_by_key(|route| route.order);
+        let index = RouteIndex::build(&routes);
+        Self {
+            routes,
+            index,
+            request_id: None,
+            access_log: None,
+            tracer: None,
+            config: Config::default(),
+        }
+    }
+
+    /// First route in order whose predicates all match the request.
+    pub fn find<T>(&self, request: &Request<T>) -> Option<&Route> {
+        let host_routes =
+            request_host(request).and_then(|host| self.index.hosts.get(&host.to_ascii_lowercase()));
+        // Every route sits in exactly one candidate list and each list is in
+        // route order, so the first match of each list below the best so far
+        // is all that needs checking.
+        let mut best: Option<usize> = None;
+        let mut consider = |positions: &[usize]| {
+            for &position in positions {
+                if best.is_some_and(|best| position >= best) {
+                    return;
+                }
+                let reachable = !self.index.host_indexed[position]
+                    || host_routes.is_some_and(|routes| routes.binary_search(&position).is_ok());
+                if reachable && self.routes[position].matches(request) {
+                    best = Some(position);
+                    return;
+                }
+            }
+        };
+        if let Some(path) = request.uri().path().strip_prefix('/') {
+            self.index.paths.visit(path.split('/'), &mut consider);
+        }
+        consider(&self.index.any_path);
+        best.map(|position| &self.routes[position])
+    }
+}
+
+#[derive(Debug, Default)]
+struct RouteIndex {
+    paths: PathNode,
+    /// Routes without a top-level `Path` predicate, or with a literal one
+    /// not starting with `/`.
+    any_path: Vec<usize>,
+    /// Routes by exact lowercased host, in route order.
+    hosts: HashMap<String, Vec<usize>>,
+    /// Whether a route is reachable only through `hosts`.
+    host_indexed: Vec<bool>,
+}
+
+impl RouteIndex {
+    fn build(routes: &[Route]) -> Self {
+        let mut index = Self::default();
+        for (position, route) in routes.iter().enumerate() {
+            let path = route.predicates.iter().find_map(|predicate| match predicate {
+                Predicate::Path(path) => Some(path.segments()),
+                _ => None,
+            });
+            match path.flatten() {
+                Some(segments) => index.paths.insert(&segments, position),
+                None => index.any_path.push(position),
+            }
+
+            let hosts = route.predicates.iter().find_map(|predicate| match predicate {
+                Predicate::Host(host) => host.literal_hosts(),
+                _ => None,
+            });
+            index.host_indexed.push(hosts.is_some());
+            for host in hosts.into_iter().flatten() {
+                let positions = index.hosts.entry(host).or_default();
+                if positions.last() != Some(&position) {
+                    positions.push(position);
+                }
+            }
+        }
+        index
+    }
+}
+
+/// Radix trie over path segments.
+#[derive(Debug, Default)]
+struct PathNode {
+    literals: HashMap<String, PathNode>,
+    /// Child for `{name}` and `*` segments.
+    single: Option<Box<PathNode>>,
+    /// Routes whose pattern ends at this node.
+    exact: Vec<usize>,
+    /// Routes whose pattern ends with a catch-all at this node.
+    catch_all: Vec<usize>,
+}
+
+impl PathNode {
+    fn insert(&mut self, segments: &[PathSegment], position: usize) {
+        let Some((segment, rest)) = segments.split_first() else {
+            self.exact.push(position);
+            return;
+        };
+        match segment {
+            PathSegment::Literal(literal) => {
+                self.literals.entry(literal.clone()).or_default().insert(rest, position)
+            }
+            PathSegment::Variable(_) | PathSegment::Wildcard => {
+                self.single.get_or_insert_with(Default::default).insert(rest, position)
+            }
+            PathSegment::CatchAll(_) => self.catch_all.push(position),
+        }
+    }
+
+    /// Calls `visit` with every list of routes whose pattern may match the
+    /// remaining path `parts`.
+    fn visit<F: FnMut(&[usize])>(&self, mut parts: Split<'_, char>, visit: &mut F) {
+        visit(&self.catch_all);
+        let Some(part) = parts.next() else {
+            visit(&self.exact);
+            return;
+        };
+        if let Some(child) = self.literals.get(part) {
+            child.visit(parts.clone(), visit);
+        }
+        if let Some(child) = self.single.as_ref().filter(|_| !part.is_empty()) {
+            child.visit(parts, visit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gateway::config_loader::build_routes;
+
+    /// Builds a table from YAML route entries, each given as a flow mapping
+    /// without its `destination`.
+    fn table(routes: &[&str]) -> RouteTable {
+        let routes: Vec<String> = routes
+            .iter()
+            .map(|route| format!("  - {{destination: http://localhost:9000, {}}}", route))
+            .collect();
+        let config = serde_yaml::from_str(&format!("routes:\n{}", routes.join("\n"))).unwrap();
+        RouteTable::new(build_routes(config).unwrap())
+    }
+
+    fn request(method: &str, host: Option<&str>, path: &str) -> Request<()> {
+        let mut request = Request::builder().method(method).uri(path);
+        if let Some(host) = host {
+            request = request.header("host", host);
+        }
+        request.body(()).unwrap()
+    }
+
+    /// Id of the route the table finds, checked against a plain scan.
+    fn found<'a>(table: &'a RouteTable, request: &Request<()>) -> Option<&'a str> {
+        let found = table.find(request).map(|route| route.id.as_str());
+        let scanned = table.routes.iter().find(|route| route.matches(request));
+        assert_eq!(found, scanned.map(|route| route.id.as_str()), "{:?}", request);
+        found
+    }
+
+    #[test]
+    fn keeps_route_order_across_indexed_and_unindexed_routes() {
+        let table = table(&[
+            "id: any, order: 5, predicates: [{type: Method, method: GET}]",
+            "id: catch, predicates: [{type: Path, path: '/items/**', pattern: true}]",
+            "id: special, predicates: [{type: Path, path: /items/special}]",
+            "id: reviews, order: -1, predicates: [{type: Path, path: '/items/{id}/reviews', pattern: true}]",
+            "id: orders, predicates: [{type: Path, path: /orders}]",
+            "id: closed, order: -5, predicates: [{type: Header, header: x-maintenance}]",
+        ]);
+        let get = |path| request("GET", None, path);
+
+        // Equal orders keep file order, whichever index holds the route
+        assert_eq!(found(&table, &get("/items/special")), Some("catch"));
+        assert_eq!(found(&table, &get("/items/1/reviews")), Some("reviews"));
+        assert_eq!(found(&table, &get("/orders")), Some("orders"));
+        assert_eq!(found(&table, &get("/users")), Some("any"));
+        assert_eq!(found(&table, &request("POST", None, "/users")), None);
+
+        let mut closed = get("/orders");
+        closed.headers_mut().insert("x-maintenance", "1".parse().unwrap());
+        assert_eq!(found(&table, &closed), Some("closed"));
+    }
+
+    #[test]
+    fn looks_up_literal_hosts_and_tries_host_templates() {
+        let routes = [
+            "id: exact, predicates: [{type: Host, patterns: [api.example.org, API.example.net]}]",
+            "id: tenant, predicates: [{type: Host, patterns: ['{sub}.example.org']}]",
+        ];
+        let indexed = table(&routes);
+        let get = |host| request("GET", Some(host), "/");
+        assert_eq!(found(&indexed, &get("API.Example.org:8080")), Some("exact"));
+        assert_eq!(found(&indexed, &get("api.example.net")), Some("exact"));
+        assert_eq!(found(&indexed, &get("shop.example.org")), Some("tenant"));
+        assert_eq!(found(&indexed, &get("example.org")), None);
+        assert_eq!(
+            found(&indexed, &request("GET", None, "http://api.example.org/")),
+            Some("exact")
+        );
+        assert_eq!(found(&indexed, &request("GET", None, "/")), None);
+
+        // A template ahead in order wins even for a host that is indexed
+        let reordered = table(&[&format!("order: 1, {}", routes[0]), routes[1]]);
+        assert_eq!(found(&reordered, &get("api.example.org")), Some("tenant"));
+    }
+
+    #[test]
+    fn tells_root_and_trailing_slashes_apart() {
+        let table = table(&[
+            "id: root, predicates: [{type: Path, path: /}]",
+            "id: items, predicates: [{type: Path, path: /items}]",
+            "id: slash, predicates: [{type: Path, path: /items/}]",
+            "id: docs, predicates: [{type: Path, path: '/docs/**', pattern: true}]",
+            "id: named, predicates: [{type: Path, path: '/{name}', pattern: true}]",
+        ]);
+        let get = |path| request("GET", None, path);
+        assert_eq!(found(&table, &get("/")), Some("root"));
+        assert_eq!(found(&table, &get("/items")), Some("items"));
+        assert_eq!(found(&table, &get("/items/")), Some("slash"));
+        assert_eq!(found(&table, &get("/items//")), None);
+        assert_eq!(found(&table, &get("/docs")), Some("docs"));
+        assert_eq!(found(&table, &get("/docs/")), Some("docs"));
+        assert_eq!(found(&table, &get("/docs/a/b")), Some("docs"));
+        assert_eq!(found(&table, &get("/users")), Some("named"));
+        assert_eq!(found(&table, &get("/users/")), None);
+    }
+
+    #[test]
+    fn tries_routes_without_a_path_for_every_request() {
+        let table = table(&[
+            "id: relative, predicates: [{type: Path, path: relative}]",
+            "id: posts, predicates: [{type: Method, method: POST}]",
+            "id: items, predicates: [{type: Path, path: /items}]",
+            "id: weighted, predicates: [{type: Weight, group: all, weight: 1}]",
+        ]);
+        assert_eq!(table.index.any_path, [0, 1, 3]);
+        assert_eq!(found(&table, &request("POST", None, "/items")), Some("posts"));
+        assert_eq!(found(&table, &request("GET", None, "/items")), Some("items"));
+        assert_eq!(found(&table, &request("GET", None, "/relative")), None);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::gateway::errors::GatewayError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Variable(String),
+}
+
+/// A string with `{name}` placeholders, parsed once at config load.
+#[derive(Clone, Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, GatewayError> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').map(|end| start + end).ok_or_else(|| {
+                GatewayError::InvalidConfig(format!("unclosed '{{' in template '{}'", template))
+            })?;
+            let name = &rest[start + 1..end];
+            if name.is_empty() {
+                return Err(GatewayError::InvalidConfig(format!(
+                    "empty variable name in template '{}'",
+                    template
+                )));
+            }
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            parts.push(Part::Variable(name.to_string()));
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Substitutes every placeholder using `lookup`, or returns the name of
+    /// the first variable it could not resolve.
+    pub fn render(&self, lookup: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Variable(name) => {
+                    rendered.push_str(&lookup(name).ok_or_else(|| name.clone())?)
+                }
+            }
+        }
+        Ok(rendered)
+    }
+}
@@ -0,0 +1,404 @@
+pub mod context;
+pub mod export;
+
+use std::mem;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use http::header::USER_AGENT;
+use http::{HeaderMap, Request};
+use tokio::sync::mpsc;
+
+pub use context::{inject, Propagation, SpanContext, SpanId, TraceId};
+pub use export::ExportSettings;
+
+use crate::gateway::errors::GatewayError;
+
+/// Starts spans and hands finished, sampled ones to the exporter.
+#[derive(Clone, Debug)]
+pub struct Tracer {
+    inner: Arc<TracerInner>,
+}
+
+#[derive(Debug)]
+struct TracerInner {
+    /// Share of new traces that are recorded.
+    sample_ratio: f64,
+    propagation: Vec<Propagation>,
+    spans: mpsc::Sender<SpanData>,
+}
+
+impl Tracer {
+    /// Starts the exporter task. Must be called from within the runtime.
+    pub fn new(
+        settings: ExportSettings,
+        sample_ratio: f64,
+        propagation: Vec<Propagation>,
+    ) -> Result<Self, GatewayError> {
+        if !(0.0..=1.0).contains(&sample_ratio) {
+            return Err(GatewayError::InvalidConfig(format!(
+                "sample_ratio {} is not between 0 and 1",
+                sample_ratio
+            )));
+        }
+        let spans = export::spawn(settings)?;
+        Ok(Self { inner: Arc::new(TracerInner { sample_ratio, propagation, spans }) })
+    }
+
+    /// The span for a request the gateway received, continuing the caller's
+    /// trace when it sent one.
+    pub fn server_span<B>(&self, req: &Request<B>, remote_addr: SocketAddr) -> Span {
+        let parent = self.inner.propagation.iter().find_map(|format| format.extract(req.headers()));
+        let parent_span_id = parent.as_ref().map(|parent| parent.span_id);
+        let context = match parent {
+            Some(parent) => SpanContext {
+                trace_id: parent.trace_id,
+                span_id: SpanId::random(),
+                sampled: parent.sampled.unwrap_or_else(|| self.sample(parent.trace_id)),
+                trace_state: parent.trace_state,
+            },
+            None => {
+                let trace_id = TraceId::random();
+                let sampled = self.sample(trace_id);
+                SpanContext { trace_id, span_id: SpanId::random(), sampled, trace_state: None }
+            }
+        };
+        let mut span =
+            Span::start(self, context, parent_span_id, req.method().as_str(), SpanKind::Server);
+        span.set_attribute("http.request.method", req.method().as_str());
+        span.set_attribute("url.path", req.uri().path());
+        if let Some(query) = req.uri().query() {
+            span.set_attribute("url.query", query);
+        }
+        span.set_attribute("client.address", remote_addr.ip().to_string());
+        span.set_attribute("network.protocol.version", format!("{:?}", req.version()));
+        if let Some(agent) = req.headers().get(USER_AGENT).and_then(|agent| agent.to_str().ok()) {
+            span.set_attribute("user_agent.original", agent);
+        }
+        span
+    }
+
+    /// Decides by the trace id, so every gateway instance with the same ratio
+    /// agrees on a trace.
+    fn sample(&self, trace_id: TraceId) -> bool {
+        if self.inner.sample_ratio >= 1.0 {
+            return true;
+        }
+        let mut low = [0; 8];
+        low.copy_from_slice(&trace_id.0[8..]);
+        (u64::from_be_bytes(low) as f64) < self.inner.sample_ratio * u64::MAX as f64
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum SpanKind {
+    Internal,
+    Server,
+    Client,
+}
+
+#[derive(Clone, Debug)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(value: &str) -> Self {
+        AttributeValue::String(value.to_string())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(value: String) -> Self {
+        AttributeValue::String(value)
+    }
+}
+
+impl From<i64> for AttributeValue {
+    fn from(value: i64) -> Self {
+        AttributeValue::Int(value)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(value: bool) -> Self {
+        AttributeValue::Bool(value)
+    }
+}
+
+/// A finished span as it is exported.
+#[derive(Debug)]
+pub struct SpanData {
+    pub context: SpanContext,
+    pub parent_span_id: Option<SpanId>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub attributes: Vec<(&'static str, AttributeValue)>,
+    /// Set when the operation failed.
+    pub error: Option<String>,
+}
+
+/// An operation in progress. It ends when dropped and is exported then if
+/// its trace is sampled.
+#[derive(Debug)]
+pub struct Span {
+    tracer: Tracer,
+    data: SpanData,
+}
+
+impl Span {
+    fn start(
+        tracer: &Tracer,
+        context: SpanContext,
+        parent_span_id: Option<SpanId>,
+        name: &str,
+        kind: SpanKind,
+    ) -> Self {
+        let now = SystemTime::now();
+        let data = SpanData {
+            context,
+            parent_span_id,
+            name: name.to_string(),
+            kind,
+            start: now,
+            end: now,
+            attributes: Vec::new(),
+            error: None,
+        };
+        Self { tracer: tracer.clone(), data }
+    }
+
+    /// Lets other parts of the request start children of this span.
+    pub fn scope(&self) -> TraceScope {
+        TraceScope { tracer: self.tracer.clone(), context: self.data.context.clone() }
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.data.name = name.into();
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: impl Into<AttributeValue>) {
+        self.data.attributes.push((key, value.into()));
+    }
+
+    pub fn set_error(&mut self, message: impl Into<String>) {
+        self.data.error = Some(message.into());
+    }
+
+    /// Sets this span as the parent in the headers of an outgoing request,
+    /// replacing any trace headers already there.
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        inject(&self.tracer.inner.propagation, &self.data.context, headers);
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if !self.data.context.sampled {
+            return;
+        }
+        let data = SpanData {
+            context: self.data.context.clone(),
+            parent_span_id: self.data.parent_span_id,
+            name: mem::take(&mut self.data.name),
+            kind: self.data.kind,
+            start: self.data.start,
+            end: SystemTime::now(),
+            attributes: mem::take(&mut self.data.attributes),
+            error: self.data.error.take(),
+        };
+        // Full or closed; spans are dropped rather than slowing requests
+        let _ = self.tracer.inner.spans.try_send(data);
+    }
+}
+
+/// The span a request is in, kept in its extensions so routing, filters and
+/// the upstream call can add theirs beneath it.
+#[derive(Clone, Debug)]
+pub struct TraceScope {
+    tracer: Tracer,
+    context: SpanContext,
+}
+
+impl TraceScope {
+    pub fn child(&self, name: &str, kind: SpanKind) -> Span {
+        let context = SpanContext { span_id: SpanId::random(), ..self.context.clone() };
+        Span::start(&self.tracer, context, Some(self.context.span_id), name, kind)
+    }
+}
+
+/// A child of the request's span, if the request is traced.
+pub fn child_span<B>(req: &Request<B>, name: &str, kind: SpanKind) -> Option<Span> {
+    req.extensions().get::<TraceScope>().map(|scope| scope.child(name, kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use http::{HeaderName, HeaderValue, Response};
+    use http_body_util::{BodyExt, Full};
+    use hyper::body::Incoming;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use serde_json::Value;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const PARENT_ID: &str = "00f067aa0ba902b7";
+
+    /// Headers and JSON body of each export request.
+    type Batches = Arc<Mutex<Vec<(HeaderMap, Value)>>>;
+
+    /// Accepts OTLP/HTTP exports and keeps them for inspection.
+    async fn collector() -> (String, Batches) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/v1/traces", listener.local_addr().unwrap());
+        let batches = Batches::default();
+        let received = batches.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let batches = received.clone();
+                let service = service_fn(move |req: Request<Incoming>| {
+                    let batches = batches.clone();
+                    async move {
+                        let headers = req.headers().clone();
+                        let body = req.into_body().collect().await.unwrap().to_bytes();
+                        batches
+                            .lock()
+                            .unwrap()
+                            .push((headers, serde_json::from_slice(&body).unwrap()));
+                        Ok::<_, Infallible>(Response::new(Full::new(Bytes::from("{}"))))
+                    }
+                });
+                tokio::spawn(http1::Builder::new().serve_connection(TokioIo::new(stream), service));
+            }
+        });
+        (endpoint, batches)
+    }
+
+    fn settings(endpoint: &str) -> ExportSettings {
+        ExportSettings {
+            endpoint: endpoint.to_string(),
+            service_name: "edge".to_string(),
+            headers: vec![(HeaderName::from_static("x-api-key"), HeaderValue::from_static("k"))],
+            batch_size: 2,
+            flush_interval: Duration::from_millis(50),
+            max_queue: 16,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    fn tracer(endpoint: &str, sample_ratio: f64) -> Tracer {
+        let propagation = vec![Propagation::W3C, Propagation::B3];
+        Tracer::new(settings(endpoint), sample_ratio, propagation).unwrap()
+    }
+
+    fn request(headers: &[(&'static str, &str)]) -> Request<()> {
+        let mut req = Request::get("/orders?page=2");
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        req.body(()).unwrap()
+    }
+
+    fn remote() -> SocketAddr {
+        "10.1.2.3:40000".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn honours_the_callers_sampling_decision() {
+        let never = tracer("http://127.0.0.1:9/", 0.0);
+        let always = tracer("http://127.0.0.1:9/", 1.0);
+        let sampled = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        let unsampled = format!("00-{}-{}-00", TRACE_ID, PARENT_ID);
+
+        let span = never.server_span(&request(&[("traceparent", &sampled)]), remote());
+        assert!(span.data.context.sampled);
+        assert_eq!(span.data.context.trace_id.to_hex(), TRACE_ID);
+        assert_eq!(span.data.parent_span_id.unwrap().to_hex(), PARENT_ID);
+        let span = always.server_span(&request(&[("traceparent", &unsampled)]), remote());
+        assert!(!span.data.context.sampled);
+
+        // Without a decision from the caller the ratio applies
+        let undecided = format!("{}-{}", TRACE_ID, PARENT_ID);
+        assert!(!never.server_span(&request(&[("b3", &undecided)]), remote()).data.context.sampled);
+        assert!(always.server_span(&request(&[("b3", &undecided)]), remote()).data.context.sampled);
+        assert!(!never.server_span(&request(&[]), remote()).data.context.sampled);
+        assert!(always.server_span(&request(&[]), remote()).data.context.sampled);
+        assert!(Tracer::new(settings("http://127.0.0.1:9/"), 1.5, Vec::new()).is_err());
+    }
+
+    #[tokio::test]
+    async fn exports_sampled_spans_in_batches() {
+        let (endpoint, batches) = collector().await;
+        let tracer = tracer(&endpoint, 1.0);
+        let traceparent = format!("00-{}-{}-00", TRACE_ID, PARENT_ID);
+        // An unsampled trace is never exported
+        drop(tracer.server_span(&request(&[("traceparent", &traceparent)]), remote()));
+
+        let traceparent = format!("00-{}-{}-01", TRACE_ID, PARENT_ID);
+        let headers = [("traceparent", traceparent.as_str()), ("tracestate", "vendor=abc")];
+        let server = tracer.server_span(&request(&headers), remote());
+        let server_id = server.data.context.span_id;
+        let mut client = server.scope().child("upstream", SpanKind::Client);
+        client.set_attribute("http.response.status_code", 502i64);
+        client.set_error("connection refused");
+        let mut outgoing = HeaderMap::new();
+        client.inject(&mut outgoing);
+        assert_eq!(
+            outgoing["traceparent"],
+            format!("00-{}-{}-01", TRACE_ID, client.data.context.span_id.to_hex())
+        );
+        assert_eq!(outgoing["tracestate"], "vendor=abc");
+        drop(client);
+        drop(server);
+
+        for _ in 0..200 {
+            if !batches.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let batches = batches.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        let (headers, body) = &batches[0];
+        assert_eq!(headers["x-api-key"], "k");
+        assert_eq!(headers["content-type"], "application/json");
+
+        let resource = &body["resourceSpans"][0];
+        assert_eq!(resource["resource"]["attributes"][0]["value"]["stringValue"], "edge");
+        let spans = resource["scopeSpans"][0]["spans"].as_array().unwrap();
+        assert_eq!(spans.len(), 2);
+        let (client, server) = (&spans[0], &spans[1]);
+        assert!(spans.iter().all(|span| span["traceId"] == TRACE_ID));
+        assert!(spans.iter().all(|span| span["traceState"] == "vendor=abc"));
+        assert_eq!(server["name"], "GET");
+        assert_eq!(server["kind"], 2);
+        assert_eq!(server["parentSpanId"], PARENT_ID);
+        assert_eq!(client["kind"], 3);
+        assert_eq!(client["parentSpanId"], server_id.to_hex());
+        assert_eq!(client["status"]["code"], 2);
+        assert_eq!(client["status"]["message"], "connection refused");
+        let status = &client["attributes"][0];
+        assert_eq!(status["key"], "http.response.status_code");
+        assert_eq!(status["value"]["intValue"], "502");
+        let attributes = server["attributes"].as_array().unwrap();
+        assert!(attributes
+            .iter()
+            .any(|a| a["key"] == "url.query" && a["value"]["stringValue"] == "page=2"));
+    }
+}
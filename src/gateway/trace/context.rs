@@ -0,0 +1,325 @@
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+const B3: HeaderName = HeaderName::from_static("b3");
+const B3_TRACE_ID: HeaderName = HeaderName::from_static("x-b3-traceid");
+const B3_SPAN_ID: HeaderName = HeaderName::from_static("x-b3-spanid");
+const B3_PARENT_SPAN_ID: HeaderName = HeaderName::from_static("x-b3-parentspanid");
+const B3_SAMPLED: HeaderName = HeaderName::from_static("x-b3-sampled");
+const B3_FLAGS: HeaderName = HeaderName::from_static("x-b3-flags");
+
+/// Every header a propagation format uses, cleared before injecting so an
+/// upstream never sees two different contexts.
+const PROPAGATION_HEADERS: [HeaderName; 8] =
+    [TRACEPARENT, TRACESTATE, B3, B3_TRACE_ID, B3_SPAN_ID, B3_PARENT_SPAN_ID, B3_SAMPLED, B3_FLAGS];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceId(pub [u8; 16]);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpanId(pub [u8; 8]);
+
+impl TraceId {
+    pub fn random() -> Self {
+        loop {
+            let id = Self(rand::random());
+            if id.0 != [0; 16] {
+                return id;
+            }
+        }
+    }
+
+    /// 32 lowercase hex digits; B3's 64-bit ids are left-padded with zeros.
+    fn parse(hex: &str) -> Option<Self> {
+        let padded = match hex.len() {
+            16 => format!("{:0>32}", hex),
+            32 => hex.to_string(),
+            _ => return None,
+        };
+        let id = Self(decode_hex(&padded)?);
+        (id.0 != [0; 16]).then_some(id)
+    }
+
+    pub fn to_hex(self) -> String {
+        encode_hex(&self.0)
+    }
+}
+
+impl SpanId {
+    pub fn random() -> Self {
+        loop {
+            let id = Self(rand::random());
+            if id.0 != [0; 8] {
+                return id;
+            }
+        }
+    }
+
+    fn parse(hex: &str) -> Option<Self> {
+        let id = Self(decode_hex(hex)?);
+        (id.0 != [0; 8]).then_some(id)
+    }
+
+    pub fn to_hex(self) -> String {
+        encode_hex(&self.0)
+    }
+}
+
+fn decode_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+    {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (byte, pair) in bytes.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// What identifies a span across processes.
+#[derive(Clone, Debug)]
+pub struct SpanContext {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    pub sampled: bool,
+    /// Vendor data from `tracestate`, passed on unchanged.
+    pub trace_state: Option<String>,
+}
+
+/// The caller's span, as read from the request headers.
+#[derive(Clone, Debug)]
+pub struct RemoteParent {
+    pub trace_id: TraceId,
+    pub span_id: SpanId,
+    /// `None` when the caller left the decision to the gateway.
+    pub sampled: Option<bool>,
+    pub trace_state: Option<String>,
+}
+
+/// A way of carrying the span context in HTTP headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Propagation {
+    W3C,
+    B3,
+    B3Multi,
+}
+
+impl Propagation {
+    /// The caller's span, or `None` when the headers are missing or
+    /// malformed.
+    pub fn extract(self, headers: &HeaderMap) -> Option<RemoteParent> {
+        let header = |name| headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok());
+        match self {
+            Propagation::W3C => {
+                let mut parent = parse_traceparent(header(TRACEPARENT)?)?;
+                parent.trace_state =
+                    header(TRACESTATE).map(str::trim).filter(|s| !s.is_empty()).map(String::from);
+                Some(parent)
+            }
+            Propagation::B3 => parse_b3(header(B3)?),
+            Propagation::B3Multi => {
+                let sampled = match (header(B3_FLAGS), header(B3_SAMPLED)) {
+                    (Some("1"), _) => Some(true),
+                    (_, None) => None,
+                    (_, Some(sampled)) => Some(matches!(sampled, "1" | "true")),
+                };
+                Some(RemoteParent {
+                    trace_id: TraceId::parse(header(B3_TRACE_ID)?)?,
+                    span_id: SpanId::parse(header(B3_SPAN_ID)?)?,
+                    sampled,
+                    trace_state: None,
+                })
+            }
+        }
+    }
+
+    fn inject(self, context: &SpanContext, headers: &mut HeaderMap) {
+        let trace_id = context.trace_id.to_hex();
+        let span_id = context.span_id.to_hex();
+        let sampled = if context.sampled { "1" } else { "0" };
+        let mut set = |name: HeaderName, value: String| {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        };
+        match self {
+            Propagation::W3C => {
+                set(TRACEPARENT, format!("00-{}-{}-0{}", trace_id, span_id, sampled));
+                if let Some(state) = &context.trace_state {
+                    set(TRACESTATE, state.clone());
+                }
+            }
+            Propagation::B3 => set(B3, format!("{}-{}-{}", trace_id, span_id, sampled)),
+            Propagation::B3Multi => {
+                set(B3_TRACE_ID, trace_id);
+                set(B3_SPAN_ID, span_id);
+                set(B3_SAMPLED, sampled.to_string());
+            }
+        }
+    }
+}
+
+/// Replaces whatever trace headers the request had with `context` in each
+/// of the formats.
+pub fn inject(propagation: &[Propagation], context: &SpanContext, headers: &mut HeaderMap) {
+    for name in PROPAGATION_HEADERS {
+        headers.remove(name);
+    }
+    for format in propagation {
+        format.inject(context, headers);
+    }
+}
+
+/// `version-traceid-parentid-flags`; later versions may append fields.
+fn parse_traceparent(value: &str) -> Option<RemoteParent> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    let version = decode_hex::<1>(parts[0])?[0];
+    let valid_length = if version == 0 { parts.len() == 4 } else { parts.len() >= 4 };
+    if version == 0xff || !valid_length {
+        return None;
+    }
+    let flags = decode_hex::<1>(parts[3])?[0];
+    Some(RemoteParent {
+        trace_id: TraceId::parse(parts[1]).filter(|_| parts[1].len() == 32)?,
+        span_id: SpanId::parse(parts[2])?,
+        sampled: Some(flags & 1 == 1),
+        trace_state: None,
+    })
+}
+
+/// `traceid-spanid[-sampled[-parentspanid]]`. A lone sampling decision
+/// carries no context to join.
+fn parse_b3(value: &str) -> Option<RemoteParent> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    if parts.len() < 2 {
+        return None;
+    }
+    Some(RemoteParent {
+        trace_id: TraceId::parse(parts[0])?,
+        span_id: SpanId::parse(parts[1])?,
+        sampled: parts.get(2).map(|sampled| matches!(*sampled, "1" | "d")),
+        trace_state: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+    const SPAN_ID: &str = "00f067aa0ba902b7";
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (HeaderName::from_static(name), value.parse().unwrap()))
+            .collect()
+    }
+
+    fn extract(format: Propagation, pairs: &[(&'static str, &str)]) -> Option<RemoteParent> {
+        format.extract(&headers(pairs))
+    }
+
+    #[test]
+    fn parses_traceparent_and_tracestate() {
+        let traceparent = format!("00-{}-{}-01", TRACE_ID, SPAN_ID);
+        let parent = extract(
+            Propagation::W3C,
+            &[("traceparent", &traceparent), ("tracestate", " vendor=abc,other=1 ")],
+        )
+        .unwrap();
+        assert_eq!(parent.trace_id.to_hex(), TRACE_ID);
+        assert_eq!(parent.span_id.to_hex(), SPAN_ID);
+        assert_eq!(parent.sampled, Some(true));
+        assert_eq!(parent.trace_state.as_deref(), Some("vendor=abc,other=1"));
+
+        let unsampled = format!("00-{}-{}-00", TRACE_ID, SPAN_ID);
+        let parent = extract(Propagation::W3C, &[("traceparent", &unsampled)]).unwrap();
+        assert_eq!(parent.sampled, Some(false));
+        assert_eq!(parent.trace_state, None);
+        // Later versions may add fields
+        let future = format!("01-{}-{}-01-extra", TRACE_ID, SPAN_ID);
+        assert!(extract(Propagation::W3C, &[("traceparent", &future)]).is_some());
+    }
+
+    #[test]
+    fn rejects_malformed_traceparents() {
+        let zeros = "0".repeat(32);
+        let invalid = [
+            format!("ff-{}-{}-01", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}-01-extra", TRACE_ID, SPAN_ID),
+            format!("00-{}-{}-01", zeros, SPAN_ID),
+            format!("00-{}-{}-01", TRACE_ID, "0".repeat(16)),
+            format!("00-{}-{}-01", TRACE_ID.to_uppercase(), SPAN_ID),
+            format!("00-{}-{}-01", &TRACE_ID[16..], SPAN_ID),
+            format!("00-{}-01", TRACE_ID),
+            "garbage".to_string(),
+        ];
+        for traceparent in invalid {
+            let parent = extract(Propagation::W3C, &[("traceparent", &traceparent)]);
+            assert!(parent.is_none(), "accepted {}", traceparent);
+        }
+    }
+
+    #[test]
+    fn parses_b3_single_and_multi() {
+        let single = format!("{}-{}-d", TRACE_ID, SPAN_ID);
+        let parent = extract(Propagation::B3, &[("b3", &single)]).unwrap();
+        assert_eq!(parent.trace_id.to_hex(), TRACE_ID);
+        assert_eq!(parent.sampled, Some(true));
+
+        let short = format!("{}-{}", &TRACE_ID[16..], SPAN_ID);
+        let parent = extract(Propagation::B3, &[("b3", &short)]).unwrap();
+        assert_eq!(parent.trace_id.to_hex(), format!("{:0>32}", &TRACE_ID[16..]));
+        assert_eq!(parent.sampled, None);
+        assert!(extract(Propagation::B3, &[("b3", "0")]).is_none());
+
+        let multi = |sampled: &str, flags: &str| {
+            let mut pairs = vec![("x-b3-traceid", TRACE_ID), ("x-b3-spanid", SPAN_ID)];
+            if !sampled.is_empty() {
+                pairs.push(("x-b3-sampled", sampled));
+            }
+            if !flags.is_empty() {
+                pairs.push(("x-b3-flags", flags));
+            }
+            extract(Propagation::B3Multi, &pairs).unwrap().sampled
+        };
+        assert_eq!(multi("1", ""), Some(true));
+        assert_eq!(multi("true", ""), Some(true));
+        assert_eq!(multi("0", ""), Some(false));
+        assert_eq!(multi("0", "1"), Some(true));
+        assert_eq!(multi("", ""), None);
+        assert!(extract(Propagation::B3Multi, &[("x-b3-traceid", TRACE_ID)]).is_none());
+    }
+
+    #[test]
+    fn injects_in_every_format_and_replaces_old_headers() {
+        let context = SpanContext {
+            trace_id: TraceId::parse(TRACE_ID).unwrap(),
+            span_id: SpanId::parse(SPAN_ID).unwrap(),
+            sampled: false,
+            trace_state: Some("vendor=abc".to_string()),
+        };
+        let mut headers = headers(&[("x-b3-parentspanid", SPAN_ID), ("x-b3-flags", "1")]);
+        let formats = [Propagation::W3C, Propagation::B3, Propagation::B3Multi];
+        inject(&formats, &context, &mut headers);
+
+        assert_eq!(headers["traceparent"], format!("00-{}-{}-00", TRACE_ID, SPAN_ID));
+        assert_eq!(headers["tracestate"], "vendor=abc");
+        assert_eq!(headers["b3"], format!("{}-{}-0", TRACE_ID, SPAN_ID));
+        assert_eq!(headers["x-b3-sampled"], "0");
+        assert!(!headers.contains_key("x-b3-parentspanid"));
+        assert!(!headers.contains_key("x-b3-flags"));
+        for format in formats {
+            let parent = format.extract(&headers).unwrap();
+            assert_eq!((parent.trace_id, parent.span_id), (context.trace_id, context.span_id));
+            assert_eq!(parent.sampled, Some(false));
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::mem;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::header::CONTENT_TYPE;
+use http::{HeaderName, HeaderValue, Request};
+use http_body_util::Full;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+
+use super::{AttributeValue, SpanData, SpanKind};
+use crate::gateway::errors::GatewayError;
+use crate::gateway::http_client;
+
+/// Where and how finished spans are sent.
+#[derive(Clone, Debug)]
+pub struct ExportSettings {
+    /// OTLP/HTTP traces URL.
+    pub endpoint: String,
+    pub service_name: String,
+    pub headers: Vec<(HeaderName, HeaderValue)>,
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    pub max_queue: usize,
+    pub timeout: Duration,
+}
+
+/// Starts the task exporting spans sent to the returned channel.
+pub fn spawn(settings: ExportSettings) -> Result<mpsc::Sender<SpanData>, GatewayError> {
+    if !settings.endpoint.starts_with("https://") && !settings.endpoint.starts_with("http://") {
+        return Err(GatewayError::InvalidConfig(format!(
+            "invalid tracing endpoint '{}'",
+            settings.endpoint
+        )));
+    }
+    let (spans, receiver) = mpsc::channel(settings.max_queue.max(1));
+    tokio::spawn(export_spans(receiver, settings));
+    Ok(spans)
+}
+
+/// Sends spans in batches of `batch_size`, or whatever has finished once
+/// `flush_interval` has passed. Ends after flushing when every tracer using
+/// it is gone, e.g. after a config reload.
+async fn export_spans(mut spans: mpsc::Receiver<SpanData>, settings: ExportSettings) {
+    let mut batch = Vec::new();
+    let mut ticker = tokio::time::interval(settings.flush_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            span = spans.recv() => {
+                let Some(span) = span else {
+                    if !batch.is_empty() {
+                        export(&settings, mem::take(&mut batch)).await;
+                    }
+                    return;
+                };
+                batch.push(span);
+                if batch.len() >= settings.batch_size.max(1) {
+                    export(&settings, mem::take(&mut batch)).await;
+                }
+            }
+            _ = ticker.tick() => {
+                if !batch.is_empty() {
+                    export(&settings, mem::take(&mut batch)).await;
+                }
+            }
+        }
+    }
+}
+
+async fn export(settings: &ExportSettings, batch: Vec<SpanData>) {
+    let count = batch.len();
+    let body = request_body(&settings.service_name, &batch).to_string();
+    let mut request = Request::post(&settings.endpoint).header(CONTENT_TYPE, "application/json");
+    for (name, value) in &settings.headers {
+        request = request.header(name, value);
+    }
+    let result = match request.body(Full::new(Bytes::from(body))) {
+        Ok(request) => http_client::send(request, settings.timeout).await.map(|_| ()),
+        Err(err) => Err(err.to_string()),
+    };
+    if let Err(err) = result {
+        eprintln!("Exporting {} spans failed: {}", count, err);
+    }
+}
+
+/// An `ExportTraceServiceRequest` in the OTLP JSON encoding.
+fn request_body(service_name: &str, batch: &[SpanData]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [{ "key": "service.name", "value": { "stringValue": service_name } }]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "cloud-gateway" },
+                "spans": batch.iter().map(span).collect::<Vec<_>>()
+            }]
+        }]
+    })
+}
+
+fn span(span: &SpanData) -> Value {
+    let mut value = json!({
+        "traceId": span.context.trace_id.to_hex(),
+        "spanId": span.context.span_id.to_hex(),
+        "name": span.name,
+        "kind": match span.kind {
+            SpanKind::Internal => 1,
+            SpanKind::Server => 2,
+            SpanKind::Client => 3,
+        },
+        "startTimeUnixNano": unix_nanos(span.start),
+        "endTimeUnixNano": unix_nanos(span.end),
+        "attributes": span.attributes.iter().map(|(key, value)| json!({
+            "key": key,
+            "value": match value {
+                AttributeValue::String(text) => json!({ "stringValue": text }),
+                // 64-bit integers are strings in OTLP JSON
+                AttributeValue::Int(number) => json!({ "intValue": number.to_string() }),
+                AttributeValue::Bool(flag) => json!({ "boolValue": flag }),
+            }
+        })).collect::<Vec<_>>(),
+        "status": match &span.error {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 0 }),
+        },
+    });
+    if let Some(parent) = span.parent_span_id {
+        value["parentSpanId"] = parent.to_hex().into();
+    }
+    if let Some(state) = &span.context.trace_state {
+        value["traceState"] = state.clone().into();
+    }
+    value
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+}
@@ -0,0 +1,3 @@
+pub mod admin;
+pub mod gateway;
+pub mod responder;
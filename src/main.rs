@@ -1,23 +1,39 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 
-use gateway::config_loader::{self, YamlConfigLoader};
+use cloud_gateway::gateway::config_loader::{self, YamlConfigLoader};
+use cloud_gateway::gateway::config_watcher::watch_config;
 use hyper::server::conn::http1;
 use hyper::service::service_fn;
 use hyper_util::rt::TokioIo;
 use tokio::net::TcpListener;
 use tokio::sync::RwLock;
 
-use gateway::route::Route;
-mod gateway;
+use cloud_gateway::admin::admin_responder;
+use cloud_gateway::gateway::metrics::METRICS;
+use cloud_gateway::gateway::route_table::RouteTable;
+use cloud_gateway::responder::responder;
 use config_loader::ConfigLoader;
-use responder::responder;
-mod responder;
+
+const CONFIG_PATH: &str = "config.yaml";
+const DEFAULT_ADMIN_ADDR: ([u8; 4], u16) = ([127, 0, 0, 1], 9090);
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let routes: Vec<Route> = YamlConfigLoader::load_config("config.yaml").await?;
-    let routes = Arc::new(RwLock::new(routes));
+    let routes: RouteTable = YamlConfigLoader::load_config(CONFIG_PATH).await?;
+    METRICS.config_loaded();
+    let admin_addr = routes.config.admin.as_ref().and_then(|admin| admin.listen);
+    let admin_addr = admin_addr.unwrap_or(SocketAddr::from(DEFAULT_ADMIN_ADDR));
+    let routes = Arc::new(RwLock::new(Arc::new(routes)));
+    tokio::spawn(watch_config::<YamlConfigLoader>(
+        CONFIG_PATH.to_string(),
+        routes.clone(),
+        CONFIG_POLL_INTERVAL,
+    ));
+    let admin_listener = TcpListener::bind(admin_addr).await?;
+    tokio::spawn(serve_admin(admin_listener, routes.clone()));
     let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
     let listener = TcpListener::bind(addr).await?;
 
@@ -27,6 +43,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let io = TokioIo::new(stream);
 
         tokio::task::spawn(async move {
+            let _connection = METRICS.client_connection();
             if let Err(err) = http1::Builder::new()
                 .serve_connection(
                     io,
@@ -39,3 +56,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         });
     }
 }
+
+/// Serves `/metrics` and the actuator endpoints on their own port so they
+/// are never routed or exposed with the proxied traffic. Route changes made
+/// there are persisted to `CONFIG_PATH` when the config asks for it.
+async fn serve_admin(listener: TcpListener, routes: Arc<RwLock<Arc<RouteTable>>>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                eprintln!("Admin listener failed to accept: {:?}", err);
+                continue;
+            }
+        };
+        let io = TokioIo::new(stream);
+        let routes = routes.clone();
+        tokio::task::spawn(async move {
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(
+                    io,
+                    service_fn(move |req| admin_responder(req, routes.clone(), CONFIG_PATH)),
+                )
+                .await
+            {
+                eprintln!("Error serving admin connection: {:?}", err);
+            }
+        });
+    }
+}
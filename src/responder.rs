@@ -1,94 +1,295 @@
+use std::sync::atomic::Ordering;
+use std::time::Instant;
 use std::{net::SocketAddr, sync::Arc};
 
-use http::{Response, StatusCode, Uri};
+use http::header::ACCESS_CONTROL_REQUEST_METHOD;
+use http::{Method, Response, StatusCode, Uri};
+use http_body_util::BodyExt;
 use hyper::{body::Incoming, Request};
 use hyper_util::rt::TokioIo;
 use tokio::{net::TcpStream, sync::RwLock};
 
 use crate::gateway::{
-    bodies::{pinned_body::box_pinned_body, single_chunk_response_body, BoxBody},
-    filters::{Filter, Filterable},
-    route::Route,
+    access_log::{AccessRecord, RequestContext, UpstreamExchange},
+    bodies::{pinned_body::box_pinned_body, single_chunk_response_body, BoxBody, CountingBody},
+    filters::{
+        is_preflight, request_id, restore_host, BufferedBody, ConcurrencyPermits, Filterable,
+        FilteredResult, OriginalUri,
+    },
+    metrics::METRICS,
+    predicates::weight::WeightRoll,
+    route::{Destination, Route, RouteId},
+    route_table::RouteTable,
+    trace::{child_span, Span, SpanKind, TraceScope},
 };
 
 /// Main service entry point for each request.
 pub async fn responder(
     mut req: Request<Incoming>,
-    routes: Arc<RwLock<Vec<Route>>>,
+    routes: Arc<RwLock<Arc<RouteTable>>>,
     remote_addr: SocketAddr,
 ) -> Result<Response<BoxBody>, hyper::Error> {
     // Optionally store remote_addr in request.extensions
     req.extensions_mut().insert(remote_addr);
+    // One roll per request keeps weight groups consistent across their routes
+    req.extensions_mut().insert(WeightRoll::random());
+    // Filters may rewrite the URI; keep the one the client sent for logging
+    let original_uri = OriginalUri(req.uri().clone());
+    req.extensions_mut().insert(original_uri);
+
+    let started = Instant::now();
+    let method = req.method().clone();
+    // The lock is only held to take the table, so reloads never wait for
+    // requests in flight
+    let table = routes.read().await.clone();
+    let span = table.tracer.as_ref().map(|tracer| tracer.server_span(&req, remote_addr));
+    if let Some(span) = &span {
+        req.extensions_mut().insert(span.scope());
+    }
+    let record = table.access_log.as_ref().map(|_| AccessRecord::new(&req, remote_addr));
+    let response = route_request(req, &table).await?;
+
+    let context = response.extensions().get::<RequestContext>();
+    let route = context.and_then(|context| context.route_id.as_deref());
+    METRICS.request(route, &method, response.status(), started.elapsed());
+    let response = match (&table.access_log, record) {
+        (Some(access_log), Some(record)) => access_log.attach(record, response),
+        _ => response,
+    };
+    Ok(match span {
+        Some(span) => end_server_span(span, &method, response),
+        None => response,
+    })
+}
+
+/// Names the request's span after its route and ends it once the response
+/// body has been sent.
+fn end_server_span(
+    mut span: Span,
+    method: &Method,
+    response: Response<BoxBody>,
+) -> Response<BoxBody> {
+    let status = response.status();
+    let context = response.extensions().get::<RequestContext>().cloned().unwrap_or_default();
+    if let Some(route) = context.route_id {
+        span.set_name(format!("{} {}", method, route));
+        span.set_attribute("http.route", route);
+    }
+    if let Some(id) = context.request_id {
+        span.set_attribute("gateway.request_id", id);
+    }
+    span.set_attribute("http.response.status_code", status.as_u16() as i64);
+    if status.is_server_error() {
+        span.set_error(status.to_string());
+    }
+    response.map(|body| -> BoxBody {
+        Box::pin(CountingBody::new(body, move |bytes| {
+            span.set_attribute("http.response.body.size", bytes as i64);
+        }))
+    })
+}
+
+/// Routes the request and runs it through its route's filters.
+async fn route_request(
+    mut req: Request<Incoming>,
+    routes_guard: &RouteTable,
+) -> Result<Response<BoxBody>, hyper::Error> {
+    // Routes often accept only the actual method, so a preflight is matched
+    // as the request it announces
+    let preflight = is_preflight(&req);
+    let mut matching = child_span(&req, "route match", SpanKind::Internal);
+    let route = if preflight {
+        routes_guard.find(&announced_request(&req))
+    } else {
+        routes_guard.find(&req)
+    };
+    if let (Some(span), Some(route)) = (&mut matching, route) {
+        span.set_attribute("gateway.route.id", route.id.clone());
+    }
+    drop(matching);
+    if let Some(route) = route {
+        let variables = route.template_variables(&req);
+        req.extensions_mut().insert(variables);
+        req.extensions_mut().insert(RouteId(route.id.clone()));
+        // Assigned up front so that every log line and error has the id
+        if let Some(filter) = route.request_id() {
+            filter.assign(&mut req);
+        }
+
+        if let Some(cors) = route.cors().filter(|_| preflight) {
+            let id = request_id(&req).to_string();
+            let mut response = with_request_id(cors.preflight(&req), &id).await;
+            response.extensions_mut().insert(RequestContext::of(&req));
+            return Ok(response);
+        }
+        let head = request_head(&req);
+        let mut context = RequestContext::of(&head);
 
-    let routes_guard = routes.read().await;
-    if let Some(route) = routes_guard.iter().find(|route| route.matches(&req)) {
         // Apply filters
-        match apply_filters(&route.filters, req).await {
-            Ok(filtered_req) => forward_request(filtered_req, &route.destination).await,
-            Err(_e) => {
-                // If filter application fails, produce 500
-                let body = single_chunk_response_body("Filter application failed");
-                Ok(Response::builder()
-                    .status(StatusCode::INTERNAL_SERVER_ERROR)
-                    .body(body)
-                    .unwrap())
+        let mut response = match apply_filters(route, req).await {
+            Ok(mut filtered_req) => {
+                // Filters may have identified the client
+                context = RequestContext::of(&filtered_req);
+                match &route.destination {
+                    Destination::Upstream(uri) => {
+                        // Concurrency slots are held until the response is sent
+                        let permits = filtered_req.extensions_mut().remove::<ConcurrencyPermits>();
+                        let name = format!("upstream {}", filtered_req.method());
+                        let mut span = child_span(&filtered_req, &name, SpanKind::Client);
+                        if let Some(span) = &span {
+                            span.inject(filtered_req.headers_mut());
+                        }
+                        let response = forward_request(filtered_req, uri).await;
+                        if let (Some(span), Ok(response)) = (&mut span, &response) {
+                            record_upstream(span, response);
+                        }
+                        match permits {
+                            Some(permits) => response.map(|r| permits.hold_until_sent(r)),
+                            None => response,
+                        }
+                    }
+                    Destination::Static(response) => Ok(response.respond()),
+                }
             }
+            // A filter answered the request itself, e.g. with an error
+            Err(response) => Ok(with_request_id(response, request_id(&head)).await),
+        };
+        if let Ok(response) = &mut response {
+            for filter in route.filters.iter().rev() {
+                filter.apply_response(&head, response);
+            }
+            response.extensions_mut().insert(context);
         }
+        response
     } else {
         // No route matched => 404
-        let body = single_chunk_response_body("No matching route found");
-        Ok(Response::builder().status(StatusCode::NOT_FOUND).body(body).unwrap())
+        if let Some(filter) = &routes_guard.request_id {
+            filter.assign(&mut req);
+        }
+        let body = tagged("No matching route found", request_id(&req));
+        let mut response = Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(single_chunk_response_body(body))
+            .unwrap();
+        if let Some(filter) = &routes_guard.request_id {
+            filter.apply_response(&request_head(&req), &mut response);
+        }
+        response.extensions_mut().insert(RequestContext::of(&req));
+        Ok(response)
+    }
+}
+
+/// Adds the request id to the text of an error the gateway produced itself.
+async fn with_request_id(response: Response<BoxBody>, id: &str) -> Response<BoxBody> {
+    let status = response.status();
+    if !(status.is_client_error() || status.is_server_error()) || id == "-" {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let text = body.collect().await.map(|body| body.to_bytes()).unwrap_or_default();
+    let text = tagged(&String::from_utf8_lossy(&text), id);
+    Response::from_parts(parts, single_chunk_response_body(text))
+}
+
+fn tagged(text: &str, id: &str) -> String {
+    if id == "-" {
+        text.to_string()
+    } else {
+        format!("{} ({})", text, id)
+    }
+}
+
+fn bad_gateway(reason: &str, id: &str) -> Response<BoxBody> {
+    Response::builder()
+        .status(StatusCode::BAD_GATEWAY)
+        .body(single_chunk_response_body(tagged(&format!("Bad Gateway: {}", reason), id)))
+        .unwrap()
+}
+
+/// Copy of the request without its body, for response filters.
+fn request_head<B>(req: &Request<B>) -> Request<()> {
+    let mut head = Request::new(());
+    *head.method_mut() = req.method().clone();
+    *head.uri_mut() = req.uri().clone();
+    *head.version_mut() = req.version();
+    *head.headers_mut() = req.headers().clone();
+    *head.extensions_mut() = req.extensions().clone();
+    head
+}
+
+/// The request a CORS preflight asks permission for.
+fn announced_request<B>(req: &Request<B>) -> Request<()> {
+    let mut announced = request_head(req);
+    let method = req.headers().get(ACCESS_CONTROL_REQUEST_METHOD);
+    if let Some(method) = method.and_then(|method| Method::from_bytes(method.as_bytes()).ok()) {
+        *announced.method_mut() = method;
+    }
+    announced
+}
+
+fn record_upstream(span: &mut Span, response: &Response<BoxBody>) {
+    if let Some(exchange) = response.extensions().get::<UpstreamExchange>() {
+        span.set_attribute("server.address", exchange.address.clone());
+    }
+    span.set_attribute("http.response.status_code", response.status().as_u16() as i64);
+    if response.status().is_server_error() {
+        span.set_error(response.status().to_string());
     }
 }
 
 /// Apply filters to the incoming request.
-async fn apply_filters(
-    filters: &[Filter],
-    mut req: Request<Incoming>,
-) -> Result<Request<Incoming>, hyper::Error> {
-    // Example: Each filter might manipulate headers, URIs, etc.
-    for filter in filters {
-        req = filter.apply(req).await?;
+async fn apply_filters(route: &Route, mut req: Request<Incoming>) -> FilteredResult {
+    let scope = req.extensions().get::<TraceScope>().cloned();
+    for filter in &route.filters {
+        let name = format!("filter {}", filter.name());
+        let mut span = scope.as_ref().map(|scope| scope.child(&name, SpanKind::Internal));
+        req = filter.apply(req).await.inspect_err(|response| {
+            METRICS.filter_response(&route.id, filter.name(), response.status());
+            if let Some(span) = &mut span {
+                span.set_attribute("http.response.status_code", response.status().as_u16() as i64);
+            }
+        })?;
     }
     Ok(req)
 }
 
 async fn forward_request(
-    req: Request<Incoming>,
+    mut req: Request<Incoming>,
     destination: &str,
 ) -> Result<Response<BoxBody>, hyper::Error> {
+    let id = request_id(&req).to_string();
+    let route = req.extensions().get::<RouteId>().map(|route| route.0.clone());
+    let bad_gateway = |reason| {
+        METRICS.upstream_error(route.as_deref(), reason);
+        bad_gateway(reason, &id)
+    };
     let uri = match destination.parse::<Uri>() {
         Ok(u) => u,
         Err(_) => {
-            return Ok(Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(single_chunk_response_body("Bad Gateway: invalid URI"))
-                .unwrap());
+            return Ok(bad_gateway("invalid URI"));
         }
     };
 
     let host = match uri.host() {
         Some(h) => h,
         None => {
-            return Ok(Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(single_chunk_response_body("Bad Gateway: missing host"))
-                .unwrap());
+            return Ok(bad_gateway("missing host"));
         }
     };
 
+    // Host goes out as the filters left it, unless PreserveHostHeader pinned it
+    restore_host(&mut req);
+
     let port = uri.port_u16().unwrap_or(80);
     let address = format!("{}:{}", host, port);
+    let exchange = UpstreamExchange::new(address.clone());
 
     // Handle I/O errors with a 502.
     let stream = match TcpStream::connect(address).await {
         Ok(s) => s,
         Err(e) => {
-            eprintln!("Connection error: {e}");
-            return Ok(Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(single_chunk_response_body("Bad Gateway: cannot connect"))
-                .unwrap());
+            eprintln!("[{id}] Connection error: {e}");
+            return Ok(exchange.attach(bad_gateway("cannot connect")));
         }
     };
 
@@ -97,34 +298,43 @@ async fn forward_request(
     let (mut sender, conn) = match hyper::client::conn::http1::handshake(io).await {
         Ok(pair) => pair,
         Err(e) => {
-            eprintln!("Handshake error: {e}");
-            return Ok(Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(single_chunk_response_body("Bad Gateway: handshake failed"))
-                .unwrap());
+            eprintln!("[{id}] Handshake error: {e}");
+            return Ok(exchange.attach(bad_gateway("handshake failed")));
         }
     };
 
     // Drive the connection in a background task
+    let conn_id = id.clone();
+    let open_connection = METRICS.upstream_connection(&exchange.address);
     tokio::task::spawn(async move {
+        let _open_connection = open_connection;
         if let Err(err) = conn.await {
-            eprintln!("Connection failed: {:?}", err);
+            eprintln!("[{conn_id}] Connection failed: {:?}", err);
         }
     });
 
+    let original_uri = req.extensions().get::<OriginalUri>().map(|uri| uri.0.clone());
+
+    // A filter that read the body leaves it behind in memory
+    let req = match req.extensions_mut().remove::<BufferedBody>() {
+        Some(BufferedBody(body)) => req.map(|_| single_chunk_response_body(body)),
+        None => req.map(box_pinned_body),
+    };
+    let bytes_sent = exchange.bytes_sent.clone();
+    let req = req.map(|body| {
+        CountingBody::new(body, move |bytes| bytes_sent.store(bytes, Ordering::Relaxed))
+    });
+
     // Send request to the remote server
     let response = match sender.send_request(req).await {
         Ok(r) => r,
         Err(e) => {
-            eprintln!("Forward request error: {e}");
-            return Ok(Response::builder()
-                .status(StatusCode::BAD_GATEWAY)
-                .body(single_chunk_response_body("Bad Gateway: request failed"))
-                .unwrap());
+            eprintln!("[{id}] Forward request error for {:?}: {e}", original_uri);
+            return Ok(exchange.attach(bad_gateway("request failed")));
         }
     };
 
     // The remote server's response body is usually `Incoming` with `Data=Bytes` and `Error=hyper::Error`.
     // Just pin it, turning it into `Box<dyn Body<...> + Send>`.
-    Ok(response.map(box_pinned_body))
+    Ok(exchange.attach(response.map(box_pinned_body)))
 }